@@ -0,0 +1,134 @@
+// P2P 消息的帧格式：4 字节大端长度 + JSON Payload
+// 默认 no_std：不碰 std::io、不分配堆内存（serde_json_core + heapless 定长缓冲区），
+// 打开 std feature 之后错误类型才能和 std::io / tokio_util 对接
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use serde::{Deserialize, Serialize};
+
+// ================= 1. 消息协议 =================
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum P2PMessage {
+    Hello { version: u32 },
+    Ping,
+    Pong,
+}
+
+/// 定长版本单帧 Payload 的最大长度，超过直接报错（也防止对方声明一个超大长度让我们一直等）
+pub const MAX_FRAME_LEN: usize = 256;
+
+/// 头部 4 字节
+pub const HEADER_LEN: usize = 4;
+
+/// 编码后的一整帧，定长缓冲区，不需要分配器
+pub type FrameBuf = heapless::Vec<u8, { HEADER_LEN + MAX_FRAME_LEN }>;
+
+// ================= 2. 错误 =================
+
+/// 编解码错误
+///
+/// 实现的是 `core::fmt::Display`，不依赖 `std::io::Error`；
+/// `Io` 变体只在 std feature 下存在，因为 tokio_util 要求 `Error: From<io::Error>`
+#[derive(Debug)]
+pub enum CodecError {
+    /// 声明的 Payload 长度超过 `MAX_FRAME_LEN`
+    FrameTooLarge(usize),
+    /// Payload 不是合法的消息 JSON
+    Deserialize(serde_json_core::de::Error),
+    /// 序列化结果放不进定长缓冲区
+    Serialize(serde_json_core::ser::Error),
+    /// 底层读写出错
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl core::fmt::Display for CodecError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CodecError::FrameTooLarge(len) => {
+                write!(f, "frame too large: {len} > {MAX_FRAME_LEN}")
+            }
+            CodecError::Deserialize(e) => write!(f, "invalid payload: {e}"),
+            CodecError::Serialize(e) => write!(f, "serialize failed: {e}"),
+            #[cfg(feature = "std")]
+            CodecError::Io(e) => write!(f, "io error: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CodecError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for CodecError {
+    fn from(e: std::io::Error) -> Self {
+        CodecError::Io(e)
+    }
+}
+
+// ================= 3. 编解码 =================
+
+/// 解析一个完整的 Payload，长度不设上限，调用方自己负责切出这一帧
+pub fn decode_payload(payload: &[u8]) -> Result<P2PMessage, CodecError> {
+    let (msg, _) = serde_json_core::from_slice(payload).map_err(CodecError::Deserialize)?;
+    Ok(msg)
+}
+
+/// 尝试从 `src` 开头切出一帧，Payload 超过 `MAX_FRAME_LEN` 直接报错
+///
+/// - `Ok(None)`：数据还不够一帧（半包），等更多数据
+/// - `Ok(Some((msg, n)))`：解出一条消息，调用方消耗掉前 `n` 个字节
+pub fn decode_frame(src: &[u8]) -> Result<Option<(P2PMessage, usize)>, CodecError> {
+    // 【守门】头部都不完整
+    let Some(header) = src.first_chunk::<HEADER_LEN>() else {
+        return Ok(None);
+    };
+
+    // 【偷看】只读长度，不消耗数据
+    let length = u32::from_be_bytes(*header) as usize;
+    if length > MAX_FRAME_LEN {
+        return Err(CodecError::FrameTooLarge(length));
+    }
+
+    // 【验货】身子还没收全
+    let Some(payload) = src.get(HEADER_LEN..HEADER_LEN + length) else {
+        return Ok(None);
+    };
+
+    Ok(Some((decode_payload(payload)?, HEADER_LEN + length)))
+}
+
+/// 从 `src` 里连续解出最多 `N` 条消息，返回解出的消息和消耗的字节数
+/// 结果放在定长的 `heapless::Vec` 里，剩下的半包留给下一次
+pub fn decode_frames<const N: usize>(
+    src: &[u8],
+) -> Result<(heapless::Vec<P2PMessage, N>, usize), CodecError> {
+    let mut msgs = heapless::Vec::new();
+    let mut consumed = 0;
+
+    while !msgs.is_full() {
+        let Some((msg, n)) = decode_frame(&src[consumed..])? else {
+            break;
+        };
+        // 上面已经检查过 is_full，这里一定放得下
+        let _ = msgs.push(msg);
+        consumed += n;
+    }
+
+    Ok((msgs, consumed))
+}
+
+/// 把一条消息编码成一整帧（头部 + Payload）
+pub fn encode_frame(item: &P2PMessage) -> Result<FrameBuf, CodecError> {
+    let mut frame = FrameBuf::new();
+    // 先把头部的位置占住，Payload 写完再回填长度
+    frame
+        .resize_default(HEADER_LEN + MAX_FRAME_LEN)
+        .expect("capacity is HEADER_LEN + MAX_FRAME_LEN");
+
+    let length =
+        serde_json_core::to_slice(item, &mut frame[HEADER_LEN..]).map_err(CodecError::Serialize)?;
+
+    frame[..HEADER_LEN].copy_from_slice(&(length as u32).to_be_bytes());
+    frame.truncate(HEADER_LEN + length);
+    Ok(frame)
+}
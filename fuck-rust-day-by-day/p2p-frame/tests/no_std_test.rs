@@ -0,0 +1,51 @@
+// 编译期检查：不打开 std feature 时，整个 crate 在 #![no_std] 下可用
+// 这个文件里用不了 std 的任何东西（Vec、String、format! 都没有），只能用 core 和 heapless
+#![no_std]
+
+use p2p_frame::{CodecError, MAX_FRAME_LEN, P2PMessage, decode_frame, decode_frames, encode_frame};
+
+#[test]
+fn test_round_trip_without_std() {
+    let msgs = [
+        P2PMessage::Hello { version: u32::MAX },
+        P2PMessage::Ping,
+        P2PMessage::Pong,
+    ];
+
+    // 三帧粘在一起，最后再粘半个包
+    let mut stream: heapless::Vec<u8, 256> = heapless::Vec::new();
+    for msg in &msgs {
+        stream
+            .extend_from_slice(&encode_frame(msg).unwrap())
+            .unwrap();
+    }
+    let half = encode_frame(&P2PMessage::Ping).unwrap();
+    stream.extend_from_slice(&half[..5]).unwrap();
+
+    // 容量只有 2：先拿到两条
+    let (decoded, consumed) = decode_frames::<2>(&stream).unwrap();
+    assert_eq!(decoded.as_slice(), &msgs[..2]);
+
+    // 剩下的：一条完整的 + 半包
+    let rest = &stream[consumed..];
+    let (decoded, consumed) = decode_frames::<8>(rest).unwrap();
+    assert_eq!(decoded.as_slice(), &msgs[2..]);
+    assert_eq!(&rest[consumed..], &half[..5]);
+}
+
+#[test]
+fn test_errors_without_std() {
+    // 声明的长度太大
+    let huge = ((MAX_FRAME_LEN + 1) as u32).to_be_bytes();
+    assert!(matches!(
+        decode_frame(&huge),
+        Err(CodecError::FrameTooLarge(len)) if len == MAX_FRAME_LEN + 1
+    ));
+
+    // Payload 不是合法消息
+    let bad = [0, 0, 0, 4, b'o', b'o', b'p', b's'];
+    assert!(matches!(
+        decode_frame(&bad),
+        Err(CodecError::Deserialize(_))
+    ));
+}
@@ -1,5 +1,12 @@
-use std::cmp::Ordering;
-use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use lru::LruCache;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tokio::sync::{mpsc, oneshot};
 
 // 模拟以太坊地址
 type Address = u64;
@@ -10,12 +17,167 @@ type Nonce = u64;
 // 模拟 Gas Price (简化为 priority fee)
 type GasPrice = u64;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
+#[serde(into = "TxEnvelope", from = "TxEnvelope")]
 pub struct Transaction {
     pub sender: Address,
     pub nonce: Nonce,
     pub gas_price: GasPrice,
     pub hash: String, // 模拟  tx hash
+    pub tx_type: TxType,
+}
+
+/// EIP-2718 交易类型，签名交易编码后的第一个字节
+///
+/// Legacy 交易没有这个字节（RLP list 的第一个字节 >= 0xc0），JSON 里约定写成 0x0
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TxType {
+    #[default]
+    Legacy = 0,
+    /// 带 access list 的交易
+    Eip2930 = 1,
+    /// base fee + 小费的交易
+    Eip1559 = 2,
+}
+
+// ================= JSON：和以太坊 RPC 的交易格式一样 =================
+// {"type":"0x2","from":"0xa","nonce":"0x1","maxPriorityFeePerGas":"0x64","hash":"..."}
+//
+// 先看 "type"，再决定其余字段怎么解析：serde 的 internally tagged enum 就是干这个的，
+// 它会先把整个对象读进缓冲区，找到 type 字段，再按对应的变体解析剩下的字段。
+// Transaction 自己不用手写 Serialize / Deserialize，into / from 转成 TxEnvelope 再交给 derive
+//
+// 简化：这里的 gas_price 其实是小费，EIP-1559 交易里对应 maxPriorityFeePerGas，
+// 另外两种叫 gasPrice；EIP-2930 的 accessList 和 1559 的 maxFeePerGas 没有建模
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum TxEnvelope {
+    #[serde(rename = "0x0", rename_all = "camelCase")]
+    Legacy {
+        from: Quantity,
+        nonce: Quantity,
+        gas_price: Quantity,
+        hash: String,
+    },
+    #[serde(rename = "0x1", rename_all = "camelCase")]
+    Eip2930 {
+        from: Quantity,
+        nonce: Quantity,
+        gas_price: Quantity,
+        hash: String,
+    },
+    #[serde(rename = "0x2", rename_all = "camelCase")]
+    Eip1559 {
+        from: Quantity,
+        nonce: Quantity,
+        max_priority_fee_per_gas: Quantity,
+        hash: String,
+    },
+}
+
+impl From<Transaction> for TxEnvelope {
+    fn from(tx: Transaction) -> Self {
+        let (from, nonce, price, hash) = (
+            Quantity(tx.sender),
+            Quantity(tx.nonce),
+            Quantity(tx.gas_price),
+            tx.hash,
+        );
+        match tx.tx_type {
+            TxType::Legacy => TxEnvelope::Legacy {
+                from,
+                nonce,
+                gas_price: price,
+                hash,
+            },
+            TxType::Eip2930 => TxEnvelope::Eip2930 {
+                from,
+                nonce,
+                gas_price: price,
+                hash,
+            },
+            TxType::Eip1559 => TxEnvelope::Eip1559 {
+                from,
+                nonce,
+                max_priority_fee_per_gas: price,
+                hash,
+            },
+        }
+    }
+}
+
+impl From<TxEnvelope> for Transaction {
+    fn from(envelope: TxEnvelope) -> Self {
+        let (tx_type, from, nonce, price, hash) = match envelope {
+            TxEnvelope::Legacy {
+                from,
+                nonce,
+                gas_price,
+                hash,
+            } => (TxType::Legacy, from, nonce, gas_price, hash),
+            TxEnvelope::Eip2930 {
+                from,
+                nonce,
+                gas_price,
+                hash,
+            } => (TxType::Eip2930, from, nonce, gas_price, hash),
+            TxEnvelope::Eip1559 {
+                from,
+                nonce,
+                max_priority_fee_per_gas,
+                hash,
+            } => (TxType::Eip1559, from, nonce, max_priority_fee_per_gas, hash),
+        };
+        Transaction {
+            sender: from.0,
+            nonce: nonce.0,
+            gas_price: price.0,
+            hash,
+            tx_type,
+        }
+    }
+}
+
+/// RPC 里的数字都是 0x 开头、没有前导 0 的十六进制字符串，比如 "0x0"、"0x1a"
+struct Quantity(u64);
+
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#x}", self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let digits = s
+            .strip_prefix("0x")
+            .ok_or_else(|| serde::de::Error::custom(format!("quantity 必须以 0x 开头: {s}")))?;
+        u64::from_str_radix(digits, 16)
+            .map(Quantity)
+            .map_err(|e| serde::de::Error::custom(format!("quantity 不是合法的十六进制 {s}: {e}")))
+    }
+}
+
+// 一笔交易的 “身份” 是 (sender, nonce)：同一个 sender 的同一个 nonce 只能有一笔上链，
+// 提高 gas_price 重新发一遍（加速 / 取消交易）换掉的就是这个位置上的旧交易。
+// 所以 Eq 和 Hash 都只看这两个字段，gas_price、hash 不一样也算同一笔，
+// 放进 HashSet 时可以用 replace 把旧的换成出价更高的新交易。
+//
+// Hash 和 Eq 必须一致：a == b 时 hash(a) == hash(b)，所以两个要一起手写，只看同样的字段
+impl PartialEq for Transaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.sender == other.sender && self.nonce == other.nonce
+    }
+}
+
+impl Hash for Transaction {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.sender.hash(state);
+        self.nonce.hash(state);
+    }
 }
 
 //=========== 核心设计：候选人凭证 ===================
@@ -26,17 +188,42 @@ struct Candidate {
     sender: Address,
     nonce: Nonce,
     gas_price: GasPrice,
+    // tx hash 字符串的前 32 个字节（不够补 0），只用来做最后的 tiebreaker
+    hash: [u8; 32],
+}
+
+impl Candidate {
+    fn of(tx: &Transaction) -> Self {
+        let mut hash = [0u8; 32];
+        let bytes = tx.hash.as_bytes();
+        let len = bytes.len().min(32);
+        hash[..len].copy_from_slice(&bytes[..len]);
+
+        Candidate {
+            sender: tx.sender,
+            nonce: tx.nonce,
+            gas_price: tx.gas_price,
+            hash,
+        }
+    }
 }
 
 // 必须实现 Ord 才能进 BinaryHeap
-// 我们希望 GasPrice 最高的排前面，如果价格一样，Nonce 小的排前面
+// 我们希望 GasPrice 最高的排前面，价格一样时 hash 字节序小的排前面
+//
+// 为什么不用 sender 做 tiebreaker：sender ID 是随便分配的，换一批 ID 出块顺序就变了；
+// hash 由交易内容决定，同样的交易池内容，不管以什么顺序 add 进来，出块顺序都一模一样
 impl Ord for Candidate {
     fn cmp(&self, other: &Self) -> Ordering {
         // 先比价格，价格高的在 Grater 堆内
         self.gas_price
             .cmp(&other.gas_price)
-            // 如果价格一样，为了确定性，我们让 Sender ID 小的排在前面，或者其他规则
+            // 大顶堆里“大”的先出，reverse 之后 hash 小的算“大”
+            .then(self.hash.cmp(&other.hash).reverse())
+            // hash 前 32 字节也一样时（模拟数据里的短 hash 可能撞），再用 sender、nonce 兜底，
+            // 保证和 derive 出来的 Eq 一致：cmp 返回 Equal 当且仅当所有字段都相等
             .then_with(|| other.sender.cmp(&self.sender))
+            .then_with(|| other.nonce.cmp(&self.nonce))
     }
 }
 
@@ -47,6 +234,63 @@ impl PartialOrd for Candidate {
     }
 }
 
+// 调试用：sender=0xA nonce=1 gas=100，hash 太长不打印
+impl std::fmt::Display for Candidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "sender={:#X} nonce={} gas={}",
+            self.sender, self.nonce, self.gas_price
+        )
+    }
+}
+
+// 每笔交易固定消耗 21000 gas（简化为普通转账）
+const TX_GAS: u64 = 21_000;
+
+/// 打包出来的区块
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub transactions: Vec<Transaction>,
+    pub total_gas: u64,
+    /// 矿工小费：每笔交易 (gas_price - base_fee) * gas 的总和，base_fee 部分被销毁
+    pub miner_tip: u64,
+}
+
+/// 打包一个区块时累计的 gas：整个区块的总量，以及每个 sender 各用了多少
+///
+/// 出块者可以限制单个 sender 的份额（比如不超过区块的 10%），
+/// 防止一个出价高的 sender 把整个区块塞满，其他人一笔都进不来
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasAccumulator {
+    per_sender: HashMap<Address, u64>,
+    total: u64,
+    block_gas_limit: u64,
+    per_sender_limit: u64,
+}
+
+impl GasAccumulator {
+    pub fn new(block_gas_limit: u64, per_sender_limit: u64) -> Self {
+        Self {
+            per_sender: HashMap::new(),
+            total: 0,
+            block_gas_limit,
+            per_sender_limit,
+        }
+    }
+
+    /// 再装一笔 `gas` 的交易，区块总量和这个 sender 的份额都不超限
+    pub fn can_include(&self, sender: Address, gas: u64) -> bool {
+        let used = self.per_sender.get(&sender).copied().unwrap_or(0);
+        self.total + gas <= self.block_gas_limit && used + gas <= self.per_sender_limit
+    }
+
+    pub fn record_inclusion(&mut self, sender: Address, gas: u64) {
+        *self.per_sender.entry(sender).or_default() += gas;
+        self.total += gas;
+    }
+}
+
 // 你的任务是填充这个结构体和实现逻辑
 pub struct BlockBuilder {
     // todo 你需要设计内部数据结构来存储待处理的交易
@@ -56,6 +300,9 @@ pub struct BlockBuilder {
 
     // 榜单：只存每个 sender 的队头交易快照，每个用户只有一笔交易在榜单中
     frontier: BinaryHeap<Candidate>,
+
+    // 活跃度：每次 sender 发来新交易就挪到最前面，驱逐时价格相同的先踢最久没动静的 sender
+    activity: LruCache<Address, ()>,
 }
 
 #[allow(dead_code)]
@@ -64,12 +311,15 @@ impl BlockBuilder {
         Self {
             pool: HashMap::new(),
             frontier: BinaryHeap::new(),
+            activity: LruCache::unbounded(),
         }
     }
 
     /// 向池子中添加一笔交易
     /// 假设所有交易都是合法的，且余额足够
     pub fn add_transaction(&mut self, tx: Transaction) {
+        self.activity.put(tx.sender, ());
+
         // 1. 先把交易存入仓库
         let sender_txs = self.pool.entry(tx.sender).or_default();
         sender_txs.insert(tx.nonce, tx.clone());
@@ -83,11 +333,7 @@ impl BlockBuilder {
 
         if let Some((&min_nonce, _)) = sender_txs.iter().next() {
             if min_nonce == tx.nonce {
-                self.frontier.push(Candidate {
-                    sender: tx.sender,
-                    nonce: tx.nonce,
-                    gas_price: tx.gas_price,
-                });
+                self.frontier.push(Candidate::of(&tx));
             }
         }
     }
@@ -97,6 +343,23 @@ impl BlockBuilder {
     /// 1. 同一个 sender 的 Nonce 必须严格递增，先出 0 才能出 1
     /// 2. 在满足 1 的前提下，优先出 GasPrice 最高的
     pub fn pop_best(&mut self) -> Option<Transaction> {
+        self.pop_best_matching(|_| true)
+    }
+
+    /// 和 pop_best 一样，但是跳过会让 sender 或整个区块超出 gas 限额的交易，换下一个候选人
+    ///
+    /// 被跳过的交易还留在池子里，下一个区块可以接着打包
+    pub fn pop_best_within(&mut self, gas: &GasAccumulator) -> Option<Transaction> {
+        self.pop_best_matching(|candidate| gas.can_include(candidate.sender, TX_GAS))
+    }
+
+    /// `accept` 返回 false 的候选人先放到一边，找到结果（或者堆空了）之后再放回榜单
+    ///
+    /// 被跳过的 sender 后面的 nonce 也不会被考虑：它们只有在队头出块之后才会进榜单
+    fn pop_best_matching(&mut self, accept: impl Fn(&Candidate) -> bool) -> Option<Transaction> {
+        let mut skipped = Vec::new();
+        let mut best = None;
+
         // 循环直到找到一个有效的交易，或者堆空了
         while let Some(candidate) = self.frontier.pop() {
             // 1。拿到 候选人信息，
@@ -108,31 +371,395 @@ impl BlockBuilder {
                 // BTreeMap first_key_value 获取最小 key
                 if let Some((&head_nonce, _)) = sender_txs.iter().next() {
                     if head_nonce == candidate.nonce {
+                        if !accept(&candidate) {
+                            skipped.push(candidate);
+                            continue;
+                        }
+
                         // 命中，这是合法的最优交易
                         // 1. 从仓库移除并取出交易
                         let tx = sender_txs.remove(&head_nonce).unwrap();
 
                         // 2. 关键一步，惰性填充
                         // 刚刚移除了 Nonce N，现在检查 Nonce N+1 是否存在
-                        if let Some((&next_nonce, next_tx)) = sender_txs.iter().next() {
+                        if let Some((_, next_tx)) = sender_txs.iter().next() {
                             // 如果存在，就把 N+1 加入榜单参与竞争
-                            self.frontier.push(Candidate {
-                                sender: next_tx.sender,
-                                nonce: next_nonce,
-                                gas_price: next_tx.gas_price,
-                            });
+                            self.frontier.push(Candidate::of(next_tx));
                         } else {
                             // 如果没交易了，清理  hashMap时里的空项
                             self.pool.remove(&candidate.sender);
+                            self.activity.pop(&candidate.sender);
                         }
 
-                        return Some(tx);
+                        best = Some(tx);
+                        break;
                     }
                 }
             }
         }
 
-        None
+        self.frontier.extend(skipped);
+        best
+    }
+
+    /// 池子里的交易总数
+    pub fn total_transaction_count(&self) -> usize {
+        self.pool.values().map(|txs| txs.len()).sum()
+    }
+
+    /// 池子超过 max_size 时驱逐交易，直到不超过 max_size
+    ///
+    /// 驱逐策略：每次踢掉所有 sender 的队尾交易（nonce 最大的那笔）里 gas_price 最低的一笔
+    /// - 只能踢队尾：踢掉中间的 nonce，后面的交易就永远执行不了了，等于白占位置
+    /// - 价格一样时，踢最久没有发过新交易的 sender；LRU 只用来决定这个先后，不直接决定踢谁，
+    ///   否则一个刚发过交易的低价 sender 会把一个很久没动静的高价 sender 挤掉
+    ///
+    /// 所有队尾放进一个小顶堆，踢掉一笔之后把这个 sender 新的队尾放回去，
+    /// 总共 O(sender 数 + 驱逐数 × log sender 数)，不用每踢一笔就扫一遍所有 sender
+    pub fn evict_oldest(&mut self, max_size: usize) {
+        let mut count = self.total_transaction_count();
+        if count <= max_size {
+            return;
+        }
+
+        // activity.iter() 从最近活跃到最久不活跃，rev 之后 rank 越小越久不活跃
+        let rank: HashMap<Address, usize> = self
+            .activity
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(rank, (sender, _))| (*sender, rank))
+            .collect();
+        // Reverse 把大顶堆变成小顶堆：价格最低的先出，同价的 rank 小（最久不活跃）的先出
+        let mut tails: BinaryHeap<Reverse<(GasPrice, usize, Address)>> = self
+            .pool
+            .iter()
+            .filter_map(|(sender, txs)| {
+                let (_, tail) = txs.last_key_value()?;
+                Some(Reverse((tail.gas_price, rank[sender], *sender)))
+            })
+            .collect();
+
+        while count > max_size {
+            let Some(Reverse((_, rank, sender))) = tails.pop() else {
+                break;
+            };
+
+            let sender_txs = self.pool.get_mut(&sender).unwrap();
+            sender_txs.pop_last();
+            match sender_txs.last_key_value() {
+                Some((_, tail)) => tails.push(Reverse((tail.gas_price, rank, sender))),
+                None => {
+                    // 榜单里它的候选人会变成过期数据，pop_best 会自己跳过
+                    self.pool.remove(&sender);
+                    self.activity.pop(&sender);
+                }
+            }
+            count -= 1;
+        }
+    }
+
+    /// 区块上链之后，把里面已经打包的交易从池子里删掉
+    ///
+    /// 打包了 sender 的某个 nonce，说明链上这个 sender 的 nonce 已经过了它，
+    /// 池子里更低的 nonce 也一起删掉：只删这一笔会在中间留个空洞，
+    /// 更高的 nonce 看起来还能执行，其实前面缺的那笔永远不会再来了
+    ///
+    /// 和 evict_oldest 不一样，这里的榜单要立刻修好，而不是留着过期候选人等 pop_best 跳过：
+    /// 同一个 sender 可能一次被打包了好几笔，它在榜单里的候选人全都过期了，新的队头却还没进榜单
+    pub fn evict_transactions(&mut self, included: &[(Address, Nonce)]) {
+        let mut affected = HashSet::new();
+        for &(sender, nonce) in included {
+            let Some(sender_txs) = self.pool.get_mut(&sender) else {
+                continue;
+            };
+            // split_off 之后 sender_txs 里剩下的是 <= nonce 的，换成 > nonce 的那部分
+            let rest = sender_txs.split_off(&(nonce + 1));
+            if !std::mem::replace(sender_txs, rest).is_empty() {
+                affected.insert(sender);
+            }
+            if sender_txs.is_empty() {
+                self.pool.remove(&sender);
+                self.activity.pop(&sender);
+            }
+        }
+
+        // 受影响的 sender 的候选人全部拿掉（不管过没过期），再按现在的队头放回去
+        self.frontier.retain(|c| !affected.contains(&c.sender));
+        for sender in affected {
+            if let Some((_, head)) = self.pool.get(&sender).and_then(|txs| txs.first_key_value()) {
+                self.frontier.push(Candidate::of(head));
+            }
+        }
+    }
+
+    /// 删掉一个 sender 的全部交易，比如它的余额变成 0 了，一笔都执行不了
+    pub fn evict_sender(&mut self, sender: Address) {
+        if self.pool.remove(&sender).is_some() {
+            self.activity.pop(&sender);
+            self.frontier.retain(|c| c.sender != sender);
+        }
+    }
+
+    /// 一次性打包出一个区块，不用自己循环调用 pop_best
+    ///
+    /// 按 pop_best 的顺序往区块里装，直到：
+    /// 1. 再装一笔就超过 gas_limit
+    /// 2. 或者最优的交易也出不起 base_fee（最优的都不行，剩下的更不行）
+    pub fn into_block(self, gas_limit: u64, base_fee: u64) -> Block {
+        // 单个 sender 的限额等于整个区块，相当于不限
+        self.into_block_with_sender_limit(gas_limit, base_fee, gas_limit)
+    }
+
+    /// 和 into_block 一样，但每个 sender 最多用 `per_sender_limit` 的 gas，
+    /// 超出的交易跳过，由出价低一些的其他 sender 的交易补上
+    pub fn into_block_with_sender_limit(
+        mut self,
+        gas_limit: u64,
+        base_fee: u64,
+        per_sender_limit: u64,
+    ) -> Block {
+        let mut block = Block {
+            transactions: Vec::new(),
+            total_gas: 0,
+            miner_tip: 0,
+        };
+        let mut gas = GasAccumulator::new(gas_limit, per_sender_limit);
+
+        while let Some(tx) = self.pop_best_within(&gas) {
+            if tx.gas_price < base_fee {
+                break;
+            }
+
+            gas.record_inclusion(tx.sender, TX_GAS);
+            block.total_gas += TX_GAS;
+            block.miner_tip += (tx.gas_price - base_fee) * TX_GAS;
+            block.transactions.push(tx);
+        }
+
+        block
+    }
+
+    /// 把池子一次性倒空，按合法的执行顺序排好返回，比如拿去做确定性的区块模拟
+    ///
+    /// 和 into_block 不同，不管 gas 和 base_fee，全部交易都出来
+    pub fn into_sorted_vec(self) -> Vec<Transaction> {
+        self.into_sorted_vec_filtered(|_| true)
+    }
+
+    /// 和 into_sorted_vec 一样，但是出块时 `predicate` 返回 false 的交易不要
+    ///
+    /// 一笔交易被过滤掉，这个 sender 后面的 nonce 也全部丢掉：中间缺了一个 nonce，
+    /// 后面的交易在链上永远执行不了。其他 sender 不受影响，照常按价格、nonce 的顺序出来
+    pub fn into_sorted_vec_filtered(
+        mut self,
+        predicate: impl Fn(&Transaction) -> bool,
+    ) -> Vec<Transaction> {
+        let mut sorted = Vec::with_capacity(self.total_transaction_count());
+
+        while let Some(tx) = self.pop_best() {
+            if predicate(&tx) {
+                sorted.push(tx);
+            } else {
+                self.evict_sender(tx.sender);
+            }
+        }
+
+        sorted
+    }
+}
+
+/// 克隆一份拿去做模拟（比如试不同的打包顺序），两份之后互不影响
+///
+/// 不直接 clone 榜单：榜单里可能还留着 evict_oldest 之类留下的过期候选人，
+/// 克隆出来的这份按仓库里每个 sender 的队头重新建一个榜单，干干净净
+impl Clone for BlockBuilder {
+    fn clone(&self) -> Self {
+        let pool = self.pool.clone();
+        let frontier = pool
+            .values()
+            .filter_map(|txs| txs.first_key_value())
+            .map(|(_, head)| Candidate::of(head))
+            .collect();
+
+        // LruCache 自己的 clone 会按容量预先分配，unbounded 的容量是 usize::MAX，直接 panic；
+        // 从最久不活跃的开始依次 put 进一个新的，活跃顺序不变
+        let mut activity = LruCache::unbounded();
+        for (sender, _) in self.activity.iter().rev() {
+            activity.put(*sender, ());
+        }
+
+        Self {
+            pool,
+            frontier,
+            activity,
+        }
+    }
+}
+
+// ========================= Actor：多个任务共用一个交易池 =====================
+//
+// 和 _2026_01_05_fuck.rs 里的 MyActor 一个套路：BlockBuilder 只归后台的 actor 任务所有，
+// 别的任务拿着 handle 发消息，不用给 BlockBuilder 加锁
+
+enum BlockBuilderMessage {
+    AddTx(Transaction),
+    // 把池子倒空，按出块顺序把交易发回来
+    Drain(oneshot::Sender<Vec<Transaction>>),
+}
+
+struct BlockBuilderActor {
+    receiver: mpsc::Receiver<BlockBuilderMessage>,
+    builder: BlockBuilder,
+}
+
+impl BlockBuilderActor {
+    async fn run(mut self) {
+        while let Some(msg) = self.receiver.recv().await {
+            match msg {
+                BlockBuilderMessage::AddTx(tx) => self.builder.add_transaction(tx),
+                BlockBuilderMessage::Drain(respond_to) => {
+                    // 换一个空的池子进来，倒空旧的那个
+                    let builder = std::mem::replace(&mut self.builder, BlockBuilder::new());
+                    let _ = respond_to.send(builder.into_sorted_vec());
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct BlockBuilderHandle {
+    sender: mpsc::Sender<BlockBuilderMessage>,
+}
+
+#[allow(dead_code)]
+impl BlockBuilderHandle {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel(1024);
+        let actor = BlockBuilderActor {
+            receiver,
+            builder: BlockBuilder::new(),
+        };
+        tokio::spawn(actor.run());
+        Self { sender }
+    }
+
+    pub async fn add_tx(&self, tx: Transaction) {
+        let _ = self.sender.send(BlockBuilderMessage::AddTx(tx)).await;
+    }
+
+    /// 把当前池子里的交易全部拿出来打包，池子清空，后面 add_tx 的交易进下一个区块
+    pub async fn drain_for_block(&self) -> Vec<Transaction> {
+        let (sender, receiver) = oneshot::channel();
+        let _ = self.sender.send(BlockBuilderMessage::Drain(sender)).await;
+        receiver.await.expect("BlockBuilderActor 已经退出")
+    }
+}
+
+/// `handle.await` 的返回值，等价于 `handle.drain_for_block().await`
+///
+/// async fn 返回的 Future 类型写不出名字，只能装箱存起来
+pub struct BlockDrainFuture {
+    inner: Pin<Box<dyn Future<Output = Vec<Transaction>> + Send>>,
+}
+
+impl Future for BlockDrainFuture {
+    type Output = Vec<Transaction>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+/// 实现了 IntoFuture 就可以直接 `.await` 一个 handle：`let block = handle.await;`
+///
+/// 会把 handle 消耗掉，还要接着用的话先 clone 一份
+impl IntoFuture for BlockBuilderHandle {
+    type Output = Vec<Transaction>;
+    type IntoFuture = BlockDrainFuture;
+
+    fn into_future(self) -> Self::IntoFuture {
+        BlockDrainFuture {
+            inner: Box::pin(async move { self.drain_for_block().await }),
+        }
+    }
+}
+
+// ========================= 调试输出 =====================
+
+/// 按出块优先级打印榜单：`Frontier[sender=0xA nonce=1 gas=100, sender=0xB nonce=0 gas=50]`
+///
+/// 原样打印堆里的所有候选人，包括 pop_best 还没来得及跳过的过期候选人
+pub fn dump_frontier(builder: &BlockBuilder) -> String {
+    // BinaryHeap::iter() 的顺序是堆内部数组的顺序，不是优先级顺序；
+    // clone 一份排个序，into_sorted_vec 是升序，反过来就是先出的在前
+    let mut candidates = builder.frontier.clone().into_sorted_vec();
+    candidates.reverse();
+
+    let items: Vec<String> = candidates.iter().map(|c| c.to_string()).collect();
+    format!("Frontier[{}]", items.join(", "))
+}
+
+/// 树状打印仓库里的全部交易，sender 从小到大、nonce 从小到大
+///
+/// ```text
+/// Pool
+/// ├── sender=0xA
+/// │   ├── nonce=0 gas=10 hash=A0
+/// │   └── nonce=1 gas=100 hash=A1
+/// └── sender=0xB
+///     └── nonce=0 gas=50 hash=B0
+/// ```
+pub fn dump_pool(builder: &BlockBuilder) -> String {
+    let mut out = String::from("Pool\n");
+
+    // HashMap 的遍历顺序是随机的，排个序输出才稳定
+    let mut senders: Vec<(&Address, &BTreeMap<Nonce, Transaction>)> = builder.pool.iter().collect();
+    senders.sort_unstable_by_key(|(sender, _)| **sender);
+
+    for (i, (sender, txs)) in senders.iter().enumerate() {
+        let last_sender = i + 1 == senders.len();
+        let (branch, indent) = if last_sender {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        out.push_str(&format!("{branch}sender={sender:#X}\n"));
+
+        for (j, tx) in txs.values().enumerate() {
+            let branch = if j + 1 == txs.len() {
+                "└── "
+            } else {
+                "├── "
+            };
+            out.push_str(&format!(
+                "{indent}{branch}nonce={} gas={} hash={}\n",
+                tx.nonce, tx.gas_price, tx.hash
+            ));
+        }
+    }
+
+    out
+}
+
+/// 测试用的交易，hash 是 sender 的十六进制加 nonce，比如 0xA 的 nonce 1 是 "A1"
+#[cfg(test)]
+fn tx(sender: Address, nonce: Nonce, gas_price: GasPrice) -> Transaction {
+    Transaction {
+        sender,
+        nonce,
+        gas_price,
+        hash: format!("{:X}{}", sender, nonce),
+        tx_type: TxType::Legacy,
+    }
+}
+
+/// 把 (sender, nonce, gas_price) 依次加进池子
+#[cfg(test)]
+fn fill(builder: &mut BlockBuilder, txs: &[(Address, Nonce, GasPrice)]) {
+    for &(sender, nonce, gas_price) in txs {
+        builder.add_transaction(tx(sender, nonce, gas_price));
     }
 }
 
@@ -158,24 +785,28 @@ fn test_work() {
             nonce: 0,
             gas_price: 10,
             hash: "A0".into(),
+            tx_type: TxType::Legacy,
         }, // 便宜的门票
         Transaction {
             sender: 0xA,
             nonce: 1,
             gas_price: 100,
             hash: "A1".into(),
+            tx_type: TxType::Legacy,
         }, // 巨贵的后续
         Transaction {
             sender: 0xB,
             nonce: 0,
             gas_price: 50,
             hash: "B0".into(),
+            tx_type: TxType::Legacy,
         }, // 中等的首发
         Transaction {
             sender: 0xA,
             nonce: 2,
             gas_price: 20,
             hash: "A2".into(),
+            tx_type: TxType::Legacy,
         },
     ];
 
@@ -195,3 +826,552 @@ fn test_work() {
     assert_eq!(result, expected, "顺序错了！被虐了吧？");
     println!("恭喜！你成功模拟了 Reth 的交易排序逻辑！");
 }
+
+#[test]
+fn test_into_block() {
+    let mut builder = BlockBuilder::new();
+
+    // 15 个 sender，gas_price 从 10 到 24，都付得起 base_fee
+    for i in 0..15u64 {
+        builder.add_transaction(tx(i, 0, 10 + i));
+    }
+    // 一笔出不起 base_fee 的
+    builder.add_transaction(tx(0xFF, 0, 5));
+
+    let block = builder.into_block(210_000, 10);
+
+    // gas_limit 只够 10 笔，按价格从高到低装：24, 23, ... 15
+    assert_eq!(block.transactions.len(), 10);
+    assert_eq!(block.total_gas, 210_000);
+    let prices: Vec<u64> = block.transactions.iter().map(|tx| tx.gas_price).collect();
+    assert_eq!(prices, (15..=24).rev().collect::<Vec<_>>());
+
+    // 小费 = (5 + 6 + ... + 14) * 21000
+    let expected_tip: u64 = (5..=14).sum::<u64>() * 21_000;
+    assert_eq!(block.miner_tip, expected_tip);
+
+    // gas 充足时，出不起 base_fee 的交易也不会被打包
+    let mut builder = BlockBuilder::new();
+    fill(&mut builder, &[(1, 0, 30), (2, 0, 9)]);
+    let block = builder.into_block(30_000_000, 10);
+    assert_eq!(block.transactions.len(), 1);
+    assert_eq!(block.miner_tip, 20 * 21_000);
+}
+
+#[test]
+fn test_evict_oldest() {
+    let mut builder = BlockBuilder::new();
+
+    // 10 个 sender，每人 100 笔，同一个 sender 的 nonce 越大出价越低（真实情况常见：后面的交易不急）
+    let mut all_prices = Vec::new();
+    for sender in 0..10u64 {
+        for nonce in 0..100u64 {
+            let gas_price = 2_000 - nonce * 10 - sender * 3;
+            all_prices.push(gas_price);
+            builder.add_transaction(tx(sender, nonce, gas_price));
+        }
+    }
+    assert_eq!(builder.total_transaction_count(), 1000);
+
+    builder.evict_oldest(500);
+    assert_eq!(builder.total_transaction_count(), 500);
+
+    // 留下来的正好是出价最高的 500 笔
+    all_prices.sort_unstable_by(|a, b| b.cmp(a));
+    let threshold = all_prices[499];
+    for txs in builder.pool.values() {
+        // 每个 sender 剩下的 nonce 依然从 0 开始连续
+        assert!(txs.keys().copied().eq(0..txs.len() as u64));
+        assert!(txs.values().all(|tx| tx.gas_price >= threshold));
+    }
+
+    // 驱逐之后照样能正常出块
+    let mut popped = 0;
+    while builder.pop_best().is_some() {
+        popped += 1;
+    }
+    assert_eq!(popped, 500);
+
+    // 队尾同价时先踢最久没发过新交易的 sender：0xC 先发完，0xB 后发，踢的是 0xC 的队尾
+    let mut builder = BlockBuilder::new();
+    fill(
+        &mut builder,
+        &[(0xC, 0, 50), (0xC, 1, 5), (0xB, 0, 50), (0xB, 1, 5)],
+    );
+    builder.evict_oldest(3);
+    assert_eq!(
+        dump_pool(&builder),
+        "Pool\n├── sender=0xB\n│   ├── nonce=0 gas=50 hash=B0\n│   └── nonce=1 gas=5 hash=B1\n└── sender=0xC\n    └── nonce=0 gas=50 hash=C0\n"
+    );
+}
+
+#[test]
+fn test_transaction_identity() {
+    use std::collections::HashSet;
+    use std::hash::BuildHasher;
+
+    let old = Transaction {
+        sender: 0xA,
+        nonce: 7,
+        gas_price: 10,
+        hash: "old".into(),
+        tx_type: TxType::Legacy,
+    };
+    let bumped = Transaction {
+        gas_price: 30,
+        hash: "bumped".into(),
+        ..old.clone()
+    };
+
+    // 只有 gas_price / hash 不同：是同一笔交易
+    assert_eq!(old, bumped);
+    let hasher = std::collections::hash_map::RandomState::new();
+    assert_eq!(hasher.hash_one(&old), hasher.hash_one(&bumped));
+    // nonce 不同就是另一笔
+    assert_ne!(
+        old,
+        Transaction {
+            nonce: 8,
+            ..old.clone()
+        }
+    );
+
+    // 去重：出价更高的新交易换掉旧的
+    let mut set = HashSet::new();
+    set.insert(old);
+    let replaced = set.replace(bumped).unwrap();
+    assert_eq!(replaced.hash, "old");
+    assert_eq!(set.len(), 1);
+    assert_eq!(set.iter().next().unwrap().gas_price, 30);
+}
+
+#[test]
+fn test_gas_accumulator() {
+    let mut gas = GasAccumulator::new(100_000, 50_000);
+    assert!(gas.can_include(1, 50_000));
+    assert!(!gas.can_include(1, 50_001));
+
+    gas.record_inclusion(1, 42_000);
+    assert!(!gas.can_include(1, TX_GAS));
+    gas.record_inclusion(2, 42_000);
+    // 2 自己的份额还够，但区块只剩 16000
+    assert!(!gas.can_include(3, TX_GAS));
+    assert!(gas.can_include(3, 16_000));
+}
+
+#[test]
+fn test_greedy_sender_limited() {
+    let mut builder = BlockBuilder::new();
+
+    // 土豪 0xA 发了 50 笔高价交易，另外 20 个 sender 各一笔低价交易
+    for nonce in 0..50 {
+        builder.add_transaction(tx(0xA, nonce, 100));
+    }
+    for sender in 0x100..0x114u64 {
+        builder.add_transaction(tx(sender, 0, 10));
+    }
+
+    // 区块装得下 100 笔，每个 sender 最多 10%
+    let gas_limit = 100 * TX_GAS;
+    let block = builder.into_block_with_sender_limit(gas_limit, 1, gas_limit / 10);
+
+    let greedy: Vec<&Transaction> = block
+        .transactions
+        .iter()
+        .filter(|tx| tx.sender == 0xA)
+        .collect();
+    assert_eq!(greedy.len(), 10);
+    assert!(greedy.len() as u64 * TX_GAS <= gas_limit / 10);
+    // 土豪出价高，先装它的 nonce 0..10，之后轮到其他人
+    assert!(greedy.iter().map(|tx| tx.nonce).eq(0..10));
+    assert!(block.transactions[..10].iter().all(|tx| tx.sender == 0xA));
+    assert_eq!(block.transactions.len(), 30);
+    assert_eq!(block.total_gas, 30 * TX_GAS);
+}
+
+#[test]
+fn test_skipped_sender_stays_in_pool() {
+    let mut builder = BlockBuilder::new();
+    fill(&mut builder, &[(0xA, 0, 100), (0xA, 1, 90), (0xB, 0, 10)]);
+
+    // 0xA 只能装一笔
+    let gas = {
+        let mut gas = GasAccumulator::new(u64::MAX, TX_GAS);
+        gas.record_inclusion(0xA, TX_GAS);
+        gas
+    };
+    let tx = builder.pop_best_within(&gas).unwrap();
+    assert_eq!(tx.sender, 0xB);
+    assert!(builder.pop_best_within(&gas).is_none());
+
+    // 下一个区块重新计数，0xA 剩下的交易按顺序出来
+    assert_eq!(builder.pop_best().unwrap().nonce, 0);
+    assert_eq!(builder.pop_best().unwrap().nonce, 1);
+}
+
+#[test]
+fn test_dump_frontier_and_pool() {
+    let mut builder = BlockBuilder::new();
+    fill(&mut builder, &[(0xA, 0, 10), (0xA, 1, 100), (0xB, 0, 50)]);
+
+    // 榜单里只有每个 sender 的队头
+    assert_eq!(
+        dump_frontier(&builder),
+        "Frontier[sender=0xB nonce=0 gas=50, sender=0xA nonce=0 gas=10]"
+    );
+    let expected = [
+        "Pool",
+        "├── sender=0xA",
+        "│   ├── nonce=0 gas=10 hash=A0",
+        "│   └── nonce=1 gas=100 hash=A1",
+        "└── sender=0xB",
+        "    └── nonce=0 gas=50 hash=B0",
+    ];
+    assert_eq!(dump_pool(&builder), expected.join("\n") + "\n");
+
+    // B0、A0 出块之后，A1 进了榜单
+    builder.pop_best();
+    builder.pop_best();
+    assert_eq!(
+        dump_frontier(&builder),
+        "Frontier[sender=0xA nonce=1 gas=100]"
+    );
+    assert_eq!(
+        dump_pool(&builder),
+        "Pool\n└── sender=0xA\n    └── nonce=1 gas=100 hash=A1\n"
+    );
+
+    let empty = BlockBuilder::new();
+    assert_eq!(dump_frontier(&empty), "Frontier[]");
+    assert_eq!(dump_pool(&empty), "Pool\n");
+}
+
+#[test]
+fn test_evict_transactions() {
+    let mut builder = BlockBuilder::new();
+    fill(
+        &mut builder,
+        &[
+            (0xA, 0, 10),
+            (0xA, 1, 100),
+            (0xA, 2, 20),
+            (0xB, 0, 50),
+            (0xB, 1, 40),
+            (0xC, 0, 30),
+        ],
+    );
+
+    // 上一个区块打包了 A0、A1、B0，还有一笔池子里没有的
+    builder.evict_transactions(&[(0xA, 0), (0xA, 1), (0xB, 0), (0xD, 0)]);
+    assert_eq!(builder.total_transaction_count(), 3);
+    // 榜单里是新的队头 A2、B1，没有过期的 A0、B0
+    assert_eq!(
+        dump_frontier(&builder),
+        "Frontier[sender=0xB nonce=1 gas=40, sender=0xC nonce=0 gas=30, sender=0xA nonce=2 gas=20]"
+    );
+
+    // 剩下的交易照常按价格、nonce 出块
+    let order: Vec<String> = std::iter::from_fn(|| builder.pop_best())
+        .map(|tx| tx.hash)
+        .collect();
+    assert_eq!(order, ["B1", "C0", "A2"]);
+
+    // 只报了 A1 被打包：A0 也一定已经上链了，一起删掉，不留 nonce 空洞
+    let mut builder = BlockBuilder::new();
+    fill(&mut builder, &[(0xA, 0, 10), (0xA, 1, 100), (0xA, 2, 20)]);
+    builder.evict_transactions(&[(0xA, 1)]);
+    assert_eq!(
+        dump_pool(&builder),
+        "Pool\n└── sender=0xA\n    └── nonce=2 gas=20 hash=A2\n"
+    );
+    assert_eq!(
+        dump_frontier(&builder),
+        "Frontier[sender=0xA nonce=2 gas=20]"
+    );
+}
+
+#[test]
+fn test_evict_sender() {
+    let mut builder = BlockBuilder::new();
+    fill(
+        &mut builder,
+        &[(0xA, 0, 100), (0xA, 1, 90), (0xB, 0, 50), (0xB, 1, 60)],
+    );
+
+    builder.evict_sender(0xA);
+    // 不在池子里的 sender 什么都不做
+    builder.evict_sender(0xF);
+
+    assert_eq!(
+        dump_frontier(&builder),
+        "Frontier[sender=0xB nonce=0 gas=50]"
+    );
+    assert_eq!(
+        dump_pool(&builder),
+        "Pool\n└── sender=0xB\n    ├── nonce=0 gas=50 hash=B0\n    └── nonce=1 gas=60 hash=B1\n"
+    );
+    assert_eq!(builder.pop_best().unwrap().hash, "B0");
+    assert_eq!(builder.pop_best().unwrap().hash, "B1");
+    assert!(builder.pop_best().is_none());
+}
+
+#[test]
+fn test_into_sorted_vec() {
+    let txs = [
+        (0xA, 0, 10),
+        (0xA, 1, 100),
+        (0xA, 2, 20),
+        (0xB, 0, 50),
+        (0xB, 1, 5),
+        (0xC, 0, 30),
+    ];
+    let builder = || {
+        let mut builder = BlockBuilder::new();
+        fill(&mut builder, &txs);
+        builder
+    };
+    let hashes = |txs: Vec<Transaction>| txs.into_iter().map(|tx| tx.hash).collect::<Vec<_>>();
+
+    // 和一直调用 pop_best 的顺序一样
+    let mut popping = builder();
+    let popped: Vec<Transaction> = std::iter::from_fn(|| popping.pop_best()).collect();
+    let sorted = builder().into_sorted_vec();
+    assert_eq!(hashes(sorted.clone()), hashes(popped));
+    assert_eq!(hashes(sorted), ["B0", "C0", "A0", "A1", "A2", "B1"]);
+
+    // A1 被过滤掉，A2 跟着丢掉；B1 出价低但是保留，顺序不变
+    let filtered = builder().into_sorted_vec_filtered(|tx| tx.hash != "A1");
+    assert_eq!(hashes(filtered), ["B0", "C0", "A0", "B1"]);
+
+    // 按价格过滤：队头就不合格的 sender 整个消失，后面再贵也不要
+    let filtered = builder().into_sorted_vec_filtered(|tx| tx.gas_price >= 20);
+    for tx in &filtered {
+        assert!(tx.gas_price >= 20);
+    }
+    assert_eq!(hashes(filtered.clone()), ["B0", "C0"]);
+
+    // 每个 sender 剩下的 nonce 都从 0 开始连续
+    let mut next_nonce: HashMap<Address, Nonce> = HashMap::new();
+    for tx in builder().into_sorted_vec_filtered(|tx| tx.sender != 0xC) {
+        let expected = next_nonce.entry(tx.sender).or_default();
+        assert_eq!(tx.nonce, *expected);
+        *expected += 1;
+    }
+    assert_eq!(next_nonce, HashMap::from([(0xA, 3), (0xB, 2)]));
+
+    assert!(BlockBuilder::new().into_sorted_vec().is_empty());
+}
+
+#[test]
+fn test_clone() {
+    let mut builder = BlockBuilder::new();
+    for sender in 0..10u64 {
+        // 0x9 只有一笔最便宜的交易，下面驱逐时整个 sender 消失
+        let nonces = if sender == 9 { 1 } else { 3 };
+        for nonce in 0..nonces {
+            // 价格打乱一点，不同 sender 交替出块
+            let gas_price = if sender == 9 {
+                1
+            } else {
+                (sender * 37 + nonce * 11) % 50 + 2
+            };
+            builder.add_transaction(tx(sender, nonce, gas_price));
+        }
+    }
+    builder.evict_oldest(27);
+    // 0x9 的候选人还在榜单里，成了过期数据
+    assert_eq!(builder.frontier.len(), 10);
+    assert_eq!(builder.pool.len(), 9);
+
+    let cloned = builder.clone();
+    // 克隆出来的榜单按队头重建，每个 sender 正好一个候选人
+    assert_eq!(cloned.frontier.len(), 9);
+    assert_eq!(dump_pool(&cloned), dump_pool(&builder));
+    assert!(cloned.activity.iter().eq(builder.activity.iter()));
+
+    // 原来的先倒空，不影响克隆出来的那份；两份出块顺序完全一样
+    let hashes = |txs: Vec<Transaction>| txs.into_iter().map(|tx| tx.hash).collect::<Vec<_>>();
+    let original = hashes(builder.into_sorted_vec());
+    let simulated = hashes(cloned.into_sorted_vec());
+    assert_eq!(original.len(), 27);
+    assert_eq!(simulated, original);
+}
+
+#[tokio::test]
+async fn test_await_handle() {
+    let handle = BlockBuilderHandle::new();
+    for i in 0..100u64 {
+        handle.add_tx(tx(i % 10, i / 10, 100 - i)).await;
+    }
+
+    // IntoFuture 消耗 handle，clone 一份留着后面继续用
+    let block = handle.clone().await;
+    assert_eq!(block.len(), 100);
+    // 每个 sender 的 nonce 从 0 开始连续出来
+    let mut next_nonce: HashMap<Address, Nonce> = HashMap::new();
+    for tx in &block {
+        let expected = next_nonce.entry(tx.sender).or_default();
+        assert_eq!(tx.nonce, *expected);
+        *expected += 1;
+    }
+    // 第一个是出价最高的 0x0 nonce=0
+    assert_eq!(block[0].hash, "00");
+
+    // 池子已经倒空了，再 await 一次什么都没有
+    assert!(handle.clone().await.is_empty());
+    assert!(handle.drain_for_block().await.is_empty());
+}
+
+#[cfg(test)]
+mod serde_tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn tx(tx_type: TxType) -> Transaction {
+        Transaction {
+            sender: 0xA,
+            nonce: 26,
+            gas_price: 100,
+            hash: "0xabc".into(),
+            tx_type,
+        }
+    }
+
+    #[test]
+    fn test_serde_round_trip_all_types() {
+        let cases = [
+            (
+                TxType::Legacy,
+                json!({"type": "0x0", "from": "0xa", "nonce": "0x1a", "gasPrice": "0x64", "hash": "0xabc"}),
+            ),
+            (
+                TxType::Eip2930,
+                json!({"type": "0x1", "from": "0xa", "nonce": "0x1a", "gasPrice": "0x64", "hash": "0xabc"}),
+            ),
+            (
+                TxType::Eip1559,
+                json!({"type": "0x2", "from": "0xa", "nonce": "0x1a", "maxPriorityFeePerGas": "0x64", "hash": "0xabc"}),
+            ),
+        ];
+
+        for (tx_type, expected) in cases {
+            let original = tx(tx_type);
+            let value = serde_json::to_value(&original).unwrap();
+            assert_eq!(value, expected);
+            assert_eq!(value["type"], format!("{:#x}", tx_type as u8));
+
+            // Eq 只看 sender + nonce，逐个字段比一下
+            let back: Transaction = serde_json::from_value(value).unwrap();
+            assert_eq!(
+                (back.tx_type, back.gas_price, &back.hash),
+                (tx_type, original.gas_price, &original.hash)
+            );
+            assert_eq!(back, original);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_dispatches_on_type() {
+        // type 不一定在第一个字段
+        let tx: Transaction = serde_json::from_str(
+            r#"{"maxPriorityFeePerGas":"0x2","hash":"h","nonce":"0x0","from":"0x1","type":"0x2"}"#,
+        )
+        .unwrap();
+        assert_eq!((tx.tx_type, tx.gas_price), (TxType::Eip1559, 2));
+
+        // 1559 交易里没有 gasPrice
+        assert!(
+            serde_json::from_str::<Transaction>(
+                r#"{"type":"0x2","from":"0x1","nonce":"0x0","gasPrice":"0x2","hash":"h"}"#
+            )
+            .is_err()
+        );
+        // 不认识的类型（EIP-4844 blob 交易）
+        assert!(
+            serde_json::from_str::<Transaction>(
+                r#"{"type":"0x3","from":"0x1","nonce":"0x0","gasPrice":"0x2","hash":"h"}"#
+            )
+            .is_err()
+        );
+        // 数字必须是 0x 开头的十六进制
+        assert!(
+            serde_json::from_str::<Transaction>(
+                r#"{"type":"0x0","from":"0x1","nonce":"7","gasPrice":"0x2","hash":"h"}"#
+            )
+            .is_err()
+        );
+    }
+}
+
+#[cfg(test)]
+mod ordering_proptests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    // 每个 sender 若干笔连续 nonce 的交易，gas_price 只在 1..4 里取，故意制造大量同价
+    fn mempool() -> impl Strategy<Value = Vec<Transaction>> {
+        prop::collection::vec(prop::collection::vec(1..4u64, 1..5), 1..8).prop_map(|senders| {
+            senders
+                .into_iter()
+                .enumerate()
+                .flat_map(|(sender, prices)| {
+                    prices
+                        .into_iter()
+                        .enumerate()
+                        .map(move |(nonce, gas_price)| Transaction {
+                            sender: sender as Address,
+                            nonce: nonce as Nonce,
+                            gas_price,
+                            hash: format!(
+                                "0x{:016x}",
+                                (sender as u64 * 31 + nonce as u64) * 0x9e37_79b9
+                            ),
+                            tx_type: TxType::Legacy,
+                        })
+                })
+                .collect()
+        })
+    }
+
+    fn pop_all(txs: Vec<Transaction>) -> Vec<String> {
+        let mut builder = BlockBuilder::new();
+        for tx in txs {
+            builder.add_transaction(tx);
+        }
+        std::iter::from_fn(|| builder.pop_best())
+            .map(|tx| tx.hash)
+            .collect()
+    }
+
+    proptest! {
+        // 同样的交易池内容，不管 add 的顺序怎么打乱，出块顺序完全一样
+        #[test]
+        fn prop_order_independent_of_insertion(
+            (txs, shuffled) in mempool().prop_flat_map(|txs| (Just(txs.clone()), Just(txs).prop_shuffle()))
+        ) {
+            prop_assert_eq!(pop_all(txs), pop_all(shuffled));
+        }
+
+        // 把 sender ID 整体重新编号（只要不改变交易内容），同价交易之间的顺序不受影响
+        #[test]
+        fn prop_order_independent_of_sender_ids(txs in mempool(), offset in 1..1_000u64) {
+            let renumbered: Vec<Transaction> = txs
+                .iter()
+                .map(|tx| Transaction { sender: 1_000 - tx.sender + offset, ..tx.clone() })
+                .collect();
+            prop_assert_eq!(pop_all(txs), pop_all(renumbered));
+        }
+
+        // Ord 和 Eq 一致，且是全序
+        #[test]
+        fn prop_candidate_ord_consistent(a in mempool(), b in mempool()) {
+            for (x, y) in a.iter().zip(&b) {
+                let (x, y) = (Candidate::of(x), Candidate::of(y));
+                prop_assert_eq!(x.cmp(&y) == Ordering::Equal, x == y);
+                prop_assert_eq!(x.cmp(&y), y.cmp(&x).reverse());
+            }
+        }
+    }
+}
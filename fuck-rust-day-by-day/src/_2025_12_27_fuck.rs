@@ -1,77 +1,122 @@
-use bytes::{Buf, BytesMut};
-use serde::{Deserialize, Serialize};
-use tokio_util::codec::Decoder;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-#[allow(dead_code)]
-#[allow(unused_variables)]
-// ================= 1. 定义消息协议 =================
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
-pub enum P2PMessage {
-    Hello { version: u32 },
-    Ping,
-    Pong,
-}
+use bytes::{Buf, BytesMut};
+use futures_util::{SinkExt, Stream};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::{Decoder, Encoder, FramedRead, FramedWrite};
+
+// ================= 1. 帧格式和消息协议 =================
+// 帧 = 4 字节大端长度 + JSON Payload
+// 消息定义和不分配堆内存的编解码放在 p2p-frame 这个 no_std crate 里（serde_json_core + heapless），
+// 没有 tokio、没有 std 的环境（比如嵌入式轻客户端）也能直接拿来收发消息；
+// 这里打开了它的 std feature，CodecError 才能当 tokio_util 的错误类型用
+pub use p2p_frame::{CodecError, P2PMessage};
+use p2p_frame::{HEADER_LEN, decode_payload, encode_frame};
+
+// ================= 2. tokio Codec =================
+// 在 BytesMut 上切帧，Payload 交给 p2p-frame 解析
+// 注意：解码这边帧长不设上限，和原来的行为一样；MAX_FRAME_LEN 只限制 decode_frame 那条定长路径
 
 // 解码器结构体（通常这里是空的，除非你需要存一些状态，比如“正在读头部”）
 #[allow(dead_code)]
 pub struct P2PCodec;
 
-// ================= 2. 核心实现：Decoder =================
 impl Decoder for P2PCodec {
     type Item = P2PMessage;
-    type Error = std::io::Error;
+    type Error = CodecError;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        // Step 1: 【守门】检查头部是否完整
-        // 我们的协议头是 4 字节 (u32)
-        if src.len() < 4 {
-            // 数据不够，告诉 Tokio：别急，再去网卡读点数据，攒够了再叫我
+        // 【守门】头部都不完整
+        let Some(header) = src.first_chunk::<HEADER_LEN>() else {
             return Ok(None);
-        }
+        };
+
+        // 【偷看】只读长度，不消耗数据
+        let length = u32::from_be_bytes(*header) as usize;
 
-        // Step 2: 【偷看 (Peek)】读取长度，但不消耗数据
-        // 注意：千万不能用 src.get_u32()，因为它会把这4个字节吃掉（移动游标）。
-        // 如果后面 Payload 不够，下次进来游标就不对了。
-        // 所以我们用切片语法 src[..4] 只是看一眼。
-        let mut length_bytes = [0u8; 4];
-        length_bytes.copy_from_slice(&src[..4]);
-        let length = u32::from_be_bytes(length_bytes) as usize;
-
-        // Step 3: 【验货】检查剩余数据是否满足 Payload 长度
-        // 需要的总长度 = 头部(4) + 内容(length)
-        if src.len() < 4 + length {
-            // 头部有了，但身子还没收全（这就是典型的“半包/拆包”）。
-            // 告诉 Tokio：我要更多数据。
-            // 优化：告诉 buffer 预留空间，避免频繁扩容
-            src.reserve(4 + length - src.len());
+        // 【验货】身子还没收全（半包）：告诉 Tokio 我要更多数据
+        // 优化：按声明的长度预留空间，避免频繁扩容
+        if src.len() < HEADER_LEN + length {
+            src.reserve(HEADER_LEN + length - src.len());
             return Ok(None);
         }
 
-        // Step 4: 【切割】数据齐了！开始动刀（解决粘包）
-        // 走到这里，src 里一定包含至少一个完整的包。
+        // 【切割】数据齐了，消耗掉这一帧，剩下的（可能是下一个粘包）留在 src 里
+        src.advance(HEADER_LEN);
+        let data = src.split_to(length);
 
-        // A. 真的消耗掉前4个字节（头部）
-        src.advance(4);
+        decode_payload(&data).map(Some)
+    }
+}
 
-        // B. 切割下 Payload
-        // split_to(n) 会做两件事：
-        // 1. 把 src 的前 n 个字节切下来返回给 data。
-        // 2. src 剩下的部分保留（可能是下一个粘包的数据）。
-        let data = src.split_to(length);
+// 写出去的格式必须和 decode 读进来的一模一样：4 字节大端长度 + JSON Payload
+// 消息都很短，编码结果一定放得进定长的 FrameBuf
+impl Encoder<P2PMessage> for P2PCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: P2PMessage, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.extend_from_slice(&encode_frame(&item)?);
+        Ok(())
+    }
+}
+
+// ================= 3. 类型化的读写适配器 =================
+// 调用方不想关心 Framed 和 Codec 的细节，只想要：
+// 读端：一个不断吐出 P2PMessage 的 Stream
+// 写端：一个 send(msg).await 的方法
+
+/// 读端：把任意 AsyncRead（TcpStream、管道、duplex...）变成 P2PMessage 的流
+#[allow(dead_code)]
+pub struct P2PReader<R: AsyncRead + Unpin> {
+    inner: FramedRead<R, P2PCodec>,
+}
+
+#[allow(dead_code)]
+impl<R: AsyncRead + Unpin> P2PReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: FramedRead::new(reader, P2PCodec),
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for P2PReader<R> {
+    type Item = Result<P2PMessage, CodecError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // FramedRead 本身就是 Unpin 的 Stream，直接转发即可
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+}
+
+/// 写端：把任意 AsyncWrite 包装成可以直接发送 P2PMessage 的对象
+#[allow(dead_code)]
+pub struct P2PWriter<W: AsyncWrite + Unpin> {
+    inner: FramedWrite<W, P2PCodec>,
+}
 
-        // Step 5: 反序列化
-        match serde_json::from_slice(&data) {
-            Ok(msg) => Ok(Some(msg)),
-            Err(e) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+#[allow(dead_code)]
+impl<W: AsyncWrite + Unpin> P2PWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: FramedWrite::new(writer, P2PCodec),
         }
     }
+
+    /// 编码并发送一条消息，SinkExt::send 内部会 flush，返回时数据已经交给底层 writer
+    pub async fn send(&mut self, msg: P2PMessage) -> Result<(), CodecError> {
+        self.inner.send(msg).await
+    }
 }
 
-// ================= 3. 测试用例验证 =================
+// ================= 4. 测试用例验证 =================
 #[cfg(test)]
 mod tests {
     use super::*;
     use bytes::BufMut;
+    use futures_util::StreamExt;
+    use p2p_frame::{MAX_FRAME_LEN, decode_frame};
 
     #[test]
     fn test_sticky_and_partial() {
@@ -133,4 +178,76 @@ mod tests {
 
         assert_eq!(buf.len(), 7);
     }
+
+    #[test]
+    fn test_frames_match_serde_json() {
+        // p2p-frame 的编码结果和原来 serde_json 的完全一致，新旧节点可以互通
+        for msg in [
+            P2PMessage::Hello { version: u32::MAX },
+            P2PMessage::Ping,
+            P2PMessage::Pong,
+        ] {
+            let json = serde_json::to_vec(&msg).unwrap();
+            let mut buf = BytesMut::new();
+            P2PCodec.encode(msg, &mut buf).unwrap();
+            assert_eq!(&buf[..4], (json.len() as u32).to_be_bytes());
+            assert_eq!(&buf[4..], json);
+        }
+    }
+
+    #[test]
+    fn test_codec_accepts_large_frames() {
+        // 合法的 JSON 后面跟一串空白，Payload 超过 MAX_FRAME_LEN
+        let mut payload = serde_json::to_vec(&P2PMessage::Hello { version: 7 }).unwrap();
+        payload.resize(MAX_FRAME_LEN * 4, b' ');
+        let mut buf = BytesMut::new();
+        buf.put_u32(payload.len() as u32);
+        buf.put_slice(&payload);
+
+        // 定长版本拒绝，tokio Codec 照常解码
+        assert!(matches!(
+            decode_frame(&buf),
+            Err(CodecError::FrameTooLarge(len)) if len == MAX_FRAME_LEN * 4
+        ));
+        let msg = P2PCodec.decode(&mut buf).unwrap();
+        assert_eq!(msg, Some(P2PMessage::Hello { version: 7 }));
+        assert!(buf.is_empty());
+
+        // 解码失败时 Display 里带着 serde_json_core 的错误
+        let mut bad = BytesMut::new();
+        bad.put_u32(4);
+        bad.put_slice(b"oops");
+        let err = P2PCodec.decode(&mut bad).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid payload: Expected this character to start a JSON value."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reader_writer_loopback() {
+        // duplex 的缓冲区故意开得很小，逼着读写两端交替推进（模拟真实网络的背压）
+        let (client, server) = tokio::io::duplex(64);
+
+        let mut writer = P2PWriter::new(client);
+        let mut reader = P2PReader::new(server);
+
+        let send_task = tokio::spawn(async move {
+            for i in 0..100 {
+                writer.send(P2PMessage::Hello { version: i }).await.unwrap();
+            }
+            // writer 在这里被 drop，读端会读到 EOF，Stream 随之结束
+        });
+
+        let mut received = Vec::new();
+        while let Some(msg) = reader.next().await {
+            received.push(msg.unwrap());
+        }
+        send_task.await.unwrap();
+
+        assert_eq!(received.len(), 100);
+        for (i, msg) in received.into_iter().enumerate() {
+            assert_eq!(msg, P2PMessage::Hello { version: i as u32 });
+        }
+    }
 }
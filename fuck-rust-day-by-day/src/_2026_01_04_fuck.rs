@@ -273,6 +273,138 @@ mod interview_tests {
         // 底层没有发生任何堆内存的分配和复制
         let _v_zero_copy = s.into_bytes();
     }
+
+    /// 批量导入用的学生记录，直接从文件 / 网络的字节里“看成” Student，不做解析和复制
+    ///
+    /// 不能用 _2026_01_18_fuck 里那个带 `name: String` 的 Student：
+    /// String 里面是指针，随便一段字节当成指针用就是 UB。
+    /// 这里所有字段都是整数，任意字节组合都是合法值；repr(C) 固定字段顺序，
+    /// 8 + 4 + 4 = 16 字节正好没有 padding（padding 字节是未初始化的，不能从 &[u8] 里读出来）
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Student {
+        id: u64,
+        score: u32,
+        age: u32,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum TransmuteError {
+        /// 起始地址不是 align_of::<T>() 的整数倍
+        Misaligned,
+        /// 字节数不是 size_of::<T>() 的整数倍，最后会剩半个元素
+        NotDivisible,
+        /// T 是零大小类型：所有元素都指向同一个地址，而且 len / size_of 会除以 0
+        WouldAlias,
+    }
+
+    /// 把字节切片原地看成 &[T]，零拷贝，返回的切片和 bytes 共用同一块内存、同一个生命周期
+    ///
+    /// # Safety
+    ///
+    /// 对齐、长度、零大小这三条在运行时检查，剩下的只能由调用方保证：
+    /// - T 的任意位模式都是合法值：不能有 bool、char、enum、引用、指针、String 这种字段
+    /// - T 没有 padding 字节，否则读到的是未初始化内存
+    /// - bytes 是按本机字节序（x86 / ARM 都是小端）写进去的 T，否则读出来的数是错的（不是 UB，只是值不对）
+    unsafe fn cast_slice<T>(bytes: &[u8]) -> Result<&[T], TransmuteError> {
+        if size_of::<T>() == 0 {
+            return Err(TransmuteError::WouldAlias);
+        }
+        if !(bytes.as_ptr() as usize).is_multiple_of(align_of::<T>()) {
+            return Err(TransmuteError::Misaligned);
+        }
+        if !bytes.len().is_multiple_of(size_of::<T>()) {
+            return Err(TransmuteError::NotDivisible);
+        }
+
+        // 地址对齐、长度整除都检查过了；&[u8] 和 &[T] 都是只读借用，同时存在也没问题
+        Ok(unsafe {
+            std::slice::from_raw_parts(bytes.as_ptr().cast::<T>(), bytes.len() / size_of::<T>())
+        })
+    }
+
+    /// # Safety
+    ///
+    /// bytes 必须是按本机字节序写进去的一串 Student（比如同一台机器上 `students_as_bytes` 的结果）。
+    /// Student 全是整数字段、没有 padding，所以任意字节都不会造出非法的 Student，
+    /// 对齐和长度由函数本身检查，不满足时返回 Err，不会 UB
+    unsafe fn transmute_student_slice(bytes: &[u8]) -> Result<&[Student], TransmuteError> {
+        unsafe { cast_slice::<Student>(bytes) }
+    }
+
+    /// 反方向：&[Student] 看成字节，任何类型都可以安全地看成 u8（对齐是 1，没有 padding 的前提下）
+    fn students_as_bytes(students: &[Student]) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(students.as_ptr().cast::<u8>(), size_of_val(students)) }
+    }
+
+    // cargo +nightly miri test transmute_student
+    #[test]
+    fn test_transmute_student_slice_aligned() {
+        let students = [
+            Student {
+                id: 1,
+                score: 90,
+                age: 18,
+            },
+            Student {
+                id: 2,
+                score: 85,
+                age: 19,
+            },
+            Student {
+                id: 3,
+                score: 77,
+                age: 20,
+            },
+        ];
+        // 从 [Student] 来的字节一定按 Student 对齐
+        let bytes = students_as_bytes(&students);
+        assert_eq!(bytes.len(), 3 * 16);
+
+        let view = unsafe { transmute_student_slice(bytes) }.unwrap();
+        assert_eq!(view, &students);
+        // 零拷贝：还是同一块内存
+        assert_eq!(view.as_ptr(), students.as_ptr());
+
+        // 空切片：0 个 Student
+        let empty = unsafe { transmute_student_slice(&bytes[..0]) }.unwrap();
+        assert!(empty.is_empty());
+
+        // 少一个字节，最后一个 Student 不完整
+        assert_eq!(
+            unsafe { transmute_student_slice(&bytes[..bytes.len() - 1]) },
+            Err(TransmuteError::NotDivisible)
+        );
+    }
+
+    #[test]
+    fn test_transmute_student_slice_misaligned() {
+        // 用 u64 数组当缓冲区，起始地址保证 8 字节对齐，往后挪 1 个字节就一定不对齐
+        let buf = [0u64; 5];
+        let bytes =
+            unsafe { std::slice::from_raw_parts(buf.as_ptr().cast::<u8>(), size_of_val(&buf)) };
+
+        assert_eq!(
+            unsafe { transmute_student_slice(&bytes[1..33]) },
+            Err(TransmuteError::Misaligned)
+        );
+        // 地址对齐的优先报：挪 8 个字节是对齐的，32 字节正好两个
+        let view = unsafe { transmute_student_slice(&bytes[8..40]) }.unwrap();
+        assert_eq!(
+            view,
+            &[Student {
+                id: 0,
+                score: 0,
+                age: 0
+            }; 2]
+        );
+
+        // 零大小类型
+        assert_eq!(
+            unsafe { cast_slice::<()>(bytes) },
+            Err(TransmuteError::WouldAlias)
+        );
+    }
 }
 
 #[cfg(test)]
@@ -454,3 +586,240 @@ mod inner_mut_tests {
         }
     }
 }
+
+/// 定长二进制格式：每个学生固定 88 字节，一整块内存直接写文件、读回来，中间不做任何解析
+///
+/// 带 String 的 Student 内存里只有 (ptr, len, cap)，字符串在堆上别的地方，没法整体当字节写出去；
+/// 换成定长数组之后，一个 Vec<StudentFixed> 就是一段连续的字节，
+/// bytemuck::cast_slice 零拷贝地把它看成 &[u8]（编译期检查了没有 padding，运行时不需要 unsafe）
+#[cfg(test)]
+mod bytemuck_tests {
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::time::Instant;
+
+    use bytemuck::{Pod, Zeroable};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    enum Gender {
+        Male,
+        Female,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Student {
+        id: i64,
+        name: String,
+        age: u32,
+        gender: Gender,
+        score: f32,
+    }
+
+    const NAME_LEN: usize = 64;
+
+    /// 字段和 offset：
+    ///   id      0..8
+    ///   name    8..72    UTF-8，后面补 0，所以名字最长 63 字节，至少留一个 0 结尾
+    ///   age     72..76
+    ///   gender  76       0 = 男，1 = 女
+    ///   _pad    77..80   让 score 落在 4 的倍数上
+    ///   score   80..84
+    ///   _tail   84..88   有 i64 字段，整个结构体按 8 字节对齐，大小要凑到 8 的倍数
+    ///
+    /// Pod 要求没有编译器偷偷插进去的 padding（那几个字节是未初始化的，不能当 &[u8] 读），
+    /// 所以两处空隙都写成显式的字段；少写一个，derive(Pod) 直接编译失败
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+    struct StudentFixed {
+        id: i64,
+        name: [u8; NAME_LEN],
+        age: u32,
+        gender: u8,
+        _pad: [u8; 3],
+        score: f32,
+        _tail: [u8; 4],
+    }
+
+    fn to_fixed(s: &Student) -> anyhow::Result<StudentFixed> {
+        let name = s.name.as_bytes();
+        anyhow::ensure!(
+            name.len() < NAME_LEN,
+            "名字有 {} 字节，最多 {} 字节",
+            name.len(),
+            NAME_LEN - 1
+        );
+        anyhow::ensure!(!name.contains(&0), "名字里不能有 \\0，否则读回来会被截断");
+
+        // zeroed：name 剩下的部分和两处 padding 都是 0，写出去的字节是确定的
+        let mut fixed = StudentFixed::zeroed();
+        fixed.id = s.id;
+        fixed.name[..name.len()].copy_from_slice(name);
+        fixed.age = s.age;
+        fixed.gender = match s.gender {
+            Gender::Male => 0,
+            Gender::Female => 1,
+        };
+        fixed.score = s.score;
+        Ok(fixed)
+    }
+
+    /// 文件里读出来的数据不一定可信：名字不是合法 UTF-8 的部分换成 U+FFFD，gender 不是 0 的都当成女
+    fn from_fixed(f: &StudentFixed) -> Student {
+        let len = f.name.iter().position(|&b| b == 0).unwrap_or(NAME_LEN);
+        Student {
+            id: f.id,
+            name: String::from_utf8_lossy(&f.name[..len]).into_owned(),
+            age: f.age,
+            gender: if f.gender == 0 {
+                Gender::Male
+            } else {
+                Gender::Female
+            },
+            score: f.score,
+        }
+    }
+
+    fn students(n: usize) -> Vec<Student> {
+        (0..n)
+            .map(|i| Student {
+                id: i as i64,
+                name: format!("student-{i}"),
+                age: 18 + (i % 5) as u32,
+                gender: if i % 2 == 0 {
+                    Gender::Male
+                } else {
+                    Gender::Female
+                },
+                score: 60.0 + (i % 40) as f32 + 0.5,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_student_fixed_layout() {
+        assert_eq!(size_of::<StudentFixed>(), 88);
+        assert_eq!(align_of::<StudentFixed>(), 8);
+        assert_eq!(std::mem::offset_of!(StudentFixed, gender), 76);
+        assert_eq!(std::mem::offset_of!(StudentFixed, score), 80);
+    }
+
+    #[test]
+    fn test_to_fixed_and_back() {
+        let alice = Student {
+            id: -1,
+            name: "张三 Alice".to_string(),
+            age: 20,
+            gender: Gender::Female,
+            score: 99.5,
+        };
+        let fixed = to_fixed(&alice).unwrap();
+        assert_eq!(fixed.gender, 1);
+        assert_eq!(from_fixed(&fixed), alice);
+
+        // 63 字节可以，64 字节没地方放结尾的 0 了
+        let name_63 = Student {
+            name: "a".repeat(63),
+            ..alice.clone()
+        };
+        assert_eq!(from_fixed(&to_fixed(&name_63).unwrap()), name_63);
+        let name_64 = Student {
+            name: "a".repeat(64),
+            ..alice.clone()
+        };
+        assert!(to_fixed(&name_64).is_err());
+
+        let with_nul = Student {
+            name: "a\0b".to_string(),
+            ..alice
+        };
+        assert!(to_fixed(&with_nul).is_err());
+    }
+
+    #[test]
+    fn test_batch_write_and_read() {
+        let students = students(1000);
+        let fixed: Vec<StudentFixed> = students.iter().map(|s| to_fixed(s).unwrap()).collect();
+
+        // &[StudentFixed] -> &[u8]：零拷贝，88000 字节一次 write 写出去
+        let bytes: &[u8] = bytemuck::cast_slice(&fixed);
+        assert_eq!(bytes.len(), 1000 * 88);
+        assert_eq!(bytes.as_ptr(), fixed.as_ptr().cast::<u8>());
+
+        let path = std::env::temp_dir().join(format!("students-{}.bin", std::process::id()));
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+
+        // 读回来：先分配好按 StudentFixed 对齐的内存，再把它当 &mut [u8] 让 read_exact 直接填进去
+        // 直接读进 Vec<u8> 再 cast_slice 不行：Vec<u8> 只保证 1 字节对齐，cast_slice 会 panic
+        let mut read_back = vec![StudentFixed::zeroed(); 1000];
+        File::open(&path)
+            .unwrap()
+            .read_exact(bytemuck::cast_slice_mut(&mut read_back))
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read_back, fixed);
+        assert_eq!(
+            read_back.iter().map(from_fixed).collect::<Vec<_>>(),
+            students
+        );
+    }
+
+    #[test]
+    #[ignore = "性能对比，手动运行: cargo test --release bench_fixed_vs_json -- --ignored --nocapture"]
+    fn bench_fixed_vs_json() {
+        const ROUNDS: u32 = 1000;
+        let students = students(1000);
+
+        let start = Instant::now();
+        let mut json = Vec::new();
+        for _ in 0..ROUNDS {
+            json = serde_json::to_vec(&students).unwrap();
+        }
+        let json_encode = start.elapsed() / ROUNDS;
+
+        let start = Instant::now();
+        let mut decoded = Vec::new();
+        for _ in 0..ROUNDS {
+            decoded = serde_json::from_slice::<Vec<Student>>(&json).unwrap();
+        }
+        let json_decode = start.elapsed() / ROUNDS;
+        assert_eq!(decoded, students);
+
+        // 定长格式：转换是逐个拷贝名字，转成字节本身不花时间
+        let start = Instant::now();
+        let mut fixed = Vec::new();
+        for _ in 0..ROUNDS {
+            fixed = students
+                .iter()
+                .map(|s| to_fixed(s).unwrap())
+                .collect::<Vec<_>>();
+        }
+        let fixed_encode = start.elapsed() / ROUNDS;
+        let bytes: &[u8] = bytemuck::cast_slice(&fixed);
+
+        // 反过来：字节看成 &[StudentFixed] 还是零拷贝，这里的时间都花在 from_fixed 建 String 上
+        let start = Instant::now();
+        let mut back = Vec::new();
+        for _ in 0..ROUNDS {
+            let view: &[StudentFixed] = bytemuck::cast_slice(bytes);
+            back = view.iter().map(from_fixed).collect::<Vec<_>>();
+        }
+        let fixed_decode = start.elapsed() / ROUNDS;
+        assert_eq!(back, students);
+
+        println!("1000 个学生:");
+        println!(
+            "JSON:  {} 字节，序列化 {:?}，反序列化 {:?}",
+            json.len(),
+            json_encode,
+            json_decode
+        );
+        println!(
+            "定长:  {} 字节，序列化 {:?}，反序列化 {:?}",
+            bytes.len(),
+            fixed_encode,
+            fixed_decode
+        );
+    }
+}
@@ -216,7 +216,10 @@ mod conditional_var_tests {
 #[cfg(test)]
 #[allow(dead_code)]
 mod test_actor {
-    use tokio::sync::{mpsc, oneshot};
+    use std::sync::Arc;
+
+    use tokio::sync::{Mutex, mpsc, oneshot};
+    use tokio::task::JoinHandle;
 
     // --- 1. 定义消息 message -----
     // 使用 enum 是最常见的方式
@@ -226,6 +229,9 @@ mod test_actor {
 
         // 这种消息是请求响应 模式，需要带一个回信地址
         GetCount(oneshot::Sender<u32>),
+
+        // 毒丸：让 actor 处理完已经排队的消息后退出，不用等所有 sender 都被 drop
+        Shutdown,
     }
 
     // ---- 2. 定义 actor 后台打工人 -------------
@@ -248,6 +254,13 @@ mod test_actor {
                         // 把当前状态发回去
                         let _ = respond_to.send(self.count);
                     }
+
+                    MyActorMessage::Shutdown => {
+                        // close 之后 send 都会失败，但已经在队列里的消息还能 recv 出来，
+                        // 全部处理完之后 recv 返回 None，循环自然结束
+                        // 重复的 Shutdown 再 close 一次也没有影响
+                        self.receiver.close();
+                    }
                 }
             }
         }
@@ -258,6 +271,9 @@ mod test_actor {
     #[derive(Clone)]
     pub struct MyActorHandle {
         sender: mpsc::Sender<MyActorMessage>,
+        // JoinHandle 不能 clone，多个 handle 共享同一个
+        // 等待时要持有锁跨越 .await，所以用 tokio 的 Mutex
+        join: Arc<Mutex<Option<JoinHandle<()>>>>,
     }
 
     impl MyActorHandle {
@@ -270,11 +286,14 @@ mod test_actor {
             };
 
             // 关键点，把 Actor 扔到后台去跑 spawm task
-            tokio::spawn(async move {
+            let join = tokio::spawn(async move {
                 actor.run().await;
             });
 
-            Self { sender: sender }
+            Self {
+                sender: sender,
+                join: Arc::new(Mutex::new(Some(join))),
+            }
         }
 
         // 封闭发送逻辑，对用户隐藏 channel 细节
@@ -293,9 +312,49 @@ mod test_actor {
             receiver.await.unwrap()
         }
 
+        // 通知 actor 停下来，不等它真的退出
+        // actor 已经 close 了收件箱的话 send 会失败，直接忽略，所以调用多次也没关系
+        pub async fn shutdown(&self) {
+            let _ = self.sender.send(MyActorMessage::Shutdown).await;
+        }
+
+        // 等 actor 的 run() 结束，actor panic 了会返回 JoinError
+        pub async fn wait_for_shutdown(&self) -> anyhow::Result<()> {
+            let mut join = self.join.lock().await;
+            if let Some(handle) = join.as_mut() {
+                handle.await?;
+                // 已经 join 过了，别的 handle 再等直接返回
+                *join = None;
+            }
+            Ok(())
+        }
+
         // thiserror 定义一个巨大的 enum Error ，列出所有的可能 ，让调用者去 match ，调用者需要知道具体是哪种错误，以便处理
         // anyhow anyhow::Result<T> 可以吞下任何错误，不需要处理特定错误，只要把错误链条打印出来 给开发者看
     }
+
+    #[tokio::test]
+    async fn test_shutdown_after_queued_messages() {
+        let handle = MyActorHandle::new();
+        // 另一个遥控器一直活着，光靠 drop sender 是停不下来的
+        let other = handle.clone();
+
+        for i in 0..10 {
+            handle.say_hell0(format!("actor {i}")).await;
+        }
+        // 排在 Shutdown 前面的查询，用来确认 10 条消息都处理了
+        let (count_tx, count_rx) = oneshot::channel();
+        let _ = handle.sender.send(MyActorMessage::GetCount(count_tx)).await;
+
+        handle.shutdown().await;
+        handle.shutdown().await;
+        handle.wait_for_shutdown().await.unwrap();
+        other.wait_for_shutdown().await.unwrap();
+
+        assert_eq!(count_rx.await.unwrap(), 10);
+        // actor 已经退出，收件箱是关着的
+        assert!(other.sender.is_closed());
+    }
 }
 
 /// T: 'static 意味着 T 是自给自足的，它不依赖于任何外部的、临时的借用数据
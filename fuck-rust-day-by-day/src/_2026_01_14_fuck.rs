@@ -166,3 +166,149 @@ async fn main() {
     let mut pipeline2 = Pipeline::<_, _, _, SafeMode>::new(source2, ToStringProcessor, ConsoleSink);
     pipeline2.run().await;
 }
+
+// ==========================================
+// 6. 并行版流水线：FuturesOrdered
+// ==========================================
+//
+// Pipeline::run 里 source -> process -> sink 是一个一个来的，上一个 process 没结束，下一个就不会开始。
+// 处理器是 I/O 型的（比如从网络下载区块 body）时，大部分时间都在等，完全可以同时发出好几个请求。
+//
+// FuturesOrdered 是一个 future 队列：里面的 future 同时被 poll（并发，但都在当前这一个 task 里，不是多线程），
+// 谁先完成都行，但是 next() 吐出结果的顺序严格等于 push_back 的顺序。
+// 第 2 个先下载完也要等第 1 个，这正是下载区块要的：区块必须按高度顺序交给后面执行。
+//
+// process 要 &mut self，多个调用不能同时借用同一个处理器，所以要求 P: Clone，每个 future 拿一份自己的副本
+
+use futures_util::StreamExt;
+use futures_util::stream::FuturesOrdered;
+
+struct ParallelPipeline<S, P, K>
+where
+    S: Source,
+    P: Processor<S::Item> + Clone,
+    K: Sink<P::Out>,
+{
+    source: S,
+    processor: P,
+    sink: K,
+    /// 最多同时有几个 process 在跑
+    parallelism: usize,
+}
+
+impl<S, P, K> ParallelPipeline<S, P, K>
+where
+    S: Source,
+    P: Processor<S::Item> + Clone,
+    K: Sink<P::Out>,
+{
+    pub fn new(source: S, processor: P, sink: K, parallelism: usize) -> Self {
+        assert!(parallelism > 0, "parallelism must be at least 1");
+        ParallelPipeline {
+            source,
+            processor,
+            sink,
+            parallelism,
+        }
+    }
+
+    pub async fn run(&mut self) {
+        let mut in_flight = FuturesOrdered::new();
+        let mut source_done = false;
+
+        loop {
+            // 1. 把队列补满到 parallelism 个
+            // 注意：等 source.next() 和 sink.send() 的时候，队列里的 future 没有被 poll。
+            // 定时器、socket 在后台照样就绪，只是要等回到 in_flight.next() 时才会被处理。
+            // 这里没用 select! 同时等 source 和队列，是因为 select! 会丢掉没选中的那个 future，
+            // NumberSource::next 这样 “先改状态再 await” 的 source 被丢掉一次就少一个数据
+            while !source_done && in_flight.len() < self.parallelism {
+                match self.source.next().await {
+                    Some(item) => {
+                        let mut processor = self.processor.clone();
+                        in_flight.push_back(async move { processor.process(item).await });
+                    }
+                    None => source_done = true,
+                }
+            }
+
+            // 2. 等队头的结果，按原来的顺序交给 sink；队列空了说明 source 也结束了
+            match in_flight.next().await {
+                Some(processed) => self.sink.send(processed).await,
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod parallel_tests {
+    use std::hash::BuildHasher;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::*;
+
+    /// 模拟下载：随机睡 0-100ms，同时记录最多有几个在并发
+    #[derive(Clone)]
+    struct SlowProcessor {
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+    }
+
+    impl Processor<u32> for SlowProcessor {
+        type Out = u32;
+
+        async fn process(&mut self, input: u32) -> Self::Out {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+
+            // 标准库里没有随机数，RandomState 每次创建的 key 都是随机的，拿来凑合一下
+            let ms = std::collections::hash_map::RandomState::new().hash_one(input) % 101;
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            input
+        }
+    }
+
+    struct CollectSink(Arc<Mutex<Vec<u32>>>);
+
+    impl Sink<u32> for CollectSink {
+        async fn send(&mut self, item: u32) {
+            self.0.lock().unwrap().push(item);
+        }
+    }
+
+    /// 没有延迟的 source，保证并发度只受 parallelism 限制
+    struct RangeSource(std::ops::Range<u32>);
+
+    impl Source for RangeSource {
+        type Item = u32;
+
+        async fn next(&mut self) -> Option<Self::Item> {
+            self.0.next()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_pipeline_keeps_order() {
+        let processor = SlowProcessor {
+            running: Arc::new(AtomicUsize::new(0)),
+            max_running: Arc::new(AtomicUsize::new(0)),
+        };
+        let output = Arc::new(Mutex::new(Vec::new()));
+
+        let mut pipeline = ParallelPipeline::new(
+            RangeSource(0..40),
+            processor.clone(),
+            CollectSink(output.clone()),
+            4,
+        );
+        pipeline.run().await;
+
+        assert_eq!(*output.lock().unwrap(), (0..40).collect::<Vec<_>>());
+        assert_eq!(processor.max_running.load(Ordering::SeqCst), 4);
+    }
+}
@@ -1,5 +1,6 @@
+use alloy::primitives::{B256, keccak256};
 use async_trait::async_trait; // 👈 引入宏
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use tokio::time::{Duration, sleep};
@@ -69,7 +70,79 @@ trait Stage: Send + Sync {
 // 4. 具体实现：HeaderStage
 // ==========================================
 
-struct HeaderStage;
+/// 简化版的区块头，只保留校验链连续性需要的字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    number: BlockNumber,
+    hash: B256,
+    parent_hash: B256,
+}
+
+/// 模拟 P2P 下载器
+///
+/// `canonical` 是正确的链；`bad` 里是分叉 / 恶意节点给的 header，
+/// 每个只会被返回一次（真实场景里回滚后会换一个 peer 重新下载）
+struct MockDownloader {
+    canonical: HashMap<BlockNumber, Header>,
+    bad: HashMap<BlockNumber, Header>,
+}
+
+impl MockDownloader {
+    /// 生成一条 1..=len 的正确链，hash 用 keccak256(number) 模拟
+    fn chain(len: BlockNumber) -> Self {
+        let hash_of = |n: BlockNumber| keccak256(n.to_be_bytes());
+        let canonical = (1..=len)
+            .map(|n| {
+                let header = Header {
+                    number: n,
+                    hash: hash_of(n),
+                    parent_hash: hash_of(n - 1),
+                };
+                (n, header)
+            })
+            .collect();
+        Self {
+            canonical,
+            bad: HashMap::new(),
+        }
+    }
+
+    /// 在高度 `number` 塞一个 parent_hash 对不上的 header
+    fn with_bad_header(mut self, number: BlockNumber) -> Self {
+        let header = Header {
+            number,
+            hash: B256::repeat_byte(0xee),
+            parent_hash: B256::repeat_byte(0xbd),
+        };
+        self.bad.insert(number, header);
+        self
+    }
+
+    fn download(&mut self, number: BlockNumber) -> Header {
+        self.bad
+            .remove(&number)
+            .unwrap_or_else(|| self.canonical[&number])
+    }
+}
+
+struct HeaderStage {
+    downloader: MockDownloader,
+    /// 已经验证通过的 header，相当于数据库里的 Headers 表
+    headers: BTreeMap<BlockNumber, Header>,
+    /// 上一个验证通过的 header 的 hash，下一个 header 的 parent_hash 必须等于它
+    /// None 表示还没有任何 header（从头同步），第一个 header 不做检查
+    last_known_hash: Option<B256>,
+}
+
+impl HeaderStage {
+    fn new(downloader: MockDownloader) -> Self {
+        Self {
+            downloader,
+            headers: BTreeMap::new(),
+            last_known_hash: None,
+        }
+    }
+}
 
 #[async_trait] // 👈 实现处也必须加这个宏
 impl Stage for HeaderStage {
@@ -91,12 +164,27 @@ impl Stage for HeaderStage {
 
         println!("⬇️  [Headers] 下载中... {} -> {}", current, new_height);
 
-        // --- 模拟故障注入 ---
-        // 场景：当我们下载到 #40，且目标是 #50 时，假装发现了分叉
-        if new_height == 40 && target == 50 {
-            println!("⚠️  [Headers] 警告：在 Block #40 发现分叉链！请求回滚至 #30");
-            // 返回回滚指令
-            return StageResult::Unwind { unwind_to: 30 };
+        // 逐个校验链的连续性：header.parent_hash 必须等于上一个块的 hash
+        for number in current + 1..=new_height {
+            let header = self.downloader.download(number);
+
+            if self
+                .last_known_hash
+                .is_some_and(|expected| header.parent_hash != expected)
+            {
+                println!(
+                    "⚠️  [Headers] 警告：Block #{} 的 parent_hash 对不上，发现分叉链！请求回滚至 #{}",
+                    number,
+                    number - 1
+                );
+                // 返回回滚指令
+                return StageResult::Unwind {
+                    unwind_to: number - 1,
+                };
+            }
+
+            self.last_known_hash = Some(header.hash);
+            self.headers.insert(number, header);
         }
 
         // 正常情况
@@ -105,7 +193,9 @@ impl Stage for HeaderStage {
 
     async fn unwind(&mut self, db: &Database, to: BlockNumber) {
         println!("🏳️  [Headers] 正在执行回滚操作 -> 目标 Block #{}", to);
-        // 真实场景会在这里 truncate 数据库表
+        // 删掉 to 之后的 header，last_known_hash 退回到 to 的 hash
+        self.headers.split_off(&(to + 1));
+        self.last_known_hash = self.headers.get(&to).map(|h| h.hash);
         db.save_progress(self.id(), to);
     }
 }
@@ -201,13 +291,65 @@ async fn main() {
     let db = Database::new();
     let mut pipeline = Pipeline::new(db.clone());
 
-    // 添加阶段
-    pipeline.add_stage(HeaderStage);
+    // 添加阶段，Block #40 是一个分叉块
+    pipeline.add_stage(HeaderStage::new(
+        MockDownloader::chain(50).with_bad_header(40),
+    ));
 
-    // 运行！目标高度 50 会触发我们的测试回滚逻辑
+    // 运行！下载到 #40 时会发现 parent_hash 不对，触发回滚
     pipeline.run(50).await;
 }
 
+#[tokio::test]
+async fn test_header_stage_detects_reorg() {
+    let db = Database::new();
+    // 1-10 正常，11 的 parent_hash 是错的
+    let mut stage = HeaderStage::new(MockDownloader::chain(20).with_bad_header(11));
+
+    assert_eq!(
+        stage.execute(&db, 20).await,
+        StageResult::Progress { height: 10 }
+    );
+    db.save_progress(stage.id(), 10);
+
+    assert_eq!(
+        stage.execute(&db, 20).await,
+        StageResult::Unwind { unwind_to: 10 }
+    );
+    stage.unwind(&db, 10).await;
+    assert_eq!(db.get_progress(stage.id()), 10);
+    assert_eq!(stage.last_known_hash, Some(stage.headers[&10].hash));
+
+    // 回滚后重新下载，拿到的是正确的 #11，继续往下同步
+    assert_eq!(
+        stage.execute(&db, 20).await,
+        StageResult::Progress { height: 20 }
+    );
+    db.save_progress(stage.id(), 20);
+    assert_eq!(
+        stage.execute(&db, 20).await,
+        StageResult::Done { height: 20 }
+    );
+
+    // 整条链首尾相连
+    for n in 2..=20 {
+        assert_eq!(stage.headers[&n].parent_hash, stage.headers[&(n - 1)].hash);
+    }
+}
+
+#[tokio::test]
+async fn test_pipeline_recovers_from_reorg() {
+    let db = Database::new();
+    let mut pipeline = Pipeline::new(db.clone());
+    pipeline.add_stage(HeaderStage::new(
+        MockDownloader::chain(20).with_bad_header(11),
+    ));
+
+    pipeline.run(20).await;
+
+    assert_eq!(db.get_progress("Headers"), 20);
+}
+
 #[test]
 fn test_mbdx() -> anyhow::Result<()> {
     let path = Path::new("/tmp/my_mbdx_data");
@@ -139,3 +139,348 @@ fn test_base_58_64() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+// ========================= EIP-712 结构化数据签名 =========================
+//
+// 上面的 sign_message（EIP-191）签的是一串字节，钱包弹窗里用户只能看到一坨 hex。
+// EIP-712 签的是带类型的结构体：钱包能把每个字段列出来给用户看；
+// 而且签名绑定了 domain（应用名字、版本、chain id），同一份数据换一条链、换一个应用就验不过，防止重放。
+//
+// 真正被签名的 32 字节：
+//   keccak256(0x19 0x01 ‖ domainSeparator ‖ hashStruct(message))
+//   hashStruct(s)   = keccak256(typeHash ‖ encodeData(s))
+//   typeHash        = keccak256("StudentAttestation(uint256 id,string name,uint256 score)")
+//   domainSeparator = hashStruct(EIP712Domain{ name, version, chainId })
+// encodeData 里 uint256 直接占 32 字节；string / bytes 这种变长的先 keccak256 再放进去，保证每个字段都是 32 字节
+
+use alloy::primitives::{SignatureError, U256};
+use alloy::signers::SignerSync; // 同步签名 (sign_hash_sync)，不用 .await
+use alloy::sol_types::{Eip712Domain, SolStruct, eip712_domain};
+
+// sol! 宏按 Solidity 的写法声明结构体，自动生成 Rust struct 和 SolStruct 实现（typeHash、encodeData 都有了）
+alloy::sol! {
+    #[derive(Debug, PartialEq, Eq)]
+    struct StudentAttestation {
+        uint256 id;
+        string name;
+        uint256 score;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Student {
+    pub id: u64,
+    pub name: String,
+    pub score: u64,
+}
+
+impl From<&Student> for StudentAttestation {
+    fn from(student: &Student) -> Self {
+        StudentAttestation {
+            id: U256::from(student.id),
+            name: student.name.clone(),
+            score: U256::from(student.score),
+        }
+    }
+}
+
+/// 签名和验签必须用同一个 domain，任何一个字段不同，domainSeparator 就不同
+pub const STUDENT_REGISTRY_DOMAIN: Eip712Domain = eip712_domain! {
+    name: "StudentRegistry",
+    version: "1",
+    chain_id: 1,
+};
+
+/// 手工拼出 EIP-712 要签名的 hash，和 `SolStruct::eip712_signing_hash` 算出来的一样
+pub fn eip712_signing_hash<T: SolStruct>(domain: &Eip712Domain, message: &T) -> B256 {
+    let mut buf = [0u8; 2 + 32 + 32];
+    // 0x19 保证和 RLP 编码的交易不会撞，0x01 是 EIP-191 里 “结构化数据” 的版本号
+    buf[0] = 0x19;
+    buf[1] = 0x01;
+    buf[2..34].copy_from_slice(domain.hash_struct().as_slice());
+    buf[34..].copy_from_slice(message.eip712_hash_struct().as_slice());
+    keccak256(buf)
+}
+
+pub fn student_signing_hash(student: &Student) -> B256 {
+    eip712_signing_hash(&STUDENT_REGISTRY_DOMAIN, &StudentAttestation::from(student))
+}
+
+/// 对学生记录做 EIP-712 签名
+pub fn sign_student(
+    signer: &PrivateKeySigner,
+    student: &Student,
+) -> alloy::signers::Result<Signature> {
+    // 已经是算好的 32 字节 hash 了，用 sign_hash 直接签，不能再用 sign_message（那会再套一层 EIP-191 前缀）
+    signer.sign_hash_sync(&student_signing_hash(student))
+}
+
+/// 从签名里恢复出签名者地址，由调用方和预期的地址比对
+pub fn verify_student_attestation(
+    student: &Student,
+    signature: &Signature,
+) -> Result<Address, SignatureError> {
+    signature.recover_address_from_prehash(&student_signing_hash(student))
+}
+
+#[cfg(test)]
+mod eip712_tests {
+    use alloy::primitives::{address, b256};
+
+    use super::*;
+
+    // EIP-712 规范里的例子：https://eips.ethereum.org/EIPS/eip-712 (Example.js)
+    alloy::sol! {
+        struct Person {
+            string name;
+            address wallet;
+        }
+
+        struct Mail {
+            Person from;
+            Person to;
+            string contents;
+        }
+    }
+
+    /// 规范里用的私钥：keccak256("cow")
+    fn cow_signer() -> PrivateKeySigner {
+        PrivateKeySigner::from_bytes(&keccak256("cow")).unwrap()
+    }
+
+    #[test]
+    fn test_eip712_spec_vector() {
+        let domain = eip712_domain! {
+            name: "Ether Mail",
+            version: "1",
+            chain_id: 1,
+            verifying_contract: address!("CcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"),
+        };
+        let mail = Mail {
+            from: Person {
+                name: "Cow".into(),
+                wallet: address!("CD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"),
+            },
+            to: Person {
+                name: "Bob".into(),
+                wallet: address!("bBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"),
+            },
+            contents: "Hello, Bob!".into(),
+        };
+
+        assert_eq!(
+            domain.hash_struct(),
+            b256!("f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f")
+        );
+        assert_eq!(
+            mail.eip712_hash_struct(),
+            b256!("c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e")
+        );
+        let hash = eip712_signing_hash(&domain, &mail);
+        assert_eq!(
+            hash,
+            b256!("be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2")
+        );
+        assert_eq!(hash, mail.eip712_signing_hash(&domain));
+
+        let signer = cow_signer();
+        assert_eq!(
+            signer.address(),
+            address!("CD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826")
+        );
+        let signature = signer.sign_hash_sync(&hash).unwrap();
+        assert_eq!(
+            signature.r(),
+            U256::from_be_bytes(
+                b256!("4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d").0
+            )
+        );
+        assert_eq!(
+            signature.s(),
+            U256::from_be_bytes(
+                b256!("07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b91562").0
+            )
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify_student() {
+        let student = Student {
+            id: 42,
+            name: "Alice".into(),
+            score: 95,
+        };
+
+        // typeHash 就是类型签名字符串的 keccak256
+        let attestation = StudentAttestation::from(&student);
+        assert_eq!(
+            StudentAttestation::eip712_encode_type(),
+            "StudentAttestation(uint256 id,string name,uint256 score)"
+        );
+        assert_eq!(
+            attestation.eip712_type_hash(),
+            keccak256("StudentAttestation(uint256 id,string name,uint256 score)")
+        );
+        assert_eq!(
+            student_signing_hash(&student),
+            attestation.eip712_signing_hash(&STUDENT_REGISTRY_DOMAIN)
+        );
+
+        let signer = cow_signer();
+        let signature = sign_student(&signer, &student).unwrap();
+        assert_eq!(
+            verify_student_attestation(&student, &signature).unwrap(),
+            signer.address()
+        );
+
+        // 分数被改过：恢复出来的是另一个（随机的）地址
+        let tampered = Student {
+            score: 100,
+            ..student.clone()
+        };
+        assert_ne!(
+            verify_student_attestation(&tampered, &signature).ok(),
+            Some(signer.address())
+        );
+
+        // 换一条链的 domain：同样的数据，签名对不上
+        let other_chain = eip712_domain! {
+            name: "StudentRegistry",
+            version: "1",
+            chain_id: 5,
+        };
+        let hash = eip712_signing_hash(&other_chain, &attestation);
+        assert_ne!(
+            signature.recover_address_from_prehash(&hash).ok(),
+            Some(signer.address())
+        );
+    }
+}
+
+// ========================= EIP-55 校验和地址 =========================
+//
+// test_address 里打印出来的地址是大小写混合的，这个大小写就是 EIP-55 的校验和：
+//   hash = keccak256(小写的 40 个 hex 字符)       注意 hash 的是 ASCII 字符串，不是 20 字节的地址
+//   第 i 个字符是字母时，hash 的第 i 个 nibble（半字节）>= 8 就大写，否则小写；数字没有大小写，不参与
+// 地址抄错一个字符，大小写对上的概率大约只有 1/2^15（平均 15 个字母）。
+// 从配置文件、命令行读用户填的地址时要校验，否则打错的地址也能解析成功，钱就转丢了
+
+/// 解析带 EIP-55 校验和的地址失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressParseError {
+    /// 去掉 0x 之后不是 40 个字符
+    InvalidLength(usize),
+    InvalidHexChar(char),
+    /// 第 `index` 个 hex 字符（不算 0x）的大小写和校验和对不上
+    ChecksumMismatch {
+        index: usize,
+    },
+}
+
+impl core::fmt::Display for AddressParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AddressParseError::InvalidLength(len) => {
+                write!(f, "expected 40 hex characters, got {len}")
+            }
+            AddressParseError::InvalidHexChar(c) => write!(f, "invalid hex character {c:?}"),
+            AddressParseError::ChecksumMismatch { index } => {
+                write!(f, "EIP-55 checksum mismatch at character {index}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AddressParseError {}
+
+// Address 是 alloy 里的类型，TryFrom 也是标准库的 trait，孤儿规则不允许在这里 impl TryFrom<&str> for Address，
+// 所以写成一个普通函数
+/// 严格按 EIP-55 解析地址：全小写、全大写的地址只有在校验和刚好就是这样时才能通过
+pub fn parse_checksummed_address(s: &str) -> Result<Address, AddressParseError> {
+    let hex = s.strip_prefix("0x").unwrap_or(s);
+    if hex.len() != 40 {
+        return Err(AddressParseError::InvalidLength(hex.len()));
+    }
+    if let Some(c) = hex.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(AddressParseError::InvalidHexChar(c));
+    }
+
+    let lower = hex.to_ascii_lowercase();
+    let hash = keccak256(lower.as_bytes());
+    for (i, c) in hex.bytes().enumerate() {
+        if c.is_ascii_digit() {
+            continue;
+        }
+        // 偶数位取高 4 位，奇数位取低 4 位
+        let nibble = (hash[i / 2] >> ((1 - i % 2) * 4)) & 0xf;
+        if c.is_ascii_uppercase() != (nibble >= 8) {
+            return Err(AddressParseError::ChecksumMismatch { index: i });
+        }
+    }
+
+    let mut bytes = [0u8; 20];
+    hex::decode_to_slice(&lower, &mut bytes).expect("checked above: 40 hex characters");
+    Ok(Address::from(bytes))
+}
+
+#[cfg(test)]
+mod checksum_tests {
+    use super::*;
+
+    // 前 8 个是 EIP-55 规范里的例子（全大写、全小写的也是合法的校验和），
+    // 后面是 EIP-712 例子里的 verifyingContract 和 USDT 合约地址
+    const VALID: [&str; 10] = [
+        "0x52908400098527886E0F7030069857D2E4169EE7",
+        "0x8617E340B3D01FA5F11F306F4090FD50E238070D",
+        "0xde709f2102306220921060314715629080e2fb77",
+        "0x27b1fdb04752bbc536007a920d24acb045561c26",
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC",
+        "0xdAC17F958D2ee523a2206206994597C13D831ec7",
+    ];
+
+    #[test]
+    fn test_valid_checksum() {
+        for s in VALID {
+            let address = parse_checksummed_address(s).unwrap();
+            // Display 输出的就是校验和格式，转回去应该一模一样
+            assert_eq!(address.to_string(), s);
+            assert_eq!(address, Address::parse_checksummed(s, None).unwrap());
+            // 0x 前缀可以省略
+            assert_eq!(parse_checksummed_address(&s[2..]), Ok(address));
+        }
+    }
+
+    #[test]
+    fn test_wrong_case() {
+        for s in VALID {
+            // 把第一个字母的大小写反过来
+            let index = s[2..].find(|c: char| c.is_ascii_alphabetic()).unwrap();
+            let mut wrong: Vec<u8> = s.bytes().collect();
+            wrong[2 + index] ^= 0x20;
+            let wrong = String::from_utf8(wrong).unwrap();
+
+            assert_eq!(
+                parse_checksummed_address(&wrong),
+                Err(AddressParseError::ChecksumMismatch { index }),
+                "{wrong}"
+            );
+            assert!(Address::parse_checksummed(&wrong, None).is_err());
+        }
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(
+            parse_checksummed_address("0x1234"),
+            Err(AddressParseError::InvalidLength(4))
+        );
+        assert_eq!(
+            parse_checksummed_address("0xZe709f2102306220921060314715629080e2fb77"),
+            Err(AddressParseError::InvalidHexChar('Z'))
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use rocksdb::WriteBatch;
+use std::sync::Arc;
+use std::sync::mpsc;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rocksdb::{DB, WriteBatch};
 
 #[test]
 fn test_rocketdb_crud() -> anyhow::Result<(), Box<dyn std::error::Error>> {
@@ -92,3 +96,188 @@ fn test_scan() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+// ==========================================
+// 流水线批量写入
+// ==========================================
+// 索引器场景：每一批数据要先 “组装” 成 WriteBatch（逐个 put，会拷贝 key/value），再提交给 RocksDB。
+// 组装是纯 CPU 活，可以并行；提交必须串行且按顺序（后面的批次可能覆盖前面的 key）。
+// 所以流水线是：rayon 线程池并行组装 -> 有界 channel -> 写线程按原始顺序依次 db.write
+//
+// 组装按窗口进行：每次取 PIPELINE_DEPTH 个，rayon 并行组装完再按顺序送进 channel，
+// 不需要重排缓冲区；写线程慢的时候 send 会阻塞，内存里最多只有两个窗口的批次
+
+pub type KvPair = (Vec<u8>, Vec<u8>);
+
+/// 组装线程最多领先写线程这么多个批次
+const PIPELINE_DEPTH: usize = 16;
+
+/// 把一组 key/value 组装成一个 WriteBatch
+fn build_batch(kvs: Vec<KvPair>) -> WriteBatch {
+    let mut batch = WriteBatch::default();
+    for (k, v) in kvs {
+        batch.put(k, v);
+    }
+    batch
+}
+
+/// 串行版本：组装一个，写一个，作为对照组
+pub fn batch_write_serial(db: &DB, groups: Vec<Vec<KvPair>>) -> anyhow::Result<()> {
+    for kvs in groups {
+        db.write(build_batch(kvs))?;
+    }
+    Ok(())
+}
+
+/// 流水线的骨架：`build` 在 rayon 线程池上并行执行，写入顺序与 `items` 的顺序严格一致
+fn write_pipeline<T, F>(db: &DB, items: Vec<T>, build: F) -> anyhow::Result<()>
+where
+    T: Send,
+    F: Fn(T) -> WriteBatch + Sync,
+{
+    let total = items.len();
+    // WriteBatch 是 Send 的，可以从组装线程交给写线程
+    let (tx, rx) = mpsc::sync_channel::<WriteBatch>(PIPELINE_DEPTH);
+
+    // 用 scope 线程，db 和 build 借用就行，不用 'static
+    let build = &build;
+    std::thread::scope(|s| {
+        let producer = s.spawn(move || {
+            let mut items = items.into_iter();
+            loop {
+                let window: Vec<T> = items.by_ref().take(PIPELINE_DEPTH).collect();
+                if window.is_empty() {
+                    return;
+                }
+                // collect 保持原来的顺序
+                let batches: Vec<WriteBatch> = window.into_par_iter().map(build).collect();
+                for batch in batches {
+                    // 写线程出错时接收端已经 drop 了，不用再组装后面的
+                    if tx.send(batch).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        let mut written = 0;
+        let mut result: anyhow::Result<()> = Ok(());
+        for batch in &rx {
+            if let Err(e) = db.write(batch) {
+                result = Err(e.into());
+                break;
+            }
+            written += 1;
+        }
+        // 先 drop 接收端，阻塞在 send 上的组装线程才会退出，然后再 join
+        drop(rx);
+        producer
+            .join()
+            .map_err(|_| anyhow::anyhow!("组装线程 panic 了"))?;
+        result?;
+        anyhow::ensure!(written == total, "只写入了 {written}/{total} 个批次");
+        Ok(())
+    })
+}
+
+/// 流水线版本：组装和写入同时进行，写入顺序与 `batches` 的顺序严格一致
+///
+/// 整个过程是阻塞的（rayon + RocksDB 同步 API），和 `async_write_batch` 一样丢到 blocking 线程池，
+/// 所以 db 要传 `Arc`，单线程运行时里也能调用
+pub async fn batch_write_with_pipeline(
+    db: Arc<DB>,
+    batches: Vec<WriteBatch>,
+) -> anyhow::Result<()> {
+    // 已经组装好的批次，组装这一步是原样传过去
+    tokio::task::spawn_blocking(move || write_pipeline(&db, batches, |batch| batch)).await?
+}
+
+/// 同上，但输入是原始的 key/value 分组，组装这一步才真正并行起来
+pub async fn batch_write_groups_with_pipeline(
+    db: Arc<DB>,
+    groups: Vec<Vec<KvPair>>,
+) -> anyhow::Result<()> {
+    tokio::task::spawn_blocking(move || write_pipeline(&db, groups, build_batch)).await?
+}
+
+/// 单个批次的异步写入：db.write 会阻塞（WAL fsync、memtable 满了还会 stall），
+/// 丢到 blocking 线程池里执行，避免卡住 tokio 的工作线程
+pub async fn async_write_batch(db: Arc<DB>, batch: WriteBatch) -> anyhow::Result<()> {
+    tokio::task::spawn_blocking(move || db.write(batch)).await??;
+    Ok(())
+}
+
+fn make_groups(batches: usize, per_batch: usize) -> Vec<Vec<KvPair>> {
+    (0..batches)
+        .map(|b| {
+            (0..per_batch)
+                .map(|i| {
+                    let key = format!("batch:{b:05}:{i:03}").into_bytes();
+                    let value = format!("value-{b}-{i}").into_bytes();
+                    (key, value)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// 默认的单线程运行时，确认不依赖多线程运行时
+#[tokio::test]
+async fn test_batch_write_with_pipeline() -> anyhow::Result<()> {
+    let path = "mydb_ex4";
+    let _ = DB::destroy(&rocksdb::Options::default(), path);
+    let db = Arc::new(DB::open_default(path)?);
+
+    // 同一个 key 在后面的批次里被覆盖，只有严格按顺序提交，最后读到的才是最后一批的值
+    let mut groups = make_groups(100, 10);
+    for (b, kvs) in groups.iter_mut().enumerate() {
+        kvs.push((b"last_batch".to_vec(), b.to_string().into_bytes()));
+    }
+
+    batch_write_groups_with_pipeline(db.clone(), groups).await?;
+
+    assert_eq!(db.get(b"batch:00042:007")?, Some(b"value-42-7".to_vec()));
+    assert_eq!(db.get(b"last_batch")?, Some(b"99".to_vec()));
+
+    // 已经组装好的批次
+    let batches: Vec<WriteBatch> = (0..50)
+        .map(|b| {
+            let mut batch = WriteBatch::default();
+            batch.put(b"last_batch", format!("prebuilt-{b}"));
+            batch
+        })
+        .collect();
+    batch_write_with_pipeline(db.clone(), batches).await?;
+    assert_eq!(db.get(b"last_batch")?, Some(b"prebuilt-49".to_vec()));
+
+    let mut batch = WriteBatch::default();
+    batch.put(b"async", b"ok");
+    async_write_batch(db.clone(), batch).await?;
+    assert_eq!(db.get(b"async")?, Some(b"ok".to_vec()));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "性能对比，手动运行: cargo test bench_serial_vs_pipeline -- --ignored --nocapture"]
+async fn bench_serial_vs_pipeline() -> anyhow::Result<()> {
+    let serial_path = "mydb_ex5";
+    let pipeline_path = "mydb_ex6";
+    let _ = DB::destroy(&rocksdb::Options::default(), serial_path);
+    let _ = DB::destroy(&rocksdb::Options::default(), pipeline_path);
+
+    // 1000 个批次，每批 100 个 key/value
+    let serial_db = DB::open_default(serial_path)?;
+    let start = std::time::Instant::now();
+    batch_write_serial(&serial_db, make_groups(1000, 100))?;
+    let serial = start.elapsed();
+
+    let pipeline_db = Arc::new(DB::open_default(pipeline_path)?);
+    let start = std::time::Instant::now();
+    batch_write_groups_with_pipeline(pipeline_db, make_groups(1000, 100)).await?;
+    let pipelined = start.elapsed();
+
+    println!("串行:   {:?}", serial);
+    println!("流水线: {:?}", pipelined);
+    Ok(())
+}
@@ -1,9 +1,15 @@
 mod executor_practice {
+    use std::cell::RefCell;
     use std::collections::VecDeque;
     use std::future::Future;
     use std::pin::Pin;
-    use std::sync::{Arc, Mutex};
-    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Wake, Waker};
+    use std::time::Duration;
+
+    use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+    use tokio::task::LocalSet;
 
     // ==========================================
     // 第一步：定义 Task（任务）
@@ -243,6 +249,235 @@ mod executor_practice {
         }
     }
 
+    // ==========================================
+    // 第五步：多线程 + 工作窃取（work-stealing）
+    // ==========================================
+    //
+    // SimpleExecutor 只有一个线程、一个全局队列。多线程版本的结构（tokio 的多线程调度器也是这个思路）：
+    //
+    //   spawn / wake ──push──▶ Injector（全局队列，所有线程共享）
+    //                              │ steal_batch_and_pop
+    //                              ▼
+    //   线程 0: Worker（本地队列）  线程 1: Worker  ...  线程 N-1: Worker
+    //        ▲                        │
+    //        └──── Stealer 偷任务 ◀───┘   自己的队列空了，先看 Injector，再去偷别人的
+    //
+    // 每个线程就相当于一个 SimpleExecutor，只是队列换成了 crossbeam_deque：
+    // - Worker: 只有所属线程能 push/pop，几乎无锁
+    // - Stealer: Worker 的 “另一端”，别的线程可以从这里偷
+    // - Injector: 多生产者多消费者的全局队列
+
+    /// 多线程 Executor 里的任务
+    ///
+    /// 和上面的 Task 不同，这里的 Waker 不再手写 RawWakerVTable，而是实现标准库的 `Wake` trait，
+    /// `Waker::from(Arc<MtTask>)` 会帮我们生成 vtable，效果和第三步完全一样
+    struct MtTask {
+        /// 完成后置为 None，防止被别的线程再 poll 一次已经 Ready 的 Future
+        future: Mutex<Option<Pin<Box<dyn Future<Output = ()> + Send>>>>,
+        shared: Arc<Shared>,
+    }
+
+    /// 所有线程共享的状态
+    struct Shared {
+        injector: Injector<Arc<MtTask>>,
+        /// 还没完成的任务数，归零时所有线程退出
+        pending: AtomicUsize,
+        /// 空闲线程在这里睡觉，有新任务进 Injector 时叫醒一个
+        idle: Mutex<()>,
+        cvar: Condvar,
+    }
+
+    impl Shared {
+        fn schedule(&self, task: Arc<MtTask>) {
+            self.injector.push(task);
+            self.cvar.notify_one();
+        }
+    }
+
+    impl Wake for MtTask {
+        fn wake(self: Arc<Self>) {
+            // wake 可能发生在任意线程，碰不到别人的 Worker，所以统一放回 Injector
+            self.shared.schedule(self.clone());
+        }
+    }
+
+    pub struct MultiThreadExecutor {
+        threads: usize,
+        shared: Arc<Shared>,
+    }
+
+    impl MultiThreadExecutor {
+        pub fn new(threads: usize) -> Self {
+            assert!(threads > 0, "至少需要一个线程");
+            MultiThreadExecutor {
+                threads,
+                shared: Arc::new(Shared {
+                    injector: Injector::new(),
+                    pending: AtomicUsize::new(0),
+                    idle: Mutex::new(()),
+                    cvar: Condvar::new(),
+                }),
+            }
+        }
+
+        /// 提交一个 Future，直接进全局队列
+        pub fn spawn<F>(&self, future: F)
+        where
+            F: Future<Output = ()> + Send + 'static,
+        {
+            self.shared.pending.fetch_add(1, Ordering::SeqCst);
+            let task = Arc::new(MtTask {
+                future: Mutex::new(Some(Box::pin(future))),
+                shared: self.shared.clone(),
+            });
+            self.shared.schedule(task);
+        }
+
+        /// 启动 N 个线程一起跑，直到所有任务完成
+        pub fn run(&self) {
+            let workers: Vec<Worker<Arc<MtTask>>> =
+                (0..self.threads).map(|_| Worker::new_fifo()).collect();
+            let stealers: Arc<Vec<Stealer<Arc<MtTask>>>> =
+                Arc::new(workers.iter().map(|w| w.stealer()).collect());
+
+            std::thread::scope(|s| {
+                for (index, local) in workers.into_iter().enumerate() {
+                    let shared = self.shared.clone();
+                    let stealers = stealers.clone();
+                    s.spawn(move || worker_loop(index, local, &shared, &stealers));
+                }
+            });
+        }
+    }
+
+    /// 找下一个任务：本地队列 -> 全局队列 -> 偷别的线程
+    fn find_task(
+        index: usize,
+        local: &Worker<Arc<MtTask>>,
+        shared: &Shared,
+        stealers: &[Stealer<Arc<MtTask>>],
+    ) -> Option<Arc<MtTask>> {
+        if let Some(task) = local.pop() {
+            return Some(task);
+        }
+
+        // Steal::Retry 表示和别的线程抢的时候冲突了，再试一次即可
+        std::iter::repeat_with(|| {
+            // 从全局队列一次拿一批到本地队列，减少对 Injector 的争用
+            shared.injector.steal_batch_and_pop(local).or_else(|| {
+                stealers
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != index)
+                    .map(|(_, stealer)| stealer.steal())
+                    .collect::<Steal<_>>()
+            })
+        })
+        .find(|steal| !steal.is_retry())
+        .and_then(|steal| steal.success())
+    }
+
+    fn worker_loop(
+        index: usize,
+        local: Worker<Arc<MtTask>>,
+        shared: &Arc<Shared>,
+        stealers: &[Stealer<Arc<MtTask>>],
+    ) {
+        loop {
+            let Some(task) = find_task(index, &local, shared, stealers) else {
+                if shared.pending.load(Ordering::SeqCst) == 0 {
+                    return;
+                }
+                // 没活干但还有任务没完成（可能正在别的线程上 poll），睡一会儿。
+                // 带超时是为了兜底：检查队列和开始 wait 之间如果刚好有人 notify，这次通知会丢
+                let guard = shared.idle.lock().unwrap();
+                let _ = shared
+                    .cvar
+                    .wait_timeout(guard, Duration::from_millis(1))
+                    .unwrap();
+                continue;
+            };
+
+            let waker = Waker::from(task.clone());
+            let mut cx = Context::from_waker(&waker);
+
+            // 同一个任务可能刚 wake 就被别的线程偷走，这里的锁保证同一时刻只有一个线程在 poll 它
+            let mut slot = task.future.lock().unwrap();
+            let Some(future) = slot.as_mut() else {
+                continue;
+            };
+
+            if future.as_mut().poll(&mut cx).is_ready() {
+                *slot = None;
+                if shared.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                    // 最后一个任务完成了，叫醒所有在睡觉的线程让它们退出
+                    shared.cvar.notify_all();
+                }
+            }
+        }
+    }
+
+    // ==========================================
+    // 第六步：单线程 Executor 跑 !Send 的 Future
+    // ==========================================
+    //
+    // 上面两个 Executor 都要求 Future: Send，因为任务可能被 wake 到别的线程上（Waker 本身必须 Send + Sync）。
+    // 但像 Rc<RefCell<T>> 这种东西是 !Send 的，持有它的 Future 也是 !Send，根本 spawn 不进去。
+    //
+    // 如果能保证任务永远只在当前线程上被 poll，就不需要 Send。tokio 的 LocalSet 就是干这个的：
+    // - LocalSet 自己是一个 Future，里面装着一堆只能在当前线程跑的任务
+    // - 在 LocalSet 里面调用 tokio::task::spawn_local，任务就挂到这个 LocalSet 上
+    // - 跨线程的 wake 只是把任务标记为就绪，真正的 poll 仍然回到这个线程
+
+    /// 和 Task 一样是类型擦除后的 Future，但没有 `+ Send`，也不需要 Mutex（只有一个线程碰它）
+    struct LocalTask {
+        future: Pin<Box<dyn Future<Output = ()>>>,
+    }
+
+    /// 基于 `tokio::task::LocalSet` 的单线程 Executor，可以 spawn !Send 的 Future
+    pub struct LocalExecutor {
+        /// spawn 时先存起来，run 的时候再统一 spawn_local（spawn_local 必须在 LocalSet 里面调用）
+        /// RefCell 而不是 Mutex：LocalExecutor 本身也是 !Send 的
+        tasks: RefCell<Vec<LocalTask>>,
+    }
+
+    impl LocalExecutor {
+        pub fn new() -> Self {
+            LocalExecutor {
+                tasks: RefCell::new(Vec::new()),
+            }
+        }
+
+        /// 只要求 'static，不要求 Send
+        pub fn spawn<F>(&self, future: F)
+        where
+            F: Future<Output = ()> + 'static,
+        {
+            self.tasks.borrow_mut().push(LocalTask {
+                future: Box::pin(future),
+            });
+        }
+
+        /// 在当前线程上运行，直到所有任务（包括任务里再 spawn_local 出来的）完成
+        pub fn run(&self) {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let local = LocalSet::new();
+
+            let tasks = std::mem::take(&mut *self.tasks.borrow_mut());
+            local.block_on(&runtime, async {
+                for task in tasks {
+                    tokio::task::spawn_local(task.future);
+                }
+            });
+
+            // LocalSet 作为 Future：里面所有任务都结束了才 Ready
+            runtime.block_on(local);
+        }
+    }
+
     // ==========================================
     // 测试用的 Future
     // ==========================================
@@ -251,6 +486,8 @@ mod executor_practice {
     struct CountDown {
         remaining: u32,
         name: &'static str,
+        /// 是否打印每一次 poll，压测时关掉，否则时间全花在 println 上
+        verbose: bool,
     }
 
     impl CountDown {
@@ -258,6 +495,15 @@ mod executor_practice {
             CountDown {
                 remaining: count,
                 name,
+                verbose: true,
+            }
+        }
+
+        fn quiet(count: u32) -> Self {
+            CountDown {
+                remaining: count,
+                name: "",
+                verbose: false,
             }
         }
     }
@@ -267,10 +513,14 @@ mod executor_practice {
 
         fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
             if self.remaining == 0 {
-                println!("[{}] ✅ 完成!", self.name);
+                if self.verbose {
+                    println!("[{}] ✅ 完成!", self.name);
+                }
                 Poll::Ready(())
             } else {
-                println!("[{}] 还剩 {} 次", self.name, self.remaining);
+                if self.verbose {
+                    println!("[{}] 还剩 {} 次", self.name, self.remaining);
+                }
                 self.remaining -= 1;
                 // 唤醒自己，让 Executor 再次 poll
                 cx.waker().wake_by_ref();
@@ -312,4 +562,84 @@ mod executor_practice {
 
         println!("\n=== 完成 ===");
     }
+
+    #[test]
+    fn test_multi_thread_executor() {
+        use std::time::Instant;
+
+        const TASKS: usize = 1000;
+
+        fn submit(
+            spawn: impl Fn(Pin<Box<dyn Future<Output = ()> + Send>>),
+            done: &Arc<AtomicUsize>,
+        ) {
+            for _ in 0..TASKS {
+                let done = done.clone();
+                spawn(Box::pin(async move {
+                    CountDown::quiet(100).await;
+                    done.fetch_add(1, Ordering::SeqCst);
+                }));
+            }
+        }
+
+        // 单线程
+        let done = Arc::new(AtomicUsize::new(0));
+        let executor = SimpleExecutor::new();
+        submit(|f| executor.spawn(f), &done);
+        let start = Instant::now();
+        executor.run();
+        let single = start.elapsed();
+        assert_eq!(done.load(Ordering::SeqCst), TASKS);
+
+        // 4 线程 + 工作窃取
+        let done = Arc::new(AtomicUsize::new(0));
+        let executor = MultiThreadExecutor::new(4);
+        submit(|f| executor.spawn(f), &done);
+        let start = Instant::now();
+        executor.run();
+        let multi = start.elapsed();
+        assert_eq!(done.load(Ordering::SeqCst), TASKS);
+
+        // CountDown 每次 poll 几乎不干活，多线程反而可能更慢（调度开销 > 计算量），这里只打印不断言
+        println!("单线程 SimpleExecutor:     {:?}", single);
+        println!("4 线程 MultiThreadExecutor: {:?}", multi);
+    }
+
+    #[test]
+    fn test_local_executor_with_rc() {
+        use std::rc::Rc;
+
+        // Rc<RefCell<..>> 是 !Send 的，下面这些 Future 交给 SimpleExecutor::spawn 会直接编译失败：
+        //   `Rc<RefCell<Vec<u32>>>` cannot be sent between threads safely
+        let acc: Rc<RefCell<Vec<u32>>> = Rc::new(RefCell::new(Vec::new()));
+
+        let executor = LocalExecutor::new();
+        for i in 0..10u32 {
+            let acc = acc.clone();
+            executor.spawn(async move {
+                // 跨 await 持有 Rc，并让出几次，让任务真正交错执行
+                for _ in 0..i % 3 {
+                    tokio::task::yield_now().await;
+                }
+                acc.borrow_mut().push(i);
+
+                // 任务里还可以继续 spawn_local
+                if i == 9 {
+                    let acc = acc.clone();
+                    tokio::task::spawn_local(async move {
+                        acc.borrow_mut().push(100);
+                    });
+                }
+            });
+        }
+        executor.run();
+
+        let mut values = acc.borrow().clone();
+        values.sort();
+        let mut expected: Vec<u32> = (0..10).collect();
+        expected.push(100);
+        assert_eq!(values, expected);
+        // 所有任务都结束了，clone 出去的 Rc 也都被释放了
+        assert_eq!(Rc::strong_count(&acc), 1);
+    }
 }
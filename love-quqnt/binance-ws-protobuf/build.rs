@@ -6,6 +6,9 @@ fn main() -> Result<()> {
     println!("cargo:rerun-if-changed=proto/market_data.proto");
 
     // 编译 proto 文件
-    prost_build::compile_protos(&["proto/market_data.proto"], &["proto/"])?;
+    // TradeRaw 的 bytes 字段生成 bytes::Bytes 而不是 Vec<u8>，解码时零拷贝
+    prost_build::Config::new()
+        .bytes([".binance.TradeRaw"])
+        .compile_protos(&["proto/market_data.proto"], &["proto/"])?;
     Ok(())
 }
@@ -0,0 +1,210 @@
+// 多个数据源合并成一个流
+//
+// merge_streams：谁先到就先吐谁（StreamMap 每次从随机的位置开始轮询，不会饿死某一路）
+// SortedMergeStream：在此基础上按 key（比如成交时间）重新排序。
+//   多个 WebSocket 连接各自是有序的，但合并之后顺序取决于网络到达时间，会有轻微乱序。
+//   缓冲 window 条消息，每次吐出缓冲区里 key 最小的那条：只要乱序的“距离”小于 window，输出就是有序的。
+//   代价是延迟：缓冲区没攒满之前不会输出（除非上游全部结束）
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::Stream;
+use tokio_stream::{StreamExt, StreamMap};
+
+/// 公平合并 N 个流，按到达顺序输出
+pub fn merge_streams<S>(streams: Vec<S>) -> impl Stream<Item = S::Item>
+where
+    S: Stream + Unpin,
+{
+    stream_map(streams).map(|(_, item)| item)
+}
+
+fn stream_map<S>(streams: Vec<S>) -> StreamMap<usize, S>
+where
+    S: Stream + Unpin,
+{
+    let mut map = StreamMap::with_capacity(streams.len());
+    for (i, s) in streams.into_iter().enumerate() {
+        map.insert(i, s);
+    }
+    map
+}
+
+/// 合并 N 个流，并在 window 大小的缓冲区内按 key 从小到大重排
+// main 里还是按到达顺序处理（merge_streams），这个目前只在测试里用
+#[allow(dead_code)]
+pub struct SortedMergeStream<S: Stream> {
+    inner: StreamMap<usize, S>,
+    key: fn(&S::Item) -> u64,
+    window: usize,
+    // BinaryHeap 是大顶堆，包一层 Reverse 变成小顶堆
+    buffer: BinaryHeap<Reverse<Keyed<S::Item>>>,
+    // 到达序号：key 相同的消息按到达顺序输出（BinaryHeap 本身不稳定）
+    seq: u64,
+    // 所有上游都结束了，之后只需要把缓冲区倒空
+    done: bool,
+}
+
+// 自动推导的 Unpin 要求 S::Item: Unpin（因为 BinaryHeap<Item>），
+// 但我们从来不会 pin 住缓冲区里的 item，只有 inner 需要 pin，而 S: Unpin 已经保证了这一点
+impl<S> Unpin for SortedMergeStream<S> where S: Stream + Unpin {}
+
+#[allow(dead_code)]
+impl<S> SortedMergeStream<S>
+where
+    S: Stream + Unpin,
+{
+    pub fn new(streams: Vec<S>, key: fn(&S::Item) -> u64, window: usize) -> Self {
+        assert!(window > 0, "window 至少为 1");
+        SortedMergeStream {
+            inner: stream_map(streams),
+            key,
+            window,
+            buffer: BinaryHeap::with_capacity(window),
+            seq: 0,
+            done: false,
+        }
+    }
+}
+
+impl<S> Stream for SortedMergeStream<S>
+where
+    S: Stream + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // 上面手动实现了 Unpin，可以直接拿 &mut
+        let this = self.get_mut();
+
+        // 先把缓冲区攒满
+        while !this.done && this.buffer.len() < this.window {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some((_, item))) => {
+                    let key = (this.key)(&item);
+                    this.buffer.push(Reverse(Keyed {
+                        key,
+                        seq: this.seq,
+                        item,
+                    }));
+                    this.seq += 1;
+                }
+                Poll::Ready(None) => this.done = true,
+                // 还没攒满，上游也暂时没数据：等着，inner 已经注册了 waker
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        // 攒满了，或者上游结束了：吐出 key 最小的
+        Poll::Ready(this.buffer.pop().map(|Reverse(keyed)| keyed.item))
+    }
+}
+
+/// 堆里的元素，只按 (key, seq) 比较，item 本身不需要实现 Ord
+struct Keyed<T> {
+    key: u64,
+    seq: u64,
+    item: T,
+}
+
+impl<T> PartialEq for Keyed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.key, self.seq) == (other.key, other.seq)
+    }
+}
+
+impl<T> Eq for Keyed<T> {}
+
+impl<T> PartialOrd for Keyed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Keyed<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.key, self.seq).cmp(&(other.key, other.seq))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::*;
+
+    // (symbol, trade_time)
+    type Tick = (&'static str, u64);
+
+    /// 按给定的到达时间（毫秒）依次吐出元素，配合 start_paused 让到达顺序完全确定
+    fn delayed(items: Vec<(u64, Tick)>) -> Pin<Box<dyn Stream<Item = Tick> + Send>> {
+        let start = Instant::now();
+        Box::pin(futures_util::StreamExt::then(
+            tokio_stream::iter(items),
+            move |(at, tick)| async move {
+                tokio::time::sleep_until(start + Duration::from_millis(at)).await;
+                tick
+            },
+        ))
+    }
+
+    /// 两路行情：到达顺序的 trade_time 是 1,3,2,5,4,7,6,8，相邻两条互相颠倒
+    fn two_sources() -> Vec<Pin<Box<dyn Stream<Item = Tick> + Send>>> {
+        vec![
+            delayed(vec![
+                (10, ("BTCUSDT", 1)),
+                (30, ("BTCUSDT", 2)),
+                (50, ("BTCUSDT", 4)),
+                (70, ("BTCUSDT", 6)),
+            ]),
+            delayed(vec![
+                (20, ("ETHUSDT", 3)),
+                (40, ("ETHUSDT", 5)),
+                (60, ("ETHUSDT", 7)),
+                (80, ("ETHUSDT", 8)),
+            ]),
+        ]
+    }
+
+    fn times(ticks: &[Tick]) -> Vec<u64> {
+        ticks.iter().map(|t| t.1).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_merge_streams_arrival_order() {
+        let merged: Vec<Tick> = merge_streams(two_sources()).collect().await;
+        assert_eq!(times(&merged), vec![1, 3, 2, 5, 4, 7, 6, 8]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sorted_merge_stream() {
+        // window = 2 足够纠正相邻颠倒
+        let sorted: Vec<Tick> = SortedMergeStream::new(two_sources(), |t| t.1, 2)
+            .collect()
+            .await;
+        assert_eq!(times(&sorted), (1..=8).collect::<Vec<_>>());
+        assert_eq!(sorted[0], ("BTCUSDT", 1));
+        assert_eq!(sorted[2], ("ETHUSDT", 3));
+
+        // window = 1 等于不排序，输出就是到达顺序
+        let unsorted: Vec<Tick> = SortedMergeStream::new(two_sources(), |t| t.1, 1)
+            .collect()
+            .await;
+        assert_eq!(times(&unsorted), vec![1, 3, 2, 5, 4, 7, 6, 8]);
+    }
+
+    #[tokio::test]
+    async fn test_sorted_merge_equal_keys_keep_arrival_order() {
+        let a = tokio_stream::iter(vec![("A", 5), ("B", 5), ("C", 1)]);
+        let b = tokio_stream::iter(Vec::<Tick>::new());
+        let out: Vec<Tick> = SortedMergeStream::new(vec![a, b], |t| t.1, 4)
+            .collect()
+            .await;
+        assert_eq!(out, vec![("C", 1), ("A", 5), ("B", 5)]);
+    }
+}
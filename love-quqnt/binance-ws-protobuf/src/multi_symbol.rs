@@ -0,0 +1,183 @@
+// 多个交易对的成交合并成一个流，每笔成交带上它的交易对
+//
+// main 里是 merge_streams：直接合并原始的 WebSocket 消息，交给 BinanceReader 解码。
+// 这里换一个角度：每个交易对一个数据源（BinanceWsSource），各自解码成 Trade，
+// 再用 StreamMap 按交易对合并，输出 (symbol, Trade)，下游可以只挑自己关心的交易对。
+//
+// 数据源统一装成 Pin<Box<dyn Stream<Item = Trade> + Send>>：真实的 WebSocket 和测试里的
+// MockSource 类型不同，装箱之后可以放进同一个 StreamMap
+
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use futures_util::Stream;
+use futures_util::stream::SplitStream;
+use prost::Message as ProstMessage;
+use tokio::net::TcpStream;
+use tokio_stream::{StreamExt, StreamMap};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+
+use crate::binance_proto::Trade;
+
+/// 一个交易对的成交流
+pub type TradeStream = Pin<Box<dyn Stream<Item = Trade> + Send>>;
+
+/// 一个交易对的币安 WebSocket 连接，把 Protobuf 消息解码成 Trade
+///
+/// 解码失败的消息和非二进制消息（Ping/Pong 之类）直接跳过，连接断开流就结束
+pub struct BinanceWsSource {
+    read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+}
+
+// main 里还是合并原始消息（merge_streams）再交给 BinanceReader，这里暂时没有调用方
+#[allow(dead_code)]
+impl BinanceWsSource {
+    pub async fn connect(symbol: &str) -> Result<Self, tungstenite::Error> {
+        let url = format!(
+            "wss://stream.binance.com:9443/ws/{}@trade?responseFormat=proto",
+            symbol.to_lowercase()
+        );
+        let (ws_stream, _) = connect_async(url).await?;
+        let (_, read) = futures_util::StreamExt::split(ws_stream);
+        Ok(BinanceWsSource { read })
+    }
+}
+
+impl Stream for BinanceWsSource {
+    type Item = Trade;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Trade>> {
+        loop {
+            match ready!(Pin::new(&mut self.read).poll_next(cx)) {
+                None => return Poll::Ready(None),
+                Some(Ok(Message::Binary(payload))) => match Trade::decode(payload) {
+                    Ok(trade) => return Poll::Ready(Some(trade)),
+                    Err(e) => eprintln!("Protobuf 解码失败: {}", e),
+                },
+                Some(Ok(_)) => {}
+                Some(Err(e)) => eprintln!("WebSocket 错误: {:?}", e),
+            }
+        }
+    }
+}
+
+/// 按交易对合并多个成交流，输出 (symbol, Trade)
+///
+/// 和 merge_streams 一样谁先到先吐；同一个交易对内部的顺序不变
+pub struct MultiSymbolStream {
+    inner: StreamMap<String, TradeStream>,
+}
+
+#[allow(dead_code)]
+impl MultiSymbolStream {
+    /// 每个交易对连一条币安 WebSocket，任意一条连不上就返回错误
+    pub async fn connect(symbols: Vec<String>) -> Result<Self, tungstenite::Error> {
+        let mut sources = Vec::with_capacity(symbols.len());
+        for symbol in symbols {
+            let source: TradeStream = Box::pin(BinanceWsSource::connect(&symbol).await?);
+            sources.push((symbol, source));
+        }
+        Ok(Self::new(sources))
+    }
+
+    /// 用现成的数据源组装，同一个交易对出现两次时后面的替换前面的（StreamMap::insert 的语义）
+    pub fn new(sources: impl IntoIterator<Item = (String, TradeStream)>) -> Self {
+        let mut inner = StreamMap::new();
+        for (symbol, source) in sources {
+            inner.insert(symbol, source);
+        }
+        MultiSymbolStream { inner }
+    }
+
+    /// 只要某一个交易对的成交
+    pub fn filter_symbol(self, symbol: String) -> impl Stream<Item = Trade> {
+        self.filter_map(move |(s, trade)| (s == symbol).then_some(trade))
+    }
+}
+
+impl Stream for MultiSymbolStream {
+    type Item = (String, Trade);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMBOLS: [&str; 3] = ["BTCUSDT", "ETHUSDT", "BNBUSDT"];
+
+    /// 不连网络，按顺序吐出事先准备好的成交
+    struct MockSource(std::vec::IntoIter<Trade>);
+
+    impl MockSource {
+        fn new(symbol: &str, n: i64) -> Self {
+            let trades: Vec<Trade> = (0..n)
+                .map(|i| Trade {
+                    event_type: "trade".into(),
+                    symbol: symbol.into(),
+                    trade_id: i,
+                    price: format!("{}.00", 100 + i),
+                    ..Default::default()
+                })
+                .collect();
+            MockSource(trades.into_iter())
+        }
+    }
+
+    impl Stream for MockSource {
+        type Item = Trade;
+
+        fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Trade>> {
+            Poll::Ready(self.get_mut().0.next())
+        }
+    }
+
+    fn mock_stream(n: i64) -> MultiSymbolStream {
+        MultiSymbolStream::new(SYMBOLS.iter().map(|s| {
+            let source: TradeStream = Box::pin(MockSource::new(s, n));
+            (s.to_string(), source)
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_multi_symbol_tagged() {
+        let items: Vec<(String, Trade)> = mock_stream(10).collect().await;
+        assert_eq!(items.len(), 30);
+
+        for symbol in SYMBOLS {
+            let ids: Vec<i64> = items
+                .iter()
+                .filter(|(s, _)| s == symbol)
+                .map(|(_, t)| t.trade_id)
+                .collect();
+            // 每个交易对 10 笔都到了，而且顺序不变
+            assert_eq!(ids, (0..10).collect::<Vec<_>>(), "{symbol}");
+        }
+        // 标签和成交本身的交易对一致
+        assert!(items.iter().all(|(s, t)| *s == t.symbol));
+    }
+
+    #[tokio::test]
+    async fn test_filter_symbol() {
+        let trades: Vec<Trade> = mock_stream(10)
+            .filter_symbol("ETHUSDT".to_string())
+            .collect()
+            .await;
+        assert_eq!(trades.len(), 10);
+        assert!(trades.iter().all(|t| t.symbol == "ETHUSDT"));
+
+        let none: Vec<Trade> = mock_stream(10)
+            .filter_symbol("DOGEUSDT".to_string())
+            .collect()
+            .await;
+        assert!(none.is_empty());
+    }
+}
@@ -0,0 +1,331 @@
+// 读 WebSocket 和聚合 K 线拆成两个任务，中间用有界 channel 连起来
+//
+// 以前在同一个任务里：聚合慢了，就没人去读 socket，数据堆在 TCP 缓冲区里，
+// 币安那边发不出去，时间长了会直接断开连接。
+// 拆开之后读任务只管解码、往 channel 里塞，socket 一直有人读；
+// channel 满了（聚合跟不上）怎么办由 OverflowMode 决定：
+//   Lossy：丢掉这笔成交，继续读。K 线会少算几笔，但连接不会断
+//   Backpressure：等 channel 有空位再读下一条，一笔不丢，慢得太久还是会被断开
+//
+// channel 里传的是 TradeRaw 而不是 Trade：它的字符串字段只是 WebSocket payload 的切片，
+// 进 channel 不需要为每个字段分配内存，到了聚合任务里再用 TradeRef 校验 UTF-8
+
+use std::collections::HashMap;
+
+use async_channel::{Receiver, Sender, TrySendError};
+use futures_util::{Stream, StreamExt};
+use prost::Message as ProstMessage;
+use tokio_tungstenite::tungstenite::{self, protocol::Message};
+
+use crate::TradeRef;
+use crate::binance_proto::TradeRaw;
+
+/// 读任务和聚合任务之间 channel 的容量
+pub const CHANNEL_CAPACITY: usize = 1000;
+
+/// channel 满了的时候读任务怎么办
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowMode {
+    /// 丢掉这笔成交
+    Lossy,
+    /// 等到 channel 有空位
+    Backpressure,
+}
+
+/// 读任务的统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReaderStats {
+    pub forwarded: u64,
+    pub dropped: u64,
+}
+
+/// 从 WebSocket 读成交，解码之后发给聚合任务
+pub struct BinanceReader {
+    tx: Sender<TradeRaw>,
+    mode: OverflowMode,
+    stats: ReaderStats,
+    // channel 满了只在刚满的时候警告一次，不然 Lossy 模式下每丢一笔打一行
+    full: bool,
+}
+
+impl BinanceReader {
+    pub fn new(tx: Sender<TradeRaw>, mode: OverflowMode) -> Self {
+        Self {
+            tx,
+            mode,
+            stats: ReaderStats::default(),
+            full: false,
+        }
+    }
+
+    /// 一直读到 WebSocket 结束或者聚合任务退出
+    ///
+    /// 返回的时候 Sender 被 drop，channel 关闭，聚合任务处理完剩下的成交就会结束
+    pub async fn run<S>(mut self, mut read: S) -> ReaderStats
+    where
+        S: Stream<Item = Result<Message, tungstenite::Error>> + Unpin,
+    {
+        while let Some(msg) = read.next().await {
+            match msg {
+                Ok(Message::Binary(payload)) => match TradeRaw::decode(payload) {
+                    Ok(raw) => {
+                        if !self.forward(raw).await {
+                            break;
+                        }
+                    }
+                    Err(e) => eprintln!("Protobuf 解码失败: {}", e),
+                },
+                Ok(Message::Text(text)) => println!("收到文本消息: {}", text),
+                Ok(_) => {}
+                Err(e) => eprintln!("WebSocket 错误: {:?}", e),
+            }
+        }
+        self.stats
+    }
+
+    /// 返回 false 表示聚合任务已经退出了，没必要再读
+    async fn forward(&mut self, raw: TradeRaw) -> bool {
+        let raw = match self.tx.try_send(raw) {
+            Ok(()) => {
+                self.full = false;
+                self.stats.forwarded += 1;
+                return true;
+            }
+            Err(TrySendError::Closed(_)) => return false,
+            Err(TrySendError::Full(raw)) => raw,
+        };
+
+        if !self.full {
+            self.full = true;
+            eprintln!(
+                "警告: 聚合跟不上，channel 满了（{} 条），{}",
+                self.tx.capacity().unwrap_or_default(),
+                match self.mode {
+                    OverflowMode::Lossy => "开始丢弃成交",
+                    OverflowMode::Backpressure => "暂停读取 WebSocket",
+                }
+            );
+        }
+
+        match self.mode {
+            OverflowMode::Lossy => {
+                self.stats.dropped += 1;
+                true
+            }
+            OverflowMode::Backpressure => {
+                let sent = self.tx.send(raw).await.is_ok();
+                if sent {
+                    self.stats.forwarded += 1;
+                }
+                sent
+            }
+        }
+    }
+}
+
+/// 一根 K 线
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candle {
+    pub symbol: String,
+    /// 这根 K 线的开始时间（毫秒），是周期的整数倍
+    pub open_time: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub trades: u64,
+}
+
+/// 按成交时间把每个交易对的成交聚合成固定周期的 K 线
+pub struct OhlcvAggregator {
+    interval_ms: i64,
+    // 每个交易对正在累积的那根 K 线
+    open: HashMap<String, Candle>,
+}
+
+impl OhlcvAggregator {
+    pub fn new(interval_ms: i64) -> Self {
+        assert!(interval_ms > 0, "K 线周期必须大于 0");
+        Self {
+            interval_ms,
+            open: HashMap::new(),
+        }
+    }
+
+    /// 累积一笔成交；成交落到下一个周期时，返回上一根已经收盘的 K 线
+    ///
+    /// 比当前 K 线还早的成交（多个连接合并之后的轻微乱序）算进当前这根。
+    /// 价格、数量不是合法数字的成交直接忽略
+    pub fn push(&mut self, trade: &TradeRef) -> Option<Candle> {
+        let (Ok(price), Ok(quantity)) = (trade.price.parse::<f64>(), trade.quantity.parse::<f64>())
+        else {
+            return None;
+        };
+        let open_time = trade.trade_time - trade.trade_time.rem_euclid(self.interval_ms);
+
+        if let Some(candle) = self.open.get_mut(trade.symbol)
+            && open_time <= candle.open_time
+        {
+            candle.high = candle.high.max(price);
+            candle.low = candle.low.min(price);
+            candle.close = price;
+            candle.volume += quantity;
+            candle.trades += 1;
+            return None;
+        }
+
+        let candle = Candle {
+            symbol: trade.symbol.to_string(),
+            open_time,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: quantity,
+            trades: 1,
+        };
+        self.open.insert(candle.symbol.clone(), candle)
+    }
+
+    /// 还没收盘的 K 线，按交易对排序
+    pub fn finish(self) -> Vec<Candle> {
+        let mut candles: Vec<Candle> = self.open.into_values().collect();
+        candles.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        candles
+    }
+
+    /// 一直处理到 channel 关闭，最后把没收盘的 K 线也交出去
+    pub async fn run(mut self, rx: Receiver<TradeRaw>, mut on_candle: impl FnMut(Candle)) {
+        while let Ok(raw) = rx.recv().await {
+            match TradeRef::try_from(&raw) {
+                Ok(trade) => {
+                    if let Some(candle) = self.push(&trade) {
+                        on_candle(candle);
+                    }
+                }
+                Err(e) => eprintln!("字符串字段不是合法的 UTF-8: {}", e),
+            }
+        }
+        self.finish().into_iter().for_each(on_candle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use tokio::time::Instant;
+
+    use super::*;
+    use crate::binance_proto::Trade;
+
+    fn trade(trade_time: i64, price: &str, quantity: &str) -> Trade {
+        Trade {
+            symbol: "BTCUSDT".into(),
+            price: price.into(),
+            quantity: quantity.into(),
+            trade_time,
+            ..Default::default()
+        }
+    }
+
+    fn messages(n: i64) -> impl Stream<Item = Result<Message, tungstenite::Error>> + Unpin {
+        let msgs: Vec<Message> = (0..n)
+            .map(|i| Message::Binary(Bytes::from(trade(i, "100", "1").encode_to_vec())))
+            .collect();
+        futures_util::stream::iter(msgs.into_iter().map(Ok))
+    }
+
+    /// 每收一笔成交睡 100ms 的聚合任务，返回收到的成交数
+    async fn slow_aggregator(rx: Receiver<TradeRaw>) -> u64 {
+        let mut aggregator = OhlcvAggregator::new(60_000);
+        let mut received = 0;
+        while let Ok(raw) = rx.recv().await {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            aggregator.push(&TradeRef::try_from(&raw).unwrap());
+            received += 1;
+        }
+        received
+    }
+
+    #[test]
+    fn test_aggregate_candles() {
+        let mut aggregator = OhlcvAggregator::new(60_000);
+        let view = |t: &Trade| TradeRaw::decode(Bytes::from(t.encode_to_vec())).unwrap();
+
+        let trades = [
+            trade(60_000, "100", "1"),
+            trade(60_500, "105", "2"),
+            trade(61_000, "99", "0.5"),
+            // 乱序：算进当前这根
+            trade(59_999, "101", "1"),
+            // 下一分钟
+            trade(120_000, "110", "3"),
+        ];
+        let mut closed = Vec::new();
+        for t in &trades {
+            let raw = view(t);
+            closed.extend(aggregator.push(&TradeRef::try_from(&raw).unwrap()));
+        }
+
+        assert_eq!(
+            closed,
+            vec![Candle {
+                symbol: "BTCUSDT".into(),
+                open_time: 60_000,
+                open: 100.0,
+                high: 105.0,
+                low: 99.0,
+                close: 101.0,
+                volume: 4.5,
+                trades: 4,
+            }]
+        );
+        let rest = aggregator.finish();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(
+            (rest[0].open_time, rest[0].open, rest[0].trades),
+            (120_000, 110.0, 1)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_aggregator_lossy() {
+        let (tx, rx) = async_channel::bounded(4);
+        let aggregator = tokio::spawn(slow_aggregator(rx));
+
+        // 20 笔一下子全到，聚合任务一笔都还没处理完
+        let stats = BinanceReader::new(tx, OverflowMode::Lossy)
+            .run(messages(20))
+            .await;
+
+        // 读任务不等待，channel 装下的 4 笔之外全丢了；聚合任务可能已经取走了第一笔，腾出一个位置
+        assert_eq!(stats.forwarded + stats.dropped, 20);
+        assert!(stats.dropped >= 15, "{stats:?}");
+        assert_eq!(aggregator.await.unwrap(), stats.forwarded);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_aggregator_backpressure() {
+        let (tx, rx) = async_channel::bounded(4);
+        let aggregator = tokio::spawn(slow_aggregator(rx));
+
+        let started = Instant::now();
+        let stats = BinanceReader::new(tx, OverflowMode::Backpressure)
+            .run(messages(20))
+            .await;
+
+        // 一笔不丢，读任务跟着聚合任务的速度走：最后 4 笔还在 channel 里的时候才读完
+        assert_eq!(
+            stats,
+            ReaderStats {
+                forwarded: 20,
+                dropped: 0
+            }
+        );
+        assert!(started.elapsed() >= Duration::from_millis(100 * 15));
+        assert_eq!(aggregator.await.unwrap(), 20);
+    }
+}
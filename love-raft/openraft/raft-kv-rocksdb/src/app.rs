@@ -1,10 +1,18 @@
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::sync::Arc;
 
 use openraft::Config;
+use openraft::LogId;
+use openraft::StoredMembership;
 use tokio::sync::RwLock;
 
+use crate::backpressure::RaftBackpressureGuard;
+use crate::lease::LeaderLease;
+use crate::metrics::MetricsCollector;
+use crate::utils::watcher::Watcher;
 use crate::ExampleRaft;
+use crate::Node;
 use crate::NodeId;
 
 // Representation of an application state. This struct can be shared around to share
@@ -16,4 +24,114 @@ pub struct App {
     pub raft: ExampleRaft,
     pub key_values: Arc<RwLock<BTreeMap<String, String>>>,
     pub config: Arc<Config>,
+
+    /// Current leader as seen by this node.
+    pub leader: Watcher<Option<NodeId>>,
+    /// Last log id applied to the state machine.
+    pub applied: Watcher<Option<LogId<NodeId>>>,
+    /// Effective membership config.
+    pub membership: Watcher<StoredMembership<NodeId, Node>>,
+    /// Lets `consistent_read` skip the quorum round-trip while it is valid.
+    pub lease: LeaderLease,
+    /// Apply latency and write rate, served at `/metrics`.
+    pub metrics: Arc<MetricsCollector>,
+    /// Blocks `/api/write` while too many writes are already waiting in raft.
+    pub write_guard: Arc<RaftBackpressureGuard>,
+}
+
+impl App {
+    /// Feed [`App::leader`], [`App::applied`], [`App::membership`] and [`App::lease`] from raft
+    /// metrics.
+    ///
+    /// Metrics are updated on every heartbeat and replication progress; the watchers only
+    /// notify their subscribers when the value they track actually changes.
+    ///
+    /// Leader and membership changes are also recorded as `leader_change` and
+    /// `membership_change` spans, which end up in Jaeger when the node is started with an OTLP
+    /// endpoint.
+    ///
+    /// Returns when raft shuts down.
+    pub async fn sync_watchers(&self) {
+        let mut metrics = self.raft.metrics();
+        loop {
+            {
+                let m = metrics.borrow_and_update();
+
+                let old_leader = self.leader.get();
+                if self.leader.send_if_changed(m.current_leader) {
+                    // No span while an election is in progress, only once a new leader is known.
+                    if let Some(new_leader) = m.current_leader {
+                        tracing::info_span!(
+                            "leader_change",
+                            ?old_leader,
+                            new_leader,
+                            term = m.current_term
+                        )
+                        .in_scope(|| tracing::info!("leader changed"));
+                    }
+                }
+
+                self.applied.send_if_changed(m.last_applied);
+                self.lease.update(&m);
+
+                let old_membership = self.membership.get();
+                let new_membership = m.membership_config.as_ref();
+                if self.membership.send_if_changed(new_membership.clone()) {
+                    let (added, removed) = membership_diff(&old_membership, new_membership);
+                    // A new membership log id with the same set of nodes, e.g. after a joint
+                    // config is left, is not worth a span.
+                    if !added.is_empty() || !removed.is_empty() {
+                        tracing::info_span!("membership_change", ?added, ?removed)
+                            .in_scope(|| tracing::info!("membership changed"));
+                    }
+                }
+            }
+
+            if metrics.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Nodes (voters and learners) that are in `new` but not in `old`, and the other way around.
+fn membership_diff(
+    old: &StoredMembership<NodeId, Node>,
+    new: &StoredMembership<NodeId, Node>,
+) -> (Vec<NodeId>, Vec<NodeId>) {
+    let old: BTreeSet<NodeId> = old.nodes().map(|(id, _)| *id).collect();
+    let new: BTreeSet<NodeId> = new.nodes().map(|(id, _)| *id).collect();
+    (
+        new.difference(&old).copied().collect(),
+        old.difference(&new).copied().collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::btreeset;
+    use openraft::Membership;
+
+    use super::*;
+
+    fn stored(voters: BTreeSet<NodeId>, learners: &[NodeId]) -> StoredMembership<NodeId, Node> {
+        let mut nodes = BTreeMap::new();
+        for id in voters.iter().chain(learners) {
+            nodes.insert(*id, Node::default());
+        }
+        StoredMembership::new(None, Membership::new(vec![voters], nodes))
+    }
+
+    #[test]
+    fn test_membership_diff() {
+        let old = stored(btreeset! {1, 2, 3}, &[]);
+
+        // Node 4 joins as a learner, node 3 is removed.
+        let new = stored(btreeset! {1, 2}, &[4]);
+        assert_eq!(membership_diff(&old, &new), (vec![4], vec![3]));
+
+        // Promoting a learner to voter does not add or remove a node.
+        let promoted = stored(btreeset! {1, 2, 4}, &[]);
+        assert_eq!(membership_diff(&new, &promoted), (vec![], vec![]));
+    }
 }
@@ -0,0 +1,322 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use openraft::Entry;
+use openraft::EntryPayload;
+use openraft::LogId;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::fs::File;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::NodeId;
+use crate::TypeConfig;
+
+/// Name of the audit log file in the node's data directory.
+pub const AUDIT_LOG_FILE: &str = "audit.log";
+
+/// The audit log is rotated before it would grow past this many bytes.
+pub const MAX_AUDIT_LOG_SIZE: u64 = 10 * 1024 * 1024;
+
+/// One line of the audit log.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// Milliseconds since the unix epoch when the entry was applied.
+    pub timestamp: u64,
+    pub log_id: LogId<NodeId>,
+    pub operation: String,
+}
+
+/// Why [`AuditLogger::log`] failed.
+#[derive(Debug)]
+pub enum AuditError {
+    /// Writing or syncing the current file failed, e.g. because the disk is full.
+    Write(io::Error),
+    /// The full file could not be renamed to its rotated path or a new file not be opened.
+    Rotate(io::Error),
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditError::Write(e) => write!(f, "failed to write audit log: {}", e),
+            AuditError::Rotate(e) => write!(f, "failed to rotate audit log: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AuditError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AuditError::Write(e) | AuditError::Rotate(e) => Some(e),
+        }
+    }
+}
+
+/// Appends every entry applied to the state machine to a JSON-lines file.
+///
+/// The lines are synced to disk before [`AuditLogger::log_all`] returns, so an entry is never
+/// applied without being in the audit trail. When the file would exceed its size limit it is
+/// renamed to `<path>.1`, replacing the previous rotated file, and a new file is started.
+///
+/// The state machine of this example is rebuilt from the last snapshot on restart, so entries
+/// applied after that snapshot are applied, and logged, once more. Use `log_id` to tell them
+/// apart.
+#[derive(Debug)]
+pub struct AuditLogger {
+    path: PathBuf,
+    max_size: u64,
+    file: Mutex<AuditFile>,
+}
+
+#[derive(Debug)]
+struct AuditFile {
+    file: File,
+    /// Bytes in `file`, tracked here to avoid a `metadata()` call per entry.
+    size: u64,
+}
+
+impl AuditLogger {
+    /// Opens the audit log at `path` for appending, creating it if it does not exist.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::with_max_size(path, MAX_AUDIT_LOG_SIZE).await
+    }
+
+    /// Same as [`AuditLogger::open`], but rotates at `max_size` bytes instead of
+    /// [`MAX_AUDIT_LOG_SIZE`].
+    pub async fn with_max_size(path: impl AsRef<Path>, max_size: u64) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = open_append(&path).await?;
+        let size = file.metadata().await?.len();
+        Ok(Self {
+            path,
+            max_size,
+            file: Mutex::new(AuditFile { file, size }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path the audit log is renamed to on rotation.
+    pub fn rotated_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".1");
+        path.into()
+    }
+
+    /// Appends `entry` to the audit log and waits until it is on disk.
+    pub async fn log(&self, entry: &Entry<TypeConfig>) -> Result<(), AuditError> {
+        self.log_all(std::slice::from_ref(entry)).await
+    }
+
+    /// Appends `entries` in order and waits until they are on disk, with a single sync.
+    pub async fn log_all(&self, entries: &[Entry<TypeConfig>]) -> Result<(), AuditError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        // Held across the writes and the rotation, so lines are never interleaved or lost.
+        let mut current = self.file.lock().await;
+        for entry in entries {
+            let record = AuditRecord {
+                timestamp,
+                log_id: entry.log_id,
+                operation: operation(&entry.payload),
+            };
+            let mut line = serde_json::to_vec(&record).map_err(|e| AuditError::Write(e.into()))?;
+            line.push(b'\n');
+
+            if current.size > 0 && current.size + line.len() as u64 > self.max_size {
+                self.rotate(&mut current).await.map_err(|e| {
+                    tracing::error!(path = %self.path.display(), "failed to rotate audit log: {}", e);
+                    AuditError::Rotate(e)
+                })?;
+            }
+
+            current
+                .file
+                .write_all(&line)
+                .await
+                .map_err(AuditError::Write)?;
+            current.size += line.len() as u64;
+        }
+        current.file.sync_data().await.map_err(AuditError::Write)?;
+        Ok(())
+    }
+
+    /// Moves the full file to [`AuditLogger::rotated_path`] and starts a new one.
+    async fn rotate(&self, current: &mut AuditFile) -> io::Result<()> {
+        // Lines written by this batch must be on disk before the file is closed.
+        current.file.sync_data().await?;
+        tokio::fs::rename(&self.path, self.rotated_path()).await?;
+        current.file = open_append(&self.path).await?;
+        current.size = 0;
+        Ok(())
+    }
+}
+
+async fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+}
+
+fn operation(payload: &EntryPayload<TypeConfig>) -> String {
+    match payload {
+        EntryPayload::Blank => "Blank".to_string(),
+        EntryPayload::Normal(req) => req.to_string(),
+        EntryPayload::Membership(mem) => format!("Membership{{{}}}", mem),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openraft::CommittedLeaderId;
+    use openraft::Membership;
+
+    use super::*;
+    use crate::store::Request;
+
+    fn entry(index: u64, payload: EntryPayload<TypeConfig>) -> Entry<TypeConfig> {
+        Entry {
+            log_id: LogId::new(CommittedLeaderId::new(1, 0), index),
+            payload,
+        }
+    }
+
+    fn set(index: u64) -> Entry<TypeConfig> {
+        entry(
+            index,
+            EntryPayload::Normal(Request::Set {
+                key: format!("key-{}", index),
+                value: format!("value-{}", index),
+            }),
+        )
+    }
+
+    async fn read_records(path: &Path) -> Vec<AuditRecord> {
+        tokio::fs::read_to_string(path)
+            .await
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_all_operations_logged_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit = AuditLogger::open(dir.path().join(AUDIT_LOG_FILE))
+            .await
+            .unwrap();
+
+        let mut entries = vec![
+            entry(1, EntryPayload::Blank),
+            entry(
+                2,
+                EntryPayload::Membership(Membership::new(vec![maplit::btreeset! {1}], None)),
+            ),
+        ];
+        entries.extend((3..=100).map(set));
+        for ent in &entries {
+            audit.log(ent).await.unwrap();
+        }
+
+        let records = read_records(audit.path()).await;
+        assert_eq!(
+            records.iter().map(|r| r.log_id).collect::<Vec<_>>(),
+            entries.iter().map(|e| e.log_id).collect::<Vec<_>>()
+        );
+        assert_eq!(records[0].operation, "Blank");
+        assert!(records[1].operation.starts_with("Membership"));
+        assert_eq!(records[2].operation, "Set{key=key-3, value=value-3}");
+        assert!(records.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+        // Reopening appends instead of truncating.
+        drop(audit);
+        let audit = AuditLogger::open(dir.path().join(AUDIT_LOG_FILE))
+            .await
+            .unwrap();
+        audit.log(&set(101)).await.unwrap();
+        let records = read_records(audit.path()).await;
+        assert_eq!(records.len(), 101);
+        assert_eq!(records[100].log_id.index, 101);
+    }
+
+    #[tokio::test]
+    async fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let line_len = {
+            let probe = AuditLogger::open(dir.path().join("probe.log"))
+                .await
+                .unwrap();
+            probe.log(&set(1)).await.unwrap();
+            tokio::fs::metadata(probe.path()).await.unwrap().len()
+        };
+
+        // Room for 3 lines of `set(1..=9)`, which all have the same length.
+        let audit = AuditLogger::with_max_size(dir.path().join(AUDIT_LOG_FILE), line_len * 3)
+            .await
+            .unwrap();
+        for index in 1..=5 {
+            audit.log(&set(index)).await.unwrap();
+        }
+
+        let indexes =
+            |records: Vec<AuditRecord>| records.iter().map(|r| r.log_id.index).collect::<Vec<_>>();
+        assert_eq!(
+            indexes(read_records(&audit.rotated_path()).await),
+            vec![1, 2, 3]
+        );
+        assert_eq!(indexes(read_records(audit.path()).await), vec![4, 5]);
+        assert!(dir.path().join("audit.log.1").exists());
+    }
+
+    #[tokio::test]
+    async fn test_rotation_within_one_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(AUDIT_LOG_FILE);
+        let line_len = {
+            let probe = AuditLogger::open(dir.path().join("probe.log"))
+                .await
+                .unwrap();
+            probe.log(&set(1)).await.unwrap();
+            tokio::fs::metadata(probe.path()).await.unwrap().len()
+        };
+
+        let audit = AuditLogger::with_max_size(&path, line_len * 3)
+            .await
+            .unwrap();
+        let entries: Vec<_> = (1..=5).map(set).collect();
+        audit.log_all(&entries).await.unwrap();
+
+        let indexes =
+            |records: Vec<AuditRecord>| records.iter().map(|r| r.log_id.index).collect::<Vec<_>>();
+        assert_eq!(
+            indexes(read_records(&audit.rotated_path()).await),
+            vec![1, 2, 3]
+        );
+        assert_eq!(indexes(read_records(audit.path()).await), vec![4, 5]);
+
+        // A directory in the way of the rotated file makes the rename fail.
+        tokio::fs::remove_file(audit.rotated_path()).await.unwrap();
+        tokio::fs::create_dir(audit.rotated_path()).await.unwrap();
+        tokio::fs::write(audit.rotated_path().join("x"), b"x")
+            .await
+            .unwrap();
+        audit.log(&set(6)).await.unwrap();
+        let err = audit.log(&set(7)).await.unwrap_err();
+        assert!(matches!(err, AuditError::Rotate(_)), "{}", err);
+    }
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+
+use tokio::sync::Notify;
+
+/// Writes that may be in `client_write` at the same time on one node.
+pub const MAX_PENDING_WRITES: usize = 1024;
+
+/// Bounds the number of writes waiting in raft, so that a write storm blocks the clients
+/// instead of growing raft's internal queue until the node runs out of memory.
+///
+/// [`RaftBackpressureGuard::acquire`] blocks the calling thread while `max_pending` writes are
+/// in flight, [`RaftBackpressureGuard::acquire_async`] waits without holding a thread, as the
+/// `/api/write` handler does. Dropping the returned [`WritePermit`] releases the slot and wakes
+/// one waiter of each kind. Releasing on drop means a write whose request is cancelled half way
+/// does not leak a slot.
+#[derive(Debug)]
+pub struct RaftBackpressureGuard {
+    max_pending: usize,
+    pending: Mutex<usize>,
+    /// Wakes threads blocked in `acquire`.
+    admitted: Condvar,
+    /// Wakes tasks waiting in `acquire_async`.
+    admitted_async: Notify,
+}
+
+/// A slot for one write, see [`RaftBackpressureGuard`].
+#[derive(Debug)]
+pub struct WritePermit {
+    guard: Arc<RaftBackpressureGuard>,
+}
+
+impl RaftBackpressureGuard {
+    pub fn new(max_pending: usize) -> Self {
+        assert!(max_pending > 0, "max_pending must be at least 1");
+        Self {
+            max_pending,
+            pending: Mutex::new(0),
+            admitted: Condvar::new(),
+            admitted_async: Notify::new(),
+        }
+    }
+
+    /// Blocks until fewer than `max_pending` writes are in flight, then takes a slot.
+    pub fn acquire(self: &Arc<Self>) -> WritePermit {
+        let pending = self.pending.lock().unwrap();
+        let mut pending = self
+            .admitted
+            .wait_while(pending, |pending| *pending >= self.max_pending)
+            .unwrap();
+        *pending += 1;
+        WritePermit {
+            guard: self.clone(),
+        }
+    }
+
+    /// Waits until fewer than `max_pending` writes are in flight, then takes a slot.
+    ///
+    /// Same as [`RaftBackpressureGuard::acquire`], but a waiting write costs a task, not a thread.
+    pub async fn acquire_async(self: &Arc<Self>) -> WritePermit {
+        loop {
+            // Registered before the check, so a release in between is not missed.
+            let admitted = self.admitted_async.notified();
+            tokio::pin!(admitted);
+            admitted.as_mut().enable();
+
+            {
+                let mut pending = self.pending.lock().unwrap();
+                if *pending < self.max_pending {
+                    *pending += 1;
+                    return WritePermit {
+                        guard: self.clone(),
+                    };
+                }
+            }
+
+            admitted.await;
+        }
+    }
+
+    /// Number of writes holding a slot.
+    pub fn pending(&self) -> usize {
+        *self.pending.lock().unwrap()
+    }
+
+    fn release(&self) {
+        *self.pending.lock().unwrap() -= 1;
+        self.admitted.notify_one();
+        self.admitted_async.notify_one();
+    }
+}
+
+impl Drop for WritePermit {
+    fn drop(&mut self) {
+        self.guard.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn test_blocks_while_saturated() {
+        let guard = Arc::new(RaftBackpressureGuard::new(2));
+        let first = guard.acquire();
+        let second = guard.acquire();
+        assert_eq!(guard.pending(), 2);
+
+        let waiter = thread::spawn({
+            let guard = guard.clone();
+            move || {
+                let started = Instant::now();
+                let permit = guard.acquire();
+                (started.elapsed(), permit)
+            }
+        });
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(!waiter.is_finished());
+        assert_eq!(guard.pending(), 2);
+
+        drop(first);
+        let (waited, third) = waiter.join().unwrap();
+        assert!(waited >= Duration::from_millis(50), "{:?}", waited);
+        assert_eq!(guard.pending(), 2);
+
+        drop((second, third));
+        assert_eq!(guard.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_async_waits_while_saturated() {
+        let guard = Arc::new(RaftBackpressureGuard::new(2));
+        let first = guard.acquire_async().await;
+        let second = guard.acquire_async().await;
+
+        // Many more waiters than threads in the runtime.
+        let waiters: Vec<_> = (0..64)
+            .map(|_| {
+                let guard = guard.clone();
+                tokio::spawn(async move {
+                    let started = Instant::now();
+                    let permit = guard.acquire_async().await;
+                    let waited = started.elapsed();
+                    drop(permit);
+                    waited
+                })
+            })
+            .collect();
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(waiters.iter().all(|w| !w.is_finished()));
+        assert_eq!(guard.pending(), 2);
+
+        drop((first, second));
+        for w in waiters {
+            let waited = w.await.unwrap();
+            assert!(waited >= Duration::from_millis(50), "{:?}", waited);
+        }
+        assert_eq!(guard.pending(), 0);
+    }
+
+    #[test]
+    fn test_never_exceeds_max_pending() {
+        let guard = Arc::new(RaftBackpressureGuard::new(3));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let writers: Vec<_> = (0..16)
+            .map(|_| {
+                let (guard, in_flight, peak) = (guard.clone(), in_flight.clone(), peak.clone());
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        let _permit = guard.acquire();
+                        let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(1));
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                    }
+                })
+            })
+            .collect();
+        for w in writers {
+            w.join().unwrap();
+        }
+
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        assert_eq!(guard.pending(), 0);
+    }
+}
@@ -1,6 +1,24 @@
+use std::time::Duration;
+
 use clap::Parser;
-use raft_kv_rocksdb::start_example_raft_node;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use raft_kv_rocksdb::log_level;
+use raft_kv_rocksdb::network::codec::SerializationFormat;
+use raft_kv_rocksdb::network::Network;
+use raft_kv_rocksdb::start_raft_node_with_network;
+use raft_kv_rocksdb::store::DEFAULT_SNAPSHOT_COMPRESSION_LEVEL;
+use raft_kv_rocksdb::AppConfig;
+use raft_kv_rocksdb::DEFAULT_COMPACTION_THRESHOLD;
+use tracing::Level;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::reload;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
+use tracing_subscriber::Layer;
 
 #[derive(Parser, Clone, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -13,27 +31,110 @@ pub struct Opt {
 
     #[clap(long)]
     pub rpc_addr: String,
+
+    /// OTLP gRPC endpoint to export spans to, e.g. `http://127.0.0.1:4317` for the Jaeger
+    /// started by `docker-compose.yml`.
+    #[clap(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+
+    /// While leader, remove voters that have not answered for this many milliseconds from the
+    /// membership. Dead nodes are never removed automatically when unset.
+    #[clap(long)]
+    pub node_timeout_ms: Option<u64>,
+
+    /// Encoding of the raft RPCs this node sends. Nodes reply in the encoding they were sent,
+    /// so upgraded nodes may mix both, but only `json` reaches nodes without this option.
+    #[clap(long, value_enum, default_value_t)]
+    pub rpc_format: SerializationFormat,
+
+    /// Build a snapshot and purge the logs it covers every this many applied entries.
+    #[clap(long, default_value_t = DEFAULT_COMPACTION_THRESHOLD)]
+    pub compaction_threshold: u64,
+
+    /// Gzip level (0-9) of snapshot data. Lower is faster, higher gives smaller snapshots.
+    #[clap(long, default_value_t = DEFAULT_SNAPSHOT_COMPRESSION_LEVEL)]
+    pub snapshot_compression_level: u32,
 }
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    // Setup the logger
-    tracing_subscriber::fmt()
+    // Parse the parameters passed by arguments.
+    let options = Opt::parse();
+
+    let tracer_provider = options
+        .otlp_endpoint
+        .as_ref()
+        .map(|endpoint| init_tracer_provider(options.id, endpoint));
+
+    // Spans of this crate, e.g. `leader_change`, are exported regardless of `RUST_LOG`, which
+    // only controls what is printed.
+    let otel_layer = tracer_provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer("raft-kv-rocksdb"))
+            .with_filter(Targets::new().with_target("raft_kv_rocksdb", Level::INFO))
+    });
+
+    // Setup the logger. The filter starts from `RUST_LOG` and can be changed at runtime with
+    // `POST /log-level`.
+    // `from_default_env` falls back to `error` when `RUST_LOG` is unset or empty.
+    let rust_log = std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "error".to_string());
+    let (filter, filter_handle) = reload::Layer::new(EnvFilter::from_default_env());
+    log_level::install(filter_handle, rust_log);
+
+    let fmt_layer = tracing_subscriber::fmt::layer()
         .with_target(true)
         .with_thread_ids(true)
         .with_level(true)
         .with_ansi(false)
-        .with_env_filter(EnvFilter::from_default_env())
-        .init();
+        .with_filter(filter);
 
-    // Parse the parameters passed by arguments.
-    let options = Opt::parse();
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(otel_layer)
+        .init();
 
-    start_example_raft_node(
+    let network = Network {
+        format: options.rpc_format,
+        ..Default::default()
+    };
+    let res = start_raft_node_with_network(
         options.id,
         format!("{}.db", options.rpc_addr),
         options.http_addr,
         options.rpc_addr,
+        network,
+        options.node_timeout_ms.map(Duration::from_millis),
+        AppConfig {
+            compaction_threshold: options.compaction_threshold,
+            snapshot_compression_level: options.snapshot_compression_level,
+            ..Default::default()
+        },
     )
-    .await
+    .await;
+
+    // Flush the spans still sitting in the batch exporter.
+    if let Some(provider) = tracer_provider {
+        let _ = provider.shutdown();
+    }
+    res
+}
+
+fn init_tracer_provider(node_id: u64, endpoint: &str) -> SdkTracerProvider {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .expect("failed to build the OTLP span exporter");
+
+    SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(format!("raft-kv-node-{}", node_id))
+                .build(),
+        )
+        .build()
 }
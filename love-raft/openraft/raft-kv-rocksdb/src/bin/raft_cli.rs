@@ -0,0 +1,31 @@
+use clap::Parser;
+use raft_kv_rocksdb::client::ExampleClient;
+use raft_kv_rocksdb::store::Request;
+
+/// Send a single write to a running cluster, e.g.
+/// `raft-cli --node-addr 127.0.0.1:21001 --command "set foo bar"`.
+#[derive(Parser, Clone, Debug)]
+#[clap(author, version, about, long_about = None)]
+pub struct Opt {
+    /// HTTP address of any node; the client follows `ForwardToLeader` to reach the leader.
+    #[clap(long)]
+    pub node_addr: String,
+
+    /// The command to write, in the form `set <key> <value>`.
+    #[clap(long)]
+    pub command: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let options = Opt::parse();
+
+    let req: Request = options.command.parse()?;
+
+    // The node id is only used to report errors; it is replaced once a leader redirects us.
+    let client = ExampleClient::new(0, options.node_addr);
+    let resp = client.write(&req).await?;
+
+    println!("{}", serde_json::to_string_pretty(&resp)?);
+    Ok(())
+}
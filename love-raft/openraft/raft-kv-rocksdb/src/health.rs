@@ -0,0 +1,125 @@
+use openraft::RaftMetrics;
+use openraft::ServerState;
+use serde::Serialize;
+
+use crate::ExampleRaft;
+use crate::Node;
+use crate::NodeId;
+
+/// A node whose state machine is more than this many entries behind its log is degraded.
+pub const MAX_APPLY_LAG: u64 = 100;
+
+/// Role of a node, as reported by [`health_check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum RaftState {
+    Leader,
+    Follower,
+    Candidate,
+    Learner,
+    /// Raft has stopped; such a node is never healthy.
+    Shutdown,
+}
+
+impl From<ServerState> for RaftState {
+    fn from(state: ServerState) -> Self {
+        match state {
+            ServerState::Leader => RaftState::Leader,
+            ServerState::Follower => RaftState::Follower,
+            ServerState::Candidate => RaftState::Candidate,
+            ServerState::Learner => RaftState::Learner,
+            ServerState::Shutdown => RaftState::Shutdown,
+        }
+    }
+}
+
+/// What `/health` and `/health/ready` report about this node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HealthStatus {
+    pub leader_id: Option<NodeId>,
+    pub is_leader: bool,
+    /// Index of the last log entry.
+    pub last_log_id: Option<u64>,
+    /// Index of the last entry applied to the state machine.
+    pub last_applied_id: Option<u64>,
+    /// Number of voters in the effective membership.
+    pub membership_size: usize,
+    pub state: RaftState,
+}
+
+/// Reads the current metrics of `raft`.
+///
+/// This only borrows the latest value of the metrics channel, it never waits for raft. It is
+/// still `async` so that a check which has to ask raft, e.g. whether this leader can still
+/// reach a quorum, can be added without changing the `/health` handlers that call it.
+pub async fn health_check(raft: &ExampleRaft) -> HealthStatus {
+    HealthStatus::from_metrics(&raft.metrics().borrow())
+}
+
+impl HealthStatus {
+    pub fn from_metrics(metrics: &RaftMetrics<NodeId, Node>) -> Self {
+        Self {
+            leader_id: metrics.current_leader,
+            is_leader: metrics.current_leader == Some(metrics.id),
+            last_log_id: metrics.last_log_index,
+            last_applied_id: metrics.last_applied.map(|log_id| log_id.index),
+            membership_size: metrics.membership_config.membership().voter_ids().count(),
+            state: metrics.state.into(),
+        }
+    }
+
+    /// How many log entries are not yet applied to the state machine.
+    pub fn apply_lag(&self) -> u64 {
+        let last_log = self.last_log_id.unwrap_or_default();
+        let last_applied = self.last_applied_id.unwrap_or_default();
+        last_log.saturating_sub(last_applied)
+    }
+
+    /// Healthy unless raft has shut down or the state machine lags more than
+    /// [`MAX_APPLY_LAG`] entries behind the log.
+    pub fn is_healthy(&self) -> bool {
+        self.state != RaftState::Shutdown && self.apply_lag() <= MAX_APPLY_LAG
+    }
+
+    /// Only a healthy leader is ready to serve writes.
+    pub fn is_ready(&self) -> bool {
+        self.is_leader && self.is_healthy()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A status reported by node 1 of a 3 node cluster.
+    pub(crate) fn status(
+        state: RaftState,
+        last_log_id: Option<u64>,
+        last_applied_id: Option<u64>,
+    ) -> HealthStatus {
+        HealthStatus {
+            leader_id: Some(1),
+            is_leader: state == RaftState::Leader,
+            last_log_id,
+            last_applied_id,
+            membership_size: 3,
+            state,
+        }
+    }
+
+    #[test]
+    fn test_healthy_within_lag() {
+        assert!(status(RaftState::Follower, None, None).is_healthy());
+        assert!(status(RaftState::Follower, Some(150), Some(50)).is_healthy());
+        assert!(!status(RaftState::Follower, Some(151), Some(50)).is_healthy());
+        assert!(!status(RaftState::Follower, Some(101), None).is_healthy());
+        assert!(!status(RaftState::Shutdown, Some(5), Some(5)).is_healthy());
+    }
+
+    #[test]
+    fn test_ready_only_on_healthy_leader() {
+        assert!(status(RaftState::Leader, Some(5), Some(5)).is_ready());
+        assert!(!status(RaftState::Follower, Some(5), Some(5)).is_ready());
+        assert!(!status(RaftState::Learner, Some(5), Some(5)).is_ready());
+        assert!(!status(RaftState::Leader, Some(500), Some(5)).is_ready());
+    }
+}
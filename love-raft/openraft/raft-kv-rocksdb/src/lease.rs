@@ -0,0 +1,157 @@
+use std::time::Duration;
+use std::time::Instant;
+
+use openraft::Config;
+use openraft::RaftMetrics;
+use openraft::ServerState;
+
+use crate::typ;
+use crate::utils::watcher::Watcher;
+use crate::ExampleRaft;
+use crate::Node;
+use crate::NodeId;
+
+/// Serves linearizable reads from the local state machine without a quorum round-trip, as long
+/// as this node has been confirmed as leader recently enough.
+///
+/// Followers do not start an election before `election_timeout_min` has passed since they last
+/// heard from the leader, so no other leader can exist within that window after a quorum acked
+/// us. The lease is kept shorter than that to leave room for clock drift and for the delay
+/// between raft publishing its metrics and [`LeaderLease::update`] observing them.
+///
+/// A leader answers `client_write` only after the entry is applied, so every acknowledged
+/// write is visible in its state machine. The exception is a fresh leader that has not applied
+/// an entry of its own term yet: entries of the previous term may be committed but not applied
+/// here, so the lease is not granted until the first entry of the current term is applied.
+#[derive(Debug)]
+pub struct LeaderLease {
+    lease_duration: Duration,
+    /// When a quorum last acknowledged this node as leader; `None` when it is not a leader
+    /// allowed to serve local reads.
+    quorum_acked_at: Watcher<Option<Instant>>,
+}
+
+impl LeaderLease {
+    pub fn new(lease_duration: Duration) -> Self {
+        Self {
+            lease_duration,
+            quorum_acked_at: Watcher::new(None),
+        }
+    }
+
+    /// A lease of half `config.election_timeout_min`.
+    ///
+    /// A follower starts an election only after `election_timeout_min` without hearing from the
+    /// leader, measured on its own clock from the heartbeat it acknowledged. Half of that stays
+    /// safe while this node's clock runs up to twice as fast as a follower's, and still leaves
+    /// time for the delay between the quorum's ack and [`LeaderLease::update`] seeing it in the
+    /// metrics. A longer lease would save a few quorum round-trips but make a stale read
+    /// possible after a leader change.
+    ///
+    /// With heartbeats less frequent than the lease is long, the lease lapses between them and
+    /// reads fall back to [`openraft::Raft::ensure_linearizable`], whose quorum round-trip
+    /// renews it.
+    pub fn for_config(config: &Config) -> Self {
+        Self::new(Duration::from_millis(config.election_timeout_min / 2))
+    }
+
+    /// Refresh the lease from raft metrics. Called by [`crate::app::App::sync_watchers`] on
+    /// every metrics change.
+    pub fn update(&self, metrics: &RaftMetrics<NodeId, Node>) {
+        let acked_at = if metrics.state == ServerState::Leader && applied_current_term(metrics) {
+            // `millis_since_quorum_ack` is relative to when the metrics were published; turn it
+            // into an instant so that the lease still expires if the metrics stop changing.
+            metrics
+                .millis_since_quorum_ack
+                .and_then(|ms| Instant::now().checked_sub(Duration::from_millis(ms)))
+        } else {
+            None
+        };
+        self.quorum_acked_at.send_if_changed(acked_at);
+    }
+
+    /// Whether a read from the local state machine is linearizable right now.
+    pub fn is_valid(&self) -> bool {
+        self.quorum_acked_at
+            .get()
+            .is_some_and(|t| t.elapsed() < self.lease_duration)
+    }
+
+    /// Return immediately while the lease is valid, otherwise fall back to
+    /// [`openraft::Raft::ensure_linearizable`], which confirms leadership with a quorum and
+    /// waits for the state machine to catch up with the read log id.
+    pub async fn ensure_readable(
+        &self,
+        raft: &ExampleRaft,
+    ) -> Result<(), typ::RaftError<typ::CheckIsLeaderError>> {
+        if self.is_valid() {
+            return Ok(());
+        }
+        raft.ensure_linearizable().await.map(|_| ())
+    }
+}
+
+/// The last applied log was proposed by the leader of the current term, i.e. this node.
+fn applied_current_term(metrics: &RaftMetrics<NodeId, Node>) -> bool {
+    metrics
+        .last_applied
+        .is_some_and(|log_id| log_id.leader_id.term == metrics.current_term)
+}
+
+#[cfg(test)]
+mod tests {
+    use openraft::CommittedLeaderId;
+    use openraft::LogId;
+
+    use super::*;
+
+    fn leader_metrics(millis_since_quorum_ack: u64) -> RaftMetrics<NodeId, Node> {
+        let mut m = RaftMetrics::new_initial(1);
+        m.state = ServerState::Leader;
+        m.current_term = 2;
+        m.current_leader = Some(1);
+        m.last_applied = Some(LogId::new(CommittedLeaderId::new(2, 1), 10));
+        m.millis_since_quorum_ack = Some(millis_since_quorum_ack);
+        m
+    }
+
+    #[test]
+    fn test_lease_granted_and_expires() {
+        let lease = LeaderLease::new(Duration::from_millis(100));
+        assert!(!lease.is_valid());
+
+        lease.update(&leader_metrics(0));
+        assert!(lease.is_valid());
+
+        // No new metrics: the lease runs out on its own.
+        std::thread::sleep(Duration::from_millis(120));
+        assert!(!lease.is_valid());
+
+        // The last quorum ack is already older than the lease.
+        lease.update(&leader_metrics(150));
+        assert!(!lease.is_valid());
+    }
+
+    #[test]
+    fn test_no_lease_unless_leader_of_applied_term() {
+        let lease = LeaderLease::new(Duration::from_secs(10));
+
+        let mut follower = leader_metrics(0);
+        follower.state = ServerState::Follower;
+        lease.update(&follower);
+        assert!(!lease.is_valid());
+
+        // Just elected in term 3, the blank entry of term 3 is not applied yet.
+        let mut fresh = leader_metrics(0);
+        fresh.current_term = 3;
+        lease.update(&fresh);
+        assert!(!lease.is_valid());
+
+        lease.update(&leader_metrics(0));
+        assert!(lease.is_valid());
+
+        // Stepping down revokes the lease at once.
+        lease.update(&follower);
+        assert!(!lease.is_valid());
+    }
+}
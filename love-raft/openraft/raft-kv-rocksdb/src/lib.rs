@@ -6,23 +6,42 @@ use std::fmt::Display;
 use std::io::Cursor;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
+use openraft::network::RaftNetworkFactory;
 use openraft::Config;
+use openraft::SnapshotPolicy;
 use tokio::net::TcpListener;
 use tokio::task;
+use tokio::time::MissedTickBehavior;
 
 use crate::app::App;
+use crate::backpressure::RaftBackpressureGuard;
+use crate::backpressure::MAX_PENDING_WRITES;
+use crate::lease::LeaderLease;
 use crate::network::api;
 use crate::network::management;
 use crate::network::Network;
+use crate::network::TracksLiveness;
+use crate::node_manager::RaftNodeManager;
 use crate::store::new_storage;
 use crate::store::Request;
 use crate::store::Response;
+use crate::store::DEFAULT_SNAPSHOT_COMPRESSION_LEVEL;
+use crate::utils::watcher::Watcher;
 
 pub mod app;
+pub mod audit;
+pub mod backpressure;
 pub mod client;
+pub mod health;
+pub mod lease;
+pub mod log_level;
+pub mod metrics;
 pub mod network;
+pub mod node_manager;
 pub mod store;
+pub mod utils;
 
 pub type NodeId = u64;
 
@@ -75,31 +94,103 @@ pub type ExampleRaft = openraft::Raft<TypeConfig>;
 
 type Server = tide::Server<Arc<App>>;
 
+/// Default number of applied entries between two snapshots, see [`AppConfig`].
+pub const DEFAULT_COMPACTION_THRESHOLD: u64 = 1000;
+
+/// Settings of a node that are not part of openraft's [`Config`].
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    /// Every this many applied entries the node builds a snapshot and purges the logs it covers
+    /// except the last `compaction_threshold`, so the log never grows much beyond twice that.
+    /// Followers lagging further behind catch up by installing the snapshot.
+    pub compaction_threshold: u64,
+
+    /// Gzip level (0-9, higher values are clamped) of the snapshot data.
+    pub snapshot_compression_level: u32,
+
+    /// Serve consistent reads from the leader lease, see [`LeaderLease`]. When off, every
+    /// consistent read confirms the leadership with a quorum first.
+    pub read_lease: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+            snapshot_compression_level: DEFAULT_SNAPSHOT_COMPRESSION_LEVEL,
+            read_lease: true,
+        }
+    }
+}
+
+/// With `node_timeout`, the leader removes voters that have not answered it for that long, see
+/// [`RaftNodeManager`].
 pub async fn start_example_raft_node<P>(
     node_id: NodeId,
     dir: P,
     http_addr: String,
     rpc_addr: String,
+    node_timeout: Option<Duration>,
 ) -> std::io::Result<()>
 where
     P: AsRef<Path>,
+{
+    // Create the network layer that will connect and communicate the raft instances.
+    let network = Network::default();
+
+    start_raft_node_with_network(
+        node_id,
+        dir,
+        http_addr,
+        rpc_addr,
+        network,
+        node_timeout,
+        AppConfig::default(),
+    )
+    .await
+}
+
+/// Same as [`start_example_raft_node`], but with a caller supplied network layer, e.g.
+/// [`network::fault_injector::FaultInjectingNetwork`] in tests.
+pub async fn start_raft_node_with_network<P, N>(
+    node_id: NodeId,
+    dir: P,
+    http_addr: String,
+    rpc_addr: String,
+    network: N,
+    node_timeout: Option<Duration>,
+    app_config: AppConfig,
+) -> std::io::Result<()>
+where
+    P: AsRef<Path>,
+    N: RaftNetworkFactory<TypeConfig> + TracksLiveness,
 {
     // Create a configuration for the raft instance.
     let config = Config {
         heartbeat_interval: 250,
         election_timeout_min: 299,
+        snapshot_policy: SnapshotPolicy::LogsSinceLast(app_config.compaction_threshold),
+        max_in_snapshot_log_to_keep: app_config.compaction_threshold,
         ..Default::default()
     };
 
     let config = Arc::new(config.validate().unwrap());
 
-    let (log_store, state_machine_store) = new_storage(&dir).await;
+    let (log_store, state_machine_store) = new_storage(&dir, &app_config)
+        .await
+        .map_err(std::io::Error::other)?;
 
     let kvs = state_machine_store.data.kvs.clone();
+    let metrics = state_machine_store.metrics.clone();
 
-    // Create the network layer that will connect and communicate the raft instances and
-    // will be used in conjunction with the store created above.
-    let network = Network {};
+    let lease = if app_config.read_lease {
+        LeaderLease::for_config(&config)
+    } else {
+        // A zero lease is never valid.
+        LeaderLease::new(Duration::ZERO)
+    };
+
+    let liveness = network.liveness();
 
     // Create a local raft instance.
     let raft = openraft::Raft::new(
@@ -119,8 +210,32 @@ where
         raft,
         key_values: kvs,
         config,
+        leader: Watcher::new(None),
+        applied: Watcher::new(None),
+        membership: Watcher::new(Default::default()),
+        lease,
+        metrics,
+        write_guard: Arc::new(RaftBackpressureGuard::new(MAX_PENDING_WRITES)),
+    });
+
+    task::spawn({
+        let app = app.clone();
+        async move { app.sync_watchers().await }
     });
 
+    task::spawn({
+        let app = app.clone();
+        async move {
+            app.metrics
+                .run(&app.raft, metrics::POLL_INTERVAL, MissedTickBehavior::Skip)
+                .await
+        }
+    });
+
+    if let Some(node_timeout) = node_timeout {
+        task::spawn(RaftNodeManager::new(app.raft.clone(), liveness, node_timeout).run());
+    }
+
     let echo_service = Arc::new(network::raft::Raft::new(app.clone()));
 
     let server = toy_rpc::Server::builder().register(echo_service).build();
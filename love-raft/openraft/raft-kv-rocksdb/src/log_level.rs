@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::time::Instant;
+
+use serde::Deserialize;
+use serde::Serialize;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::reload;
+use tracing_subscriber::EnvFilter;
+
+/// A client may change the log level at most once per this interval.
+pub const CHANGE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Body of `POST /log-level`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetLogLevelRequest {
+    /// Target to change, e.g. `openraft::replication`. Empty changes the default level.
+    pub module: String,
+    /// `off`, `error`, `warn`, `info`, `debug` or `trace`.
+    pub level: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SetLogLevelResponse {
+    /// The whole filter before the change, in `RUST_LOG` syntax.
+    pub previous_level: String,
+}
+
+#[derive(Debug)]
+pub enum SetLogLevelError {
+    InvalidLevel(String),
+    InvalidFilter(String),
+    /// The client changed the level less than [`CHANGE_INTERVAL`] ago; retry after this long.
+    RateLimited(Duration),
+    /// The subscriber owning the filter is gone.
+    Reload(reload::Error),
+}
+
+impl fmt::Display for SetLogLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetLogLevelError::InvalidLevel(level) => write!(f, "invalid log level: {}", level),
+            SetLogLevelError::InvalidFilter(e) => write!(f, "invalid filter: {}", e),
+            SetLogLevelError::RateLimited(retry) => {
+                write!(f, "log level changed too often, retry in {:?}", retry)
+            }
+            SetLogLevelError::Reload(e) => write!(f, "failed to reload filter: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SetLogLevelError {}
+
+/// Changes the `RUST_LOG` filter of a running node.
+///
+/// The filter belongs to the process-wide subscriber, so there is one control per process,
+/// installed by the binary with [`install`] and served at `POST /log-level`. Nodes started
+/// without it, e.g. in the cluster tests, answer that endpoint with `404`.
+pub struct LogLevelControl {
+    reload: Box<dyn Fn(EnvFilter) -> Result<(), reload::Error> + Send + Sync>,
+    filter: Mutex<String>,
+    /// Clients that changed the level within the last [`CHANGE_INTERVAL`]; older entries are
+    /// swept on every change, so this holds only recent clients.
+    last_change: Mutex<HashMap<IpAddr, Instant>>,
+}
+
+static CONTROL: OnceLock<LogLevelControl> = OnceLock::new();
+
+/// Registers the reload handle of the subscriber's filter; `filter` is what it was built from.
+///
+/// Only the first call has an effect.
+pub fn install<S: 'static>(handle: reload::Handle<EnvFilter, S>, filter: impl ToString) {
+    let _ = CONTROL.set(LogLevelControl::new(handle, filter));
+}
+
+/// The control installed by [`install`], if any.
+pub fn control() -> Option<&'static LogLevelControl> {
+    CONTROL.get()
+}
+
+impl LogLevelControl {
+    pub fn new<S: 'static>(handle: reload::Handle<EnvFilter, S>, filter: impl ToString) -> Self {
+        Self {
+            reload: Box::new(move |filter| handle.reload(filter)),
+            filter: Mutex::new(filter.to_string()),
+            last_change: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The current filter, in `RUST_LOG` syntax.
+    pub fn current(&self) -> String {
+        self.filter.lock().unwrap().clone()
+    }
+
+    /// Sets `req.module` to `req.level`, replacing the directive for that module if there is
+    /// one and leaving the others alone.
+    pub fn set_log_level(
+        &self,
+        client: IpAddr,
+        req: &SetLogLevelRequest,
+    ) -> Result<SetLogLevelResponse, SetLogLevelError> {
+        self.set_log_level_at(client, req, Instant::now())
+    }
+
+    fn set_log_level_at(
+        &self,
+        client: IpAddr,
+        req: &SetLogLevelRequest,
+        now: Instant,
+    ) -> Result<SetLogLevelResponse, SetLogLevelError> {
+        let level = LevelFilter::from_str(&req.level)
+            .map_err(|_| SetLogLevelError::InvalidLevel(req.level.clone()))?;
+
+        // A rejected request does not count against the client.
+        let mut filter = self.filter.lock().unwrap();
+        let new_filter = with_directive(&filter, req.module.trim(), level);
+        let env_filter = EnvFilter::try_new(&new_filter)
+            .map_err(|e| SetLogLevelError::InvalidFilter(e.to_string()))?;
+
+        {
+            let mut last_change = self.last_change.lock().unwrap();
+            last_change.retain(|_, last| now.saturating_duration_since(*last) < CHANGE_INTERVAL);
+            if let Some(last) = last_change.get(&client) {
+                let elapsed = now.saturating_duration_since(*last);
+                if elapsed < CHANGE_INTERVAL {
+                    return Err(SetLogLevelError::RateLimited(CHANGE_INTERVAL - elapsed));
+                }
+            }
+            last_change.insert(client, now);
+        }
+
+        (self.reload)(env_filter).map_err(SetLogLevelError::Reload)?;
+
+        tracing::warn!(module = %req.module, %level, "log level changed");
+        let previous_level = std::mem::replace(&mut *filter, new_filter);
+        Ok(SetLogLevelResponse { previous_level })
+    }
+}
+
+/// `filter` with the directive for `module` (the default level if empty) set to `level`.
+fn with_directive(filter: &str, module: &str, level: LevelFilter) -> String {
+    let level = level.to_string().to_lowercase();
+
+    let mut directives: Vec<String> = filter
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty() && directive_target(d) != module)
+        .map(str::to_string)
+        .collect();
+
+    if module.is_empty() {
+        directives.push(level);
+    } else {
+        directives.push(format!("{}={}", module, level));
+    }
+    directives.join(",")
+}
+
+/// Target of a `RUST_LOG` directive, empty for a bare level such as `info`.
+fn directive_target(directive: &str) -> &str {
+    match directive.rsplit_once('=') {
+        Some((target, _)) => target.split('[').next().unwrap_or_default(),
+        None if LevelFilter::from_str(directive).is_ok() => "",
+        None => directive,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Layer;
+
+    use super::*;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    fn req(module: &str, level: &str) -> SetLogLevelRequest {
+        SetLogLevelRequest {
+            module: module.to_string(),
+            level: level.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_with_directive() {
+        let info = LevelFilter::INFO;
+        assert_eq!(with_directive("", "", info), "info");
+        assert_eq!(with_directive("warn", "", info), "info");
+        assert_eq!(
+            with_directive("warn", "openraft", info),
+            "warn,openraft=info"
+        );
+        assert_eq!(
+            with_directive(
+                "warn,openraft=debug,toy_rpc=error",
+                "openraft",
+                LevelFilter::OFF
+            ),
+            "warn,toy_rpc=error,openraft=off"
+        );
+        assert_eq!(
+            with_directive("openraft[append]=debug", "openraft", info),
+            "openraft=info"
+        );
+    }
+
+    /// Collects everything written by the fmt layer.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_reload_changes_output() {
+        // Same layering as the binary: a fmt layer with a reloadable per-layer filter.
+        let (filter, handle) = reload::Layer::new(EnvFilter::new("warn"));
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .with_filter(filter),
+        );
+        let control = LogLevelControl::new(handle, "warn");
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "raft_kv_rocksdb::store", "hidden");
+            assert_eq!(captured.take(), "");
+
+            let res = control
+                .set_log_level_at(CLIENT, &req("raft_kv_rocksdb", "info"), Instant::now())
+                .unwrap();
+            assert_eq!(res.previous_level, "warn");
+            assert_eq!(control.current(), "warn,raft_kv_rocksdb=info");
+            captured.take();
+
+            tracing::info!(target: "raft_kv_rocksdb::store", "shown");
+            tracing::debug!(target: "raft_kv_rocksdb::store", "still hidden");
+            tracing::info!(target: "openraft::core", "other module");
+            let out = captured.take();
+            assert!(out.contains("shown"), "{}", out);
+            assert!(!out.contains("still hidden"), "{}", out);
+            assert!(!out.contains("other module"), "{}", out);
+        });
+    }
+
+    #[test]
+    fn test_rate_limit_per_client() {
+        let (_filter, handle) =
+            reload::Layer::<_, tracing_subscriber::Registry>::new(EnvFilter::new("warn"));
+        let control = LogLevelControl::new(handle, "warn");
+        let other = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let t0 = Instant::now();
+
+        control
+            .set_log_level_at(CLIENT, &req("", "info"), t0)
+            .unwrap();
+
+        let err = control
+            .set_log_level_at(CLIENT, &req("", "debug"), t0 + Duration::from_secs(4))
+            .unwrap_err();
+        assert!(
+            matches!(err, SetLogLevelError::RateLimited(d) if d == Duration::from_secs(6)),
+            "{}",
+            err
+        );
+        assert_eq!(control.current(), "info");
+
+        // Another client is not affected, and invalid requests do not count.
+        assert!(matches!(
+            control.set_log_level_at(other, &req("", "loud"), t0),
+            Err(SetLogLevelError::InvalidLevel(_))
+        ));
+        control
+            .set_log_level_at(other, &req("openraft", "debug"), t0)
+            .unwrap();
+
+        let res = control
+            .set_log_level_at(CLIENT, &req("", "debug"), t0 + CHANGE_INTERVAL)
+            .unwrap();
+        assert_eq!(res.previous_level, "info,openraft=debug");
+        assert_eq!(control.current(), "openraft=debug,debug");
+    }
+
+    #[test]
+    fn test_rate_limit_forgets_old_clients() {
+        let (_filter, handle) =
+            reload::Layer::<_, tracing_subscriber::Registry>::new(EnvFilter::new("warn"));
+        let control = LogLevelControl::new(handle, "warn");
+        let t0 = Instant::now();
+
+        for i in 0..100 {
+            let client = IpAddr::V4(Ipv4Addr::new(10, 0, 0, i));
+            control
+                .set_log_level_at(client, &req("", "info"), t0)
+                .unwrap();
+        }
+        assert_eq!(control.last_change.lock().unwrap().len(), 100);
+
+        control
+            .set_log_level_at(CLIENT, &req("", "debug"), t0 + CHANGE_INTERVAL)
+            .unwrap();
+        assert_eq!(control.last_change.lock().unwrap().len(), 1);
+    }
+}
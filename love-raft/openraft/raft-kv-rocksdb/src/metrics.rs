@@ -0,0 +1,358 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tokio::time::MissedTickBehavior;
+
+use crate::ExampleRaft;
+
+/// Number of most recent apply durations the latency statistics are computed over.
+pub const APPLY_LATENCY_WINDOW: usize = 1000;
+
+/// How often [`MetricsCollector::run`] refreshes the write rate.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Upper bounds, in seconds, of the buckets of the per-operation apply latency histograms.
+pub const OPERATION_LATENCY_BUCKETS: [f64; 5] = [0.0001, 0.001, 0.01, 0.1, 1.0];
+
+/// What an applied write did to the key-value map, the `operation` label of its histogram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// A `Set` of a key that did not exist.
+    Create,
+    /// A `Set` of an existing key.
+    Update,
+    Delete,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 3] = [Operation::Create, Operation::Update, Operation::Delete];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Operation::Create => "create",
+            Operation::Update => "update",
+            Operation::Delete => "delete",
+        }
+    }
+}
+
+/// Apply latency of one [`Operation`], as a Prometheus histogram.
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations per bucket of [`OPERATION_LATENCY_BUCKETS`], not cumulative; slower
+    /// observations only count in `count`.
+    buckets: [AtomicU64; OPERATION_LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_nanos: AtomicU64,
+}
+
+/// The observations of a [`Histogram`] at one point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct HistogramSnapshot {
+    /// Observations per bucket of [`OPERATION_LATENCY_BUCKETS`], not cumulative.
+    pub buckets: [u64; OPERATION_LATENCY_BUCKETS.len()],
+    pub count: u64,
+    pub sum_seconds: f64,
+}
+
+impl Histogram {
+    fn observe(&self, d: Duration) {
+        let seconds = d.as_secs_f64();
+        if let Some(i) = OPERATION_LATENCY_BUCKETS
+            .iter()
+            .position(|le| seconds <= *le)
+        {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos
+            .fetch_add(d.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            buckets: std::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed)),
+            count: self.count.load(Ordering::Relaxed),
+            sum_seconds: self.sum_nanos.load(Ordering::Relaxed) as f64 / 1e9,
+        }
+    }
+}
+
+/// Rolling statistics of the state machine, served in Prometheus text format at `/metrics`.
+///
+/// The state machine reports the duration of every applied write with
+/// [`MetricsCollector::record_apply_duration`]; [`MetricsCollector::run`] turns the write
+/// counter into a rate on a fixed interval.
+#[derive(Debug)]
+pub struct MetricsCollector {
+    /// The last [`APPLY_LATENCY_WINDOW`] apply durations, oldest first.
+    apply_durations: Mutex<VecDeque<Duration>>,
+    total_writes: AtomicU64,
+    /// `f64::to_bits` of the rate computed by the last tick.
+    writes_per_second: AtomicU64,
+    /// One histogram per [`Operation`], in the order of [`Operation::ALL`].
+    operations: [Histogram; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct MetricsSnapshot {
+    pub avg_apply_latency_ms: f64,
+    pub p99_apply_latency_ms: f64,
+    pub total_writes_per_second: f64,
+    pub total_writes: u64,
+    /// Apply latency per [`Operation`], in the order of [`Operation::ALL`].
+    pub operations: [HistogramSnapshot; 3],
+}
+
+impl Default for MetricsCollector {
+    fn default() -> Self {
+        Self {
+            apply_durations: Mutex::new(VecDeque::with_capacity(APPLY_LATENCY_WINDOW)),
+            total_writes: AtomicU64::new(0),
+            writes_per_second: AtomicU64::new(0f64.to_bits()),
+            operations: Default::default(),
+        }
+    }
+}
+
+impl MetricsCollector {
+    pub fn record_apply_duration(&self, d: Duration) {
+        {
+            let mut durations = self.apply_durations.lock().unwrap();
+            if durations.len() == APPLY_LATENCY_WINDOW {
+                durations.pop_front();
+            }
+            durations.push_back(d);
+        }
+        self.total_writes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Adds `d` to the latency histogram of `op`.
+    pub fn record_operation(&self, op: Operation, d: Duration) {
+        self.operations[op as usize].observe(d);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let mut durations: Vec<Duration> = self
+            .apply_durations
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect();
+        durations.sort_unstable();
+
+        let (avg, p99) = if durations.is_empty() {
+            (Duration::ZERO, Duration::ZERO)
+        } else {
+            let n = durations.len();
+            let avg = durations.iter().sum::<Duration>() / n as u32;
+            // Nearest-rank: the ceil(0.99 * n)-th smallest.
+            (avg, durations[(n * 99).div_ceil(100) - 1])
+        };
+
+        MetricsSnapshot {
+            avg_apply_latency_ms: millis(avg),
+            p99_apply_latency_ms: millis(p99),
+            total_writes_per_second: f64::from_bits(self.writes_per_second.load(Ordering::Relaxed)),
+            total_writes: self.total_writes.load(Ordering::Relaxed),
+            operations: std::array::from_fn(|i| self.operations[i].snapshot()),
+        }
+    }
+
+    /// Recompute the write rate every `period` until raft shuts down.
+    ///
+    /// Unlike [`crate::app::App::sync_watchers`], which wakes up on every metrics change, this
+    /// samples on a fixed interval, so a burst of writes does not cause a burst of wake-ups.
+    /// `missed_tick_behavior` decides what happens when the runtime was too busy to tick on
+    /// time:
+    /// - [`MissedTickBehavior::Burst`] fires all the missed ticks back to back. The extra
+    ///   samples are only microseconds apart and see (almost) no new writes, so the reported
+    ///   rate drops to about zero right after a stall.
+    /// - [`MissedTickBehavior::Skip`] fires once and then waits for the next multiple of
+    ///   `period`. The rate is computed over the whole stall, which is the right value.
+    ///
+    /// The rate is divided by the time actually elapsed between ticks rather than by `period`,
+    /// so it is correct with either behavior; `Skip` just avoids the misleading dips.
+    pub async fn run(
+        &self,
+        raft: &ExampleRaft,
+        period: Duration,
+        missed_tick_behavior: MissedTickBehavior,
+    ) {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(missed_tick_behavior);
+
+        let metrics = raft.metrics();
+        let mut last = (Instant::now(), self.total_writes.load(Ordering::Relaxed));
+        loop {
+            let now = interval.tick().await;
+            // The sender is dropped when raft shuts down.
+            if metrics.has_changed().is_err() {
+                return;
+            }
+
+            let writes = self.total_writes.load(Ordering::Relaxed);
+            let rate = writes_per_second(writes - last.1, now - last.0);
+            self.writes_per_second
+                .store(rate.to_bits(), Ordering::Relaxed);
+            last = (now, writes);
+        }
+    }
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_nanos() as f64 / 1_000_000.0
+}
+
+fn writes_per_second(writes: u64, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        return 0.0;
+    }
+    writes as f64 / elapsed.as_secs_f64()
+}
+
+impl MetricsSnapshot {
+    /// Render in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let gauges = [
+            (
+                "raft_kv_apply_latency_avg_ms",
+                "Average apply latency of the last 1000 writes",
+                self.avg_apply_latency_ms,
+            ),
+            (
+                "raft_kv_apply_latency_p99_ms",
+                "99th percentile apply latency of the last 1000 writes",
+                self.p99_apply_latency_ms,
+            ),
+            (
+                "raft_kv_writes_per_second",
+                "Writes applied per second",
+                self.total_writes_per_second,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            writeln!(out, "# HELP {name} {help}").unwrap();
+            writeln!(out, "# TYPE {name} gauge").unwrap();
+            writeln!(out, "{name} {value}").unwrap();
+        }
+
+        let name = "raft_kv_apply_duration_seconds";
+        writeln!(out, "# HELP {name} Apply latency of writes per operation").unwrap();
+        writeln!(out, "# TYPE {name} histogram").unwrap();
+        for (op, histogram) in Operation::ALL.iter().zip(&self.operations) {
+            let label = op.label();
+            let mut cumulative = 0;
+            for (le, n) in OPERATION_LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += n;
+                writeln!(
+                    out,
+                    "{name}_bucket{{operation=\"{label}\",le=\"{le}\"}} {cumulative}"
+                )
+                .unwrap();
+            }
+            let count = histogram.count;
+            writeln!(
+                out,
+                "{name}_bucket{{operation=\"{label}\",le=\"+Inf\"}} {count}"
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "{name}_sum{{operation=\"{label}\"}} {}",
+                histogram.sum_seconds
+            )
+            .unwrap();
+            writeln!(out, "{name}_count{{operation=\"{label}\"}} {count}").unwrap();
+        }
+        writeln!(
+            out,
+            "# HELP raft_kv_writes_total Writes applied since start"
+        )
+        .unwrap();
+        writeln!(out, "# TYPE raft_kv_writes_total counter").unwrap();
+        writeln!(out, "raft_kv_writes_total {}", self.total_writes).unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_over_window() {
+        let collector = MetricsCollector::default();
+        assert_eq!(collector.snapshot().p99_apply_latency_ms, 0.0);
+
+        // 1..=1000 ms, then 10 more: the window only keeps the last 1000.
+        for ms in 1..=1010 {
+            collector.record_apply_duration(Duration::from_millis(ms));
+        }
+        let snapshot = collector.snapshot();
+        assert_eq!(snapshot.total_writes, 1010);
+        // 11..=1010 ms
+        assert_eq!(snapshot.avg_apply_latency_ms, 510.5);
+        assert_eq!(snapshot.p99_apply_latency_ms, 1000.0);
+    }
+
+    #[test]
+    fn test_writes_per_second() {
+        assert_eq!(writes_per_second(50, Duration::from_millis(100)), 500.0);
+        // A burst tick right after the previous one.
+        assert_eq!(writes_per_second(0, Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn test_to_prometheus() {
+        let text = MetricsSnapshot {
+            avg_apply_latency_ms: 1.5,
+            p99_apply_latency_ms: 4.0,
+            total_writes_per_second: 200.0,
+            total_writes: 7,
+            operations: Default::default(),
+        }
+        .to_prometheus();
+        assert!(text.contains(
+            "# TYPE raft_kv_apply_latency_p99_ms gauge\nraft_kv_apply_latency_p99_ms 4\n"
+        ));
+        assert!(text.contains("raft_kv_writes_per_second 200\n"));
+        assert!(text.ends_with("raft_kv_writes_total 7\n"));
+    }
+
+    #[test]
+    fn test_operation_histograms() {
+        let collector = MetricsCollector::default();
+        for ms in [0, 5, 50, 2000] {
+            collector.record_operation(Operation::Update, Duration::from_millis(ms));
+        }
+        collector.record_operation(Operation::Delete, Duration::from_micros(50));
+
+        let snapshot = collector.snapshot();
+        let [create, update, delete] = snapshot.operations;
+        assert_eq!(create.count, 0);
+        assert_eq!(update.buckets, [1, 0, 1, 1, 0]);
+        assert_eq!(update.count, 4);
+        assert_eq!(update.sum_seconds, 2.055);
+        assert_eq!(delete.buckets, [1, 0, 0, 0, 0]);
+
+        let text = snapshot.to_prometheus();
+        assert!(text.contains("# TYPE raft_kv_apply_duration_seconds histogram\n"));
+        assert!(text.contains(concat!(
+            "raft_kv_apply_duration_seconds_bucket{operation=\"update\",le=\"0.01\"} 2\n",
+            "raft_kv_apply_duration_seconds_bucket{operation=\"update\",le=\"0.1\"} 3\n",
+            "raft_kv_apply_duration_seconds_bucket{operation=\"update\",le=\"1\"} 3\n",
+            "raft_kv_apply_duration_seconds_bucket{operation=\"update\",le=\"+Inf\"} 4\n",
+            "raft_kv_apply_duration_seconds_sum{operation=\"update\"} 2.055\n",
+            "raft_kv_apply_duration_seconds_count{operation=\"update\"} 4\n",
+        )));
+        assert!(text.contains("raft_kv_apply_duration_seconds_count{operation=\"create\"} 0\n"));
+    }
+}
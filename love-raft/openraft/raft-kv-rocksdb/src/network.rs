@@ -1,7 +1,16 @@
 pub mod api;
+pub mod codec;
+pub mod fault_injector;
 pub mod management;
 pub mod raft;
 mod raft_network_impl;
 
 pub use raft_network_impl::Network;
 pub use raft_network_impl::NetworkConnection;
+
+use crate::node_manager::PeerLiveness;
+
+/// A network layer whose connections report every successful RPC to a [`PeerLiveness`].
+pub trait TracksLiveness {
+    fn liveness(&self) -> PeerLiveness;
+}
@@ -29,6 +29,11 @@ pub fn rest(app: &mut Server) {
  */
 async fn write(mut req: Request<Arc<App>>) -> tide::Result {
     let body = req.body_json().await?;
+
+    // Wait for a slot without holding a thread, other requests keep being served.
+    // The slot is released when `_permit` is dropped, after raft answered.
+    let _permit = req.state().write_guard.acquire_async().await;
+
     let res = req.state().raft.client_write(body).await;
     Ok(Response::builder(StatusCode::Ok)
         .body(Body::from_json(&res)?)
@@ -47,7 +52,7 @@ async fn read(mut req: Request<Arc<App>>) -> tide::Result {
 }
 
 async fn consistent_read(mut req: Request<Arc<App>>) -> tide::Result {
-    let ret = req.state().raft.ensure_linearizable().await;
+    let ret = req.state().lease.ensure_readable(&req.state().raft).await;
 
     match ret {
         Ok(_) => {
@@ -62,8 +67,11 @@ async fn consistent_read(mut req: Request<Arc<App>>) -> tide::Result {
                 .body(Body::from_json(&res)?)
                 .build())
         }
-        e => Ok(Response::builder(StatusCode::Ok)
-            .body(Body::from_json(&e)?)
-            .build()),
+        Err(e) => {
+            let res: Result<String, _> = Err(e);
+            Ok(Response::builder(StatusCode::Ok)
+                .body(Body::from_json(&res)?)
+                .build())
+        }
     }
 }
@@ -0,0 +1,215 @@
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// How raft RPC messages are encoded in an [`RpcPayload`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SerializationFormat {
+    /// Human readable, e.g. to look at captured traffic. Understood by every node.
+    #[default]
+    Json,
+    /// Compact binary encoding, `AppendEntries` take about a fifth of their JSON size.
+    ///
+    /// Only nodes that know this format accept it, upgrade the whole cluster before enabling it.
+    Postcard,
+}
+
+/// Turns raft messages into bytes and back.
+pub trait Codec {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError>;
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError>;
+}
+
+pub struct JsonCodec;
+
+pub struct PostcardCodec;
+
+impl Codec for JsonCodec {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        serde_json::to_vec(value).map_err(CodecError::Json)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        serde_json::from_slice(bytes).map_err(CodecError::Json)
+    }
+}
+
+impl Codec for PostcardCodec {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        postcard::to_allocvec(value).map_err(CodecError::Postcard)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        postcard::from_bytes(bytes).map_err(CodecError::Postcard)
+    }
+}
+
+impl Codec for SerializationFormat {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            SerializationFormat::Json => JsonCodec.encode(value),
+            SerializationFormat::Postcard => PostcardCodec.encode(value),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        match self {
+            SerializationFormat::Json => JsonCodec.decode(bytes),
+            SerializationFormat::Postcard => PostcardCodec.decode(bytes),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CodecError {
+    Json(serde_json::Error),
+    Postcard(postcard::Error),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Json(e) => write!(f, "json codec: {}", e),
+            CodecError::Postcard(e) => write!(f, "postcard codec: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodecError::Json(e) => Some(e),
+            CodecError::Postcard(e) => Some(e),
+        }
+    }
+}
+
+/// A raft RPC message as sent over toy-rpc, encoded with `format`.
+///
+/// The format travels with the message and the receiver replies in the same format, so nodes
+/// configured with different formats still understand each other.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcPayload {
+    pub format: SerializationFormat,
+    pub data: Vec<u8>,
+}
+
+impl RpcPayload {
+    pub fn encode<T: Serialize>(
+        format: SerializationFormat,
+        value: &T,
+    ) -> Result<Self, CodecError> {
+        Ok(Self {
+            format,
+            data: format.encode(value)?,
+        })
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T, CodecError> {
+        self.format.decode(&self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use openraft::raft::AppendEntriesRequest;
+    use openraft::CommittedLeaderId;
+    use openraft::Entry;
+    use openraft::EntryPayload;
+    use openraft::LogId;
+    use openraft::Vote;
+
+    use super::*;
+    use crate::store::Request;
+    use crate::TypeConfig;
+
+    /// An `AppendEntries` from leader 1 in term 3 carrying `n` entries after index `prev`.
+    fn append_entries(prev: u64, n: u64) -> AppendEntriesRequest<TypeConfig> {
+        let log_id = |index| LogId::new(CommittedLeaderId::new(3, 1), index);
+        AppendEntriesRequest {
+            vote: Vote::new_committed(3, 1),
+            prev_log_id: Some(log_id(prev)),
+            entries: (prev + 1..=prev + n)
+                .map(|i| Entry {
+                    log_id: log_id(i),
+                    payload: EntryPayload::Normal(Request::Set {
+                        key: format!("key-{}", i),
+                        value: format!("value-{}", i),
+                    }),
+                })
+                .collect(),
+            leader_commit: Some(log_id(prev)),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let req = append_entries(1000, 100);
+
+        for format in [SerializationFormat::Json, SerializationFormat::Postcard] {
+            let payload = RpcPayload::encode(format, &req).unwrap();
+            let back: AppendEntriesRequest<TypeConfig> = payload.decode().unwrap();
+            assert_eq!(back.vote, req.vote);
+            assert_eq!(back.prev_log_id, req.prev_log_id);
+            assert_eq!(back.leader_commit, req.leader_commit);
+            assert_eq!(
+                back.entries.iter().map(|e| e.log_id).collect::<Vec<_>>(),
+                req.entries.iter().map(|e| e.log_id).collect::<Vec<_>>()
+            );
+        }
+
+        let json = RpcPayload::encode(SerializationFormat::Json, &req).unwrap();
+        let postcard = RpcPayload::encode(SerializationFormat::Postcard, &req).unwrap();
+        assert!(
+            postcard.data.len() * 4 < json.data.len(),
+            "postcard {} bytes, json {} bytes",
+            postcard.data.len(),
+            json.data.len()
+        );
+
+        // Bytes in one format are not a message in the other.
+        let mislabeled = RpcPayload {
+            format: SerializationFormat::Json,
+            data: postcard.data,
+        };
+        let err = mislabeled
+            .decode::<AppendEntriesRequest<TypeConfig>>()
+            .unwrap_err();
+        assert!(matches!(err, CodecError::Json(_)), "{}", err);
+    }
+
+    /// Size and speed of 1000 `AppendEntries` with 100 entries each, in both formats.
+    #[test]
+    #[ignore = "benchmark: cargo test --release bench_codec -- --ignored --nocapture"]
+    fn bench_codec() {
+        let requests: Vec<_> = (0..1000).map(|i| append_entries(i * 100, 100)).collect();
+
+        for format in [SerializationFormat::Json, SerializationFormat::Postcard] {
+            let start = Instant::now();
+            let encoded: Vec<Vec<u8>> = requests
+                .iter()
+                .map(|req| format.encode(req).unwrap())
+                .collect();
+            let encode_time = start.elapsed();
+
+            let start = Instant::now();
+            for bytes in &encoded {
+                let _: AppendEntriesRequest<TypeConfig> = format.decode(bytes).unwrap();
+            }
+            let decode_time = start.elapsed();
+
+            let total: usize = encoded.iter().map(|b| b.len()).sum();
+            println!(
+                "{:?}: {} bytes per request, encode {:.0} req/s, decode {:.0} req/s",
+                format,
+                total / requests.len(),
+                requests.len() as f64 / encode_time.as_secs_f64(),
+                requests.len() as f64 / decode_time.as_secs_f64(),
+            );
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use openraft::error::InstallSnapshotError;
+use openraft::error::NetworkError;
+use openraft::error::RPCError;
+use openraft::error::RaftError;
+use openraft::error::Unreachable;
+use openraft::network::RPCOption;
+use openraft::network::RaftNetwork;
+use openraft::network::RaftNetworkFactory;
+use openraft::raft::AppendEntriesRequest;
+use openraft::raft::AppendEntriesResponse;
+use openraft::raft::InstallSnapshotRequest;
+use openraft::raft::InstallSnapshotResponse;
+use openraft::raft::VoteRequest;
+use openraft::raft::VoteResponse;
+use openraft::AnyError;
+use rand::Rng;
+
+use super::Network;
+use super::NetworkConnection;
+use super::TracksLiveness;
+use crate::node_manager::PeerLiveness;
+use crate::Node;
+use crate::NodeId;
+use crate::TypeConfig;
+
+/// The faults applied to outgoing RPCs of one node.
+#[derive(Debug, Clone, Default)]
+pub struct FaultRules {
+    /// Probability in `[0, 1]` that an RPC is dropped and reported as a network error.
+    pub drop_probability: f64,
+
+    /// Extra latency added to every RPC, sampled uniformly. An empty range adds no delay.
+    pub delay_range: Range<Duration>,
+
+    /// RPCs to these nodes are never sent and reported as unreachable.
+    pub partition: HashSet<NodeId>,
+}
+
+/// A shared, mutable handle to [`FaultRules`].
+///
+/// All connections created by one [`FaultInjectingNetwork`] share the same rules, so a test can
+/// partition or heal a node while the cluster is running.
+#[derive(Debug, Clone, Default)]
+pub struct FaultConfig {
+    rules: Arc<RwLock<FaultRules>>,
+}
+
+impl FaultConfig {
+    pub fn new(rules: FaultRules) -> Self {
+        Self {
+            rules: Arc::new(RwLock::new(rules)),
+        }
+    }
+
+    /// Drop all RPCs sent to `target`.
+    pub fn partition(&self, target: NodeId) {
+        self.rules.write().unwrap().partition.insert(target);
+    }
+
+    /// Remove all partitions.
+    pub fn heal(&self) {
+        self.rules.write().unwrap().partition.clear();
+    }
+
+    pub fn set_drop_probability(&self, p: f64) {
+        self.rules.write().unwrap().drop_probability = p;
+    }
+
+    pub fn set_delay_range(&self, range: Range<Duration>) {
+        self.rules.write().unwrap().delay_range = range;
+    }
+}
+
+/// Network factory that wraps every connection in a [`FaultInjector`].
+pub struct FaultInjectingNetwork {
+    pub faults: FaultConfig,
+    /// Only RPCs that got through the faults count as a sign of life.
+    pub liveness: PeerLiveness,
+}
+
+impl TracksLiveness for FaultInjectingNetwork {
+    fn liveness(&self) -> PeerLiveness {
+        self.liveness.clone()
+    }
+}
+
+impl RaftNetworkFactory<TypeConfig> for FaultInjectingNetwork {
+    type Network = FaultInjector;
+
+    async fn new_client(&mut self, target: NodeId, node: &Node) -> Self::Network {
+        let mut network = Network {
+            liveness: self.liveness.clone(),
+            ..Default::default()
+        };
+        let inner = network.new_client(target, node).await;
+        FaultInjector::new(inner, self.faults.clone())
+    }
+}
+
+/// A [`NetworkConnection`] that drops, delays or partitions RPCs according to a [`FaultConfig`].
+pub struct FaultInjector {
+    inner: NetworkConnection,
+    faults: FaultConfig,
+}
+
+impl FaultInjector {
+    pub fn new(inner: NetworkConnection, config: FaultConfig) -> Self {
+        Self {
+            inner,
+            faults: config,
+        }
+    }
+
+    /// Decide the fate of one RPC: `Err` if it must not be sent, otherwise sleep for the
+    /// injected delay and let it through.
+    async fn inject<E: std::error::Error>(&self) -> Result<(), RPCError<NodeId, Node, E>> {
+        let target = self.inner.target();
+
+        let (dropped, delay) = {
+            let rules = self.faults.rules.read().unwrap();
+
+            if rules.partition.contains(&target) {
+                let e = AnyError::error(format!("node {} is partitioned", target));
+                return Err(RPCError::Unreachable(Unreachable::new(&e)));
+            }
+
+            let mut rng = rand::thread_rng();
+            let dropped = rng.gen_bool(rules.drop_probability.clamp(0.0, 1.0));
+            let delay = if rules.delay_range.is_empty() {
+                Duration::ZERO
+            } else {
+                rng.gen_range(rules.delay_range.clone())
+            };
+            (dropped, delay)
+        };
+
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+
+        if dropped {
+            let e = AnyError::error(format!("rpc to node {} dropped", target));
+            return Err(RPCError::Network(NetworkError::new(&e)));
+        }
+
+        Ok(())
+    }
+}
+
+impl RaftNetwork<TypeConfig> for FaultInjector {
+    async fn append_entries(
+        &mut self,
+        req: AppendEntriesRequest<TypeConfig>,
+        option: RPCOption,
+    ) -> Result<AppendEntriesResponse<NodeId>, RPCError<NodeId, Node, RaftError<NodeId>>> {
+        self.inject().await?;
+        self.inner.append_entries(req, option).await
+    }
+
+    async fn install_snapshot(
+        &mut self,
+        req: InstallSnapshotRequest<TypeConfig>,
+        option: RPCOption,
+    ) -> Result<
+        InstallSnapshotResponse<NodeId>,
+        RPCError<NodeId, Node, RaftError<NodeId, InstallSnapshotError>>,
+    > {
+        self.inject().await?;
+        self.inner.install_snapshot(req, option).await
+    }
+
+    async fn vote(
+        &mut self,
+        req: VoteRequest<NodeId>,
+        option: RPCOption,
+    ) -> Result<VoteResponse<NodeId>, RPCError<NodeId, Node, RaftError<NodeId>>> {
+        self.inject().await?;
+        self.inner.vote(req, option).await
+    }
+}
@@ -1,5 +1,7 @@
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use openraft::error::Infallible;
@@ -10,6 +12,11 @@ use tide::Response;
 use tide::StatusCode;
 
 use crate::app::App;
+use crate::health::health_check;
+use crate::health::HealthStatus;
+use crate::log_level;
+use crate::log_level::SetLogLevelError;
+use crate::log_level::SetLogLevelRequest;
 use crate::Node;
 use crate::NodeId;
 use crate::Server;
@@ -22,6 +29,13 @@ pub fn rest(app: &mut Server) {
     cluster.at("/change-membership").post(change_membership);
     cluster.at("/init").post(init);
     cluster.at("/metrics").get(metrics);
+
+    app.at("/metrics").get(prometheus_metrics);
+
+    app.at("/health").get(health);
+    app.at("/health/ready").get(ready);
+
+    app.at("/log-level").post(set_log_level);
 }
 
 /// Add a node as **Learner**.
@@ -71,3 +85,102 @@ async fn metrics(req: Request<Arc<App>>) -> tide::Result {
         .body(Body::from_json(&res)?)
         .build())
 }
+
+/// Change the log filter of this node, see [`log_level::LogLevelControl`].
+///
+/// `404` if the node was started without one, `429` if the client changed it within the last
+/// [`log_level::CHANGE_INTERVAL`]. The client is the TCP peer, proxy headers are ignored so a
+/// client cannot pick its own address.
+async fn set_log_level(mut req: Request<Arc<App>>) -> tide::Result {
+    let body: SetLogLevelRequest = req.body_json().await?;
+    let Some(control) = log_level::control() else {
+        return Ok(Response::new(StatusCode::NotFound));
+    };
+
+    let client = req
+        .peer_addr()
+        .and_then(|addr| addr.parse::<SocketAddr>().ok())
+        .map(|addr| addr.ip())
+        .unwrap_or(IpAddr::from([0, 0, 0, 0]));
+
+    match control.set_log_level(client, &body) {
+        Ok(res) => Ok(Response::builder(StatusCode::Ok)
+            .body(Body::from_json(&res)?)
+            .build()),
+        Err(e) => {
+            let code = match e {
+                SetLogLevelError::InvalidLevel(_) | SetLogLevelError::InvalidFilter(_) => {
+                    StatusCode::BadRequest
+                }
+                SetLogLevelError::RateLimited(_) => StatusCode::TooManyRequests,
+                SetLogLevelError::Reload(_) => StatusCode::InternalServerError,
+            };
+            Ok(Response::builder(code).body(e.to_string()).build())
+        }
+    }
+}
+
+/// Apply latency and write rate of this node, in the Prometheus text format.
+async fn prometheus_metrics(req: Request<Arc<App>>) -> tide::Result {
+    let snapshot = req.state().metrics.snapshot();
+    Ok(Response::builder(StatusCode::Ok)
+        .content_type("text/plain; version=0.0.4")
+        .body(snapshot.to_prometheus())
+        .build())
+}
+
+/// Liveness: 200 if the node is healthy, 503 if it is degraded.
+async fn health(req: Request<Arc<App>>) -> tide::Result {
+    let status = health_check(&req.state().raft).await;
+    let healthy = status.is_healthy();
+    health_response(&status, healthy)
+}
+
+/// Readiness: 200 only on a healthy leader, 503 on every other node.
+async fn ready(req: Request<Arc<App>>) -> tide::Result {
+    let status = health_check(&req.state().raft).await;
+    let ready = status.is_ready();
+    health_response(&status, ready)
+}
+
+fn health_response(status: &HealthStatus, ok: bool) -> tide::Result {
+    let code = if ok {
+        StatusCode::Ok
+    } else {
+        StatusCode::ServiceUnavailable
+    };
+    Ok(Response::builder(code)
+        .body(Body::from_json(status)?)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::tests::status;
+    use crate::health::RaftState;
+
+    #[test]
+    fn test_health_status_codes() -> tide::Result<()> {
+        let caught_up = status(RaftState::Follower, Some(10), Some(10));
+        let res = health_response(&caught_up, caught_up.is_healthy())?;
+        assert_eq!(res.status(), StatusCode::Ok);
+
+        let lagging = status(RaftState::Follower, Some(500), Some(10));
+        let res = health_response(&lagging, lagging.is_healthy())?;
+        assert_eq!(res.status(), StatusCode::ServiceUnavailable);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ready_status_codes() -> tide::Result<()> {
+        let leader = status(RaftState::Leader, Some(10), Some(10));
+        let res = health_response(&leader, leader.is_ready())?;
+        assert_eq!(res.status(), StatusCode::Ok);
+
+        let follower = status(RaftState::Follower, Some(10), Some(10));
+        let res = health_response(&follower, follower.is_ready())?;
+        assert_eq!(res.status(), StatusCode::ServiceUnavailable);
+        Ok(())
+    }
+}
@@ -8,6 +8,8 @@ use openraft::raft::VoteRequest;
 use openraft::raft::VoteResponse;
 use toy_rpc::macros::export_impl;
 
+use super::codec::CodecError;
+use super::codec::RpcPayload;
 use crate::app::App;
 use crate::TypeConfig;
 
@@ -55,4 +57,30 @@ impl Raft {
             .await
             .map_err(|e| toy_rpc::Error::Internal(Box::new(e)))
     }
+
+    /// [`Self::vote`] with the request encoded by the sender, see [`RpcPayload`].
+    #[export_method]
+    pub async fn vote_encoded(&self, req: RpcPayload) -> Result<RpcPayload, toy_rpc::Error> {
+        let res = self.vote(req.decode().map_err(codec_error)?).await?;
+        RpcPayload::encode(req.format, &res).map_err(codec_error)
+    }
+
+    /// [`Self::append`] with the request encoded by the sender, see [`RpcPayload`].
+    #[export_method]
+    pub async fn append_encoded(&self, req: RpcPayload) -> Result<RpcPayload, toy_rpc::Error> {
+        let res = self.append(req.decode().map_err(codec_error)?).await?;
+        RpcPayload::encode(req.format, &res).map_err(codec_error)
+    }
+
+    /// [`Self::snapshot`] with the request encoded by the sender, see [`RpcPayload`].
+    #[export_method]
+    pub async fn snapshot_encoded(&self, req: RpcPayload) -> Result<RpcPayload, toy_rpc::Error> {
+        let res = self.snapshot(req.decode().map_err(codec_error)?).await?;
+        RpcPayload::encode(req.format, &res).map_err(codec_error)
+    }
+}
+
+/// Unlike `Internal`, an `ExecutionError` is sent back to the caller.
+fn codec_error(e: CodecError) -> toy_rpc::Error {
+    toy_rpc::Error::ExecutionError(e.to_string())
 }
@@ -1,5 +1,11 @@
 use std::any::Any;
+use std::fmt::Debug;
 use std::fmt::Display;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use openraft::error::InstallSnapshotError;
 use openraft::error::NetworkError;
@@ -20,15 +26,33 @@ use serde::de::DeserializeOwned;
 use toy_rpc::pubsub::AckModeNone;
 use toy_rpc::Client;
 
+use super::codec::CodecError;
+use super::codec::RpcPayload;
+use super::codec::SerializationFormat;
 use super::raft::RaftClientStub;
+use super::TracksLiveness;
+use crate::node_manager::PeerLiveness;
 use crate::Node;
 use crate::NodeId;
 use crate::TypeConfig;
 
-pub struct Network {}
+#[derive(Debug, Clone, Default)]
+pub struct Network {
+    /// Shared by all connections, see [`crate::node_manager::RaftNodeManager`].
+    pub liveness: PeerLiveness,
+    /// Encoding of the RPCs this node sends; replies come back in the same encoding.
+    ///
+    /// With [`SerializationFormat::Json`] the openraft messages are sent as they are and toy-rpc
+    /// encodes them as JSON, exactly like nodes that predate this option.
+    pub format: SerializationFormat,
+}
+
+impl TracksLiveness for Network {
+    fn liveness(&self) -> PeerLiveness {
+        self.liveness.clone()
+    }
+}
 
-// NOTE: This could be implemented also on `Arc<ExampleNetwork>`, but since it's empty, implemented
-// directly.
 impl RaftNetworkFactory<TypeConfig> for Network {
     type Network = NetworkConnection;
 
@@ -39,11 +63,7 @@ impl RaftNetworkFactory<TypeConfig> for Network {
         let client = Client::dial_websocket(&addr).await.ok();
         tracing::debug!("new_client: is_none: {}", client.is_none());
 
-        NetworkConnection {
-            addr,
-            client,
-            target,
-        }
+        NetworkConnection::new(addr, client, target, self.liveness.clone(), self.format)
     }
 }
 
@@ -51,26 +71,110 @@ pub struct NetworkConnection {
     addr: String,
     client: Option<Client<AckModeNone>>,
     target: NodeId,
+    /// RPCs started, including those that failed to connect.
+    rpc_count: AtomicU64,
+    /// RPCs that failed to connect or returned an error.
+    error_count: AtomicU64,
+    /// Unix timestamp in milliseconds of the last successful RPC, 0 if there was none.
+    last_success: AtomicU64,
+    /// Successful RPCs are also reported here, for all connections of the node to see.
+    liveness: PeerLiveness,
+    format: SerializationFormat,
 }
+
 impl NetworkConnection {
+    fn new(
+        addr: String,
+        client: Option<Client<AckModeNone>>,
+        target: NodeId,
+        liveness: PeerLiveness,
+        format: SerializationFormat,
+    ) -> Self {
+        Self {
+            addr,
+            client,
+            target,
+            rpc_count: AtomicU64::new(0),
+            error_count: AtomicU64::new(0),
+            last_success: AtomicU64::new(0),
+            liveness,
+            format,
+        }
+    }
+
+    /// The id of the node this connection sends RPCs to.
+    pub fn target(&self) -> NodeId {
+        self.target
+    }
+
+    /// Returns the client for a new RPC, reconnecting if the last attempt failed.
+    ///
+    /// Every RPC goes through here, so this is where it is counted.
     async fn c<E: std::error::Error + DeserializeOwned>(
         &mut self,
     ) -> Result<&Client<AckModeNone>, RPCError<NodeId, Node, E>> {
+        self.rpc_count.fetch_add(1, Ordering::Relaxed);
         if self.client.is_none() {
             self.client = Client::dial_websocket(&self.addr).await.ok();
         }
-        self.client
-            .as_ref()
-            .ok_or_else(|| RPCError::Network(NetworkError::from(AnyError::default())))
+        match self.client.as_ref() {
+            Some(client) => Ok(client),
+            None => {
+                self.error_count.fetch_add(1, Ordering::Relaxed);
+                Err(RPCError::Network(NetworkError::from(AnyError::default())))
+            }
+        }
+    }
+
+    /// Records the outcome of a completed RPC.
+    fn record<T, E>(&self, result: Result<T, E>) -> Result<T, E> {
+        match &result {
+            Ok(_) => {
+                self.last_success
+                    .store(unix_millis(SystemTime::now()), Ordering::Relaxed);
+                self.liveness.record_success(self.target);
+            }
+            Err(_) => {
+                self.error_count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        result
+    }
+}
+
+impl Debug for NetworkConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let last_success = match self.last_success.load(Ordering::Relaxed) {
+            0 => "never".to_string(),
+            ms => {
+                // Saturates to zero if the clock went backwards since.
+                let ago = Duration::from_millis(unix_millis(SystemTime::now()).saturating_sub(ms));
+                format!("{:.1}s ago", ago.as_secs_f64())
+            }
+        };
+
+        f.debug_struct("NetworkConnection")
+            .field("target", &self.target)
+            .field("addr", &self.addr)
+            .field("rpc_count", &self.rpc_count.load(Ordering::Relaxed))
+            .field("error_count", &self.error_count.load(Ordering::Relaxed))
+            .field("last_success", &format_args!("{}", last_success))
+            .finish()
     }
 }
 
+fn unix_millis(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
 #[derive(Debug)]
 struct ErrWrap(Box<dyn std::error::Error>);
 
 impl Display for ErrWrap {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+        Display::fmt(&self.0, f)
     }
 }
 
@@ -98,6 +202,11 @@ fn to_error<E: std::error::Error + 'static + Clone>(
     }
 }
 
+/// A request this node can not encode, or a reply it can not decode.
+fn codec_error<E: std::error::Error>(e: CodecError) -> RPCError<NodeId, Node, E> {
+    RPCError::Network(NetworkError::new(&e))
+}
+
 // With nightly-2023-12-20, and `err(Debug)` in the instrument macro, this gives the following lint
 // warning. Without `err(Debug)` it is OK. Suppress it with `#[allow(clippy::blocks_in_conditions)]`
 //
@@ -128,6 +237,7 @@ impl RaftNetwork<TypeConfig> for NetworkConnection {
         _option: RPCOption,
     ) -> Result<AppendEntriesResponse<NodeId>, RPCError<NodeId, Node, RaftError<NodeId>>> {
         tracing::debug!(req = debug(&req), "append_entries");
+        let format = self.format;
 
         let c = self.c().await?;
         tracing::debug!("got connection");
@@ -135,7 +245,18 @@ impl RaftNetwork<TypeConfig> for NetworkConnection {
         let raft = c.raft();
         tracing::debug!("got raft");
 
-        raft.append(req).await.map_err(|e| to_error(e, self.target))
+        match format {
+            SerializationFormat::Json => {
+                let res = raft.append(req).await;
+                self.record(res).map_err(|e| to_error(e, self.target))
+            }
+            SerializationFormat::Postcard => {
+                let req = RpcPayload::encode(format, &req).map_err(codec_error)?;
+                let res = raft.append_encoded(req).await;
+                let res = self.record(res).map_err(|e| to_error(e, self.target))?;
+                res.decode().map_err(codec_error)
+            }
+        }
     }
 
     #[tracing::instrument(level = "debug", skip_all, err(Debug))]
@@ -148,12 +269,18 @@ impl RaftNetwork<TypeConfig> for NetworkConnection {
         RPCError<NodeId, Node, RaftError<NodeId, InstallSnapshotError>>,
     > {
         tracing::debug!(req = debug(&req), "install_snapshot");
-        self.c()
-            .await?
-            .raft()
-            .snapshot(req)
-            .await
-            .map_err(|e| to_error(e, self.target))
+        match self.format {
+            SerializationFormat::Json => {
+                let res = self.c().await?.raft().snapshot(req).await;
+                self.record(res).map_err(|e| to_error(e, self.target))
+            }
+            format @ SerializationFormat::Postcard => {
+                let req = RpcPayload::encode(format, &req).map_err(codec_error)?;
+                let res = self.c().await?.raft().snapshot_encoded(req).await;
+                let res = self.record(res).map_err(|e| to_error(e, self.target))?;
+                res.decode().map_err(codec_error)
+            }
+        }
     }
 
     #[tracing::instrument(level = "debug", skip_all, err(Debug))]
@@ -163,11 +290,48 @@ impl RaftNetwork<TypeConfig> for NetworkConnection {
         _option: RPCOption,
     ) -> Result<VoteResponse<NodeId>, RPCError<NodeId, Node, RaftError<NodeId>>> {
         tracing::debug!(req = debug(&req), "vote");
-        self.c()
-            .await?
-            .raft()
-            .vote(req)
-            .await
-            .map_err(|e| to_error(e, self.target))
+        match self.format {
+            SerializationFormat::Json => {
+                let res = self.c().await?.raft().vote(req).await;
+                self.record(res).map_err(|e| to_error(e, self.target))
+            }
+            format @ SerializationFormat::Postcard => {
+                let req = RpcPayload::encode(format, &req).map_err(codec_error)?;
+                let res = self.c().await?.raft().vote_encoded(req).await;
+                let res = self.record(res).map_err(|e| to_error(e, self.target))?;
+                res.decode().map_err(codec_error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_shows_stats() {
+        let liveness = PeerLiveness::default();
+        let conn = NetworkConnection::new(
+            "ws://127.0.0.1:50062".to_string(),
+            None,
+            2,
+            liveness.clone(),
+            SerializationFormat::default(),
+        );
+        assert_eq!(
+            format!("{:?}", conn),
+            r#"NetworkConnection { target: 2, addr: "ws://127.0.0.1:50062", rpc_count: 0, error_count: 0, last_success: never }"#
+        );
+
+        conn.rpc_count.fetch_add(3, Ordering::Relaxed);
+        assert!(conn.record::<(), ()>(Err(())).is_err());
+        assert_eq!(liveness.last_success(2), None);
+        assert!(conn.record::<(), ()>(Ok(())).is_ok());
+        assert!(liveness.last_success(2).is_some());
+        assert_eq!(
+            format!("{:?}", conn),
+            r#"NetworkConnection { target: 2, addr: "ws://127.0.0.1:50062", rpc_count: 3, error_count: 1, last_success: 0.0s ago }"#
+        );
     }
 }
@@ -0,0 +1,224 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use openraft::RaftMetrics;
+use openraft::ServerState;
+use tokio::time::MissedTickBehavior;
+
+use crate::ExampleRaft;
+use crate::Node;
+use crate::NodeId;
+
+/// Lower bound of how often [`RaftNodeManager`] checks its peers, however short the timeout.
+pub const MIN_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// When each peer last answered an RPC from this node.
+///
+/// All connections created by one network layer report to the same instance, see
+/// [`crate::network::TracksLiveness`]. Only the leader sends RPCs to every node on a regular
+/// basis, so only the leader's view is meaningful.
+#[derive(Debug, Clone, Default)]
+pub struct PeerLiveness {
+    last_success: Arc<Mutex<HashMap<NodeId, Instant>>>,
+}
+
+impl PeerLiveness {
+    pub fn record_success(&self, target: NodeId) {
+        self.last_success
+            .lock()
+            .unwrap()
+            .insert(target, Instant::now());
+    }
+
+    pub fn last_success(&self, target: NodeId) -> Option<Instant> {
+        self.last_success.lock().unwrap().get(&target).copied()
+    }
+}
+
+/// Removes voters that stopped answering from the membership, so that a crashed node does not
+/// have to be removed by hand.
+///
+/// Runs on every node, but only acts while the node is the leader: heartbeats and replication
+/// keep [`PeerLiveness`] fresh for every reachable peer. A peer that has not answered for
+/// `node_timeout` is removed with `change_membership(voters - dead, false)`, and a
+/// `node_removed` span is recorded for it.
+///
+/// Nodes are only removed while the responsive voters are a majority: without a quorum the
+/// membership change could not be committed, and the problem is likely on the leader's side.
+pub struct RaftNodeManager {
+    raft: ExampleRaft,
+    liveness: PeerLiveness,
+    node_timeout: Duration,
+    /// When this node was first seen as leader in its current term. A new leader has not
+    /// talked to anyone yet, so every peer is given `node_timeout` from this instant.
+    leader_since: Option<(u64, Instant)>,
+}
+
+impl RaftNodeManager {
+    pub fn new(raft: ExampleRaft, liveness: PeerLiveness, node_timeout: Duration) -> Self {
+        Self {
+            raft,
+            liveness,
+            node_timeout,
+            leader_since: None,
+        }
+    }
+
+    /// Checks the peers four times per `node_timeout` until raft shuts down.
+    pub async fn run(mut self) {
+        let mut ticker = tokio::time::interval((self.node_timeout / 4).max(MIN_CHECK_INTERVAL));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            ticker.tick().await;
+
+            let metrics = self.raft.metrics().borrow().clone();
+            if metrics.state == ServerState::Shutdown {
+                return;
+            }
+
+            let Some((voters, dead)) = self.find_dead_voters(&metrics) else {
+                continue;
+            };
+            self.remove(voters, dead).await;
+        }
+    }
+
+    /// Whether a quorum still acknowledges this node as leader.
+    ///
+    /// `Raft::is_leader` is deprecated in favor of `ensure_linearizable`, which does the same
+    /// quorum round-trip.
+    pub async fn is_leader(&self) -> bool {
+        self.raft.ensure_linearizable().await.is_ok()
+    }
+
+    /// Current voters and those of them to remove, `None` if there is nothing to do.
+    fn find_dead_voters(
+        &mut self,
+        metrics: &RaftMetrics<NodeId, Node>,
+    ) -> Option<(BTreeSet<NodeId>, Vec<NodeId>)> {
+        if metrics.state != ServerState::Leader {
+            self.leader_since = None;
+            return None;
+        }
+        let leader_since = match self.leader_since {
+            Some((term, since)) if term == metrics.current_term => since,
+            _ => {
+                let now = Instant::now();
+                self.leader_since = Some((metrics.current_term, now));
+                now
+            }
+        };
+
+        let membership = metrics.membership_config.membership();
+        // A membership change is in progress, wait for it to finish.
+        if membership.get_joint_config().len() > 1 {
+            return None;
+        }
+        let voters: BTreeSet<NodeId> = membership.voter_ids().collect();
+
+        let dead = dead_voters(metrics.id, &voters, self.node_timeout, |id| {
+            self.liveness
+                .last_success(id)
+                .map_or(leader_since, |t| t.max(leader_since))
+        });
+        (!dead.is_empty()).then_some((voters, dead))
+    }
+
+    async fn remove(&self, voters: BTreeSet<NodeId>, dead: Vec<NodeId>) {
+        if !self.is_leader().await {
+            return;
+        }
+
+        let remaining: BTreeSet<NodeId> =
+            voters.into_iter().filter(|id| !dead.contains(id)).collect();
+        match self.raft.change_membership(remaining, false).await {
+            Ok(_) => {
+                for node_id in dead {
+                    tracing::info_span!("node_removed", node_id, node_timeout = ?self.node_timeout)
+                        .in_scope(|| tracing::info!("removed unresponsive node from membership"));
+                }
+            }
+            Err(e) => tracing::warn!(?dead, "failed to remove unresponsive nodes: {}", e),
+        }
+    }
+}
+
+/// Voters other than `me` whose `last_seen` is more than `timeout` ago.
+///
+/// Empty if removing them would leave less than a majority of `voters`.
+fn dead_voters(
+    me: NodeId,
+    voters: &BTreeSet<NodeId>,
+    timeout: Duration,
+    last_seen: impl Fn(NodeId) -> Instant,
+) -> Vec<NodeId> {
+    let dead: Vec<NodeId> = voters
+        .iter()
+        .copied()
+        .filter(|id| *id != me && last_seen(*id).elapsed() > timeout)
+        .collect();
+
+    let alive = voters.len() - dead.len();
+    if alive * 2 <= voters.len() {
+        return vec![];
+    }
+    dead
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::btreeset;
+
+    use super::*;
+
+    #[test]
+    fn test_dead_voters() {
+        let timeout = Duration::from_secs(10);
+        let recent = Instant::now();
+        let stale = recent - Duration::from_secs(11);
+        // Every node in `silent` was last seen 11s ago, the others just now.
+        let silent = |silent: &'static [NodeId]| {
+            move |id| {
+                if silent.contains(&id) {
+                    stale
+                } else {
+                    recent
+                }
+            }
+        };
+        let none = Vec::<NodeId>::new();
+
+        let voters = btreeset! {1, 2, 3};
+        assert_eq!(dead_voters(1, &voters, timeout, silent(&[])), none);
+        assert_eq!(dead_voters(1, &voters, timeout, silent(&[3])), vec![3]);
+
+        // The leader itself is never removed.
+        assert_eq!(dead_voters(1, &voters, timeout, silent(&[1])), none);
+
+        // Two of three silent: the leader is probably the one cut off, leave it alone.
+        assert_eq!(dead_voters(1, &voters, timeout, silent(&[2, 3])), none);
+
+        // Two of five silent: the other three are still a majority.
+        let voters = btreeset! {1, 2, 3, 4, 5};
+        assert_eq!(
+            dead_voters(1, &voters, timeout, silent(&[4, 5])),
+            vec![4, 5]
+        );
+    }
+
+    #[test]
+    fn test_liveness() {
+        let liveness = PeerLiveness::default();
+        assert_eq!(liveness.last_success(2), None);
+
+        let before = Instant::now();
+        liveness.clone().record_success(2);
+        assert!(liveness.last_success(2).unwrap() >= before);
+        assert_eq!(liveness.last_success(3), None);
+    }
+}
@@ -1,10 +1,24 @@
+use std::any::Any;
 use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::fmt;
 use std::fmt::Debug;
+use std::io;
 use std::io::Cursor;
 use std::ops::RangeBounds;
+use std::ops::RangeInclusive;
+use std::panic;
+use std::panic::AssertUnwindSafe;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Instant;
 
+use async_compression::tokio::bufread::GzipDecoder;
+use async_compression::tokio::write::GzipEncoder;
+use async_compression::Level;
 use byteorder::BigEndian;
 use byteorder::ReadBytesExt;
 use byteorder::WriteBytesExt;
@@ -27,16 +41,29 @@ use openraft::StorageError;
 use openraft::StorageIOError;
 use openraft::StoredMembership;
 use openraft::Vote;
+use rocksdb::compaction_filter::CompactionFilter;
+use rocksdb::compaction_filter_factory::CompactionFilterContext;
+use rocksdb::compaction_filter_factory::CompactionFilterFactory;
 use rocksdb::ColumnFamily;
 use rocksdb::ColumnFamilyDescriptor;
+use rocksdb::CompactionDecision;
 use rocksdb::Direction;
 use rocksdb::Options;
+use rocksdb::WriteBatch;
 use rocksdb::DB;
 use serde::Deserialize;
 use serde::Serialize;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
 use tokio::sync::RwLock;
 
+use crate::audit::AuditLogger;
+use crate::audit::AUDIT_LOG_FILE;
+use crate::metrics::MetricsCollector;
+use crate::metrics::Operation;
 use crate::typ;
+use crate::utils::log_dump::raft_log_dump;
+use crate::AppConfig;
 use crate::Node;
 use crate::NodeId;
 use crate::SnapshotData;
@@ -50,7 +77,97 @@ use crate::TypeConfig;
  */
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Request {
-    Set { key: String, value: String },
+    Set {
+        key: String,
+        value: String,
+    },
+    /// Removes `key`, the response value is the value it had.
+    Delete {
+        key: String,
+    },
+    /// Panics when applied, to test that a panicking request does not take the node down.
+    #[cfg(test)]
+    Panic,
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Request::Set { key, value } => write!(f, "Set{{key={}, value={}}}", key, value),
+            Request::Delete { key } => write!(f, "Delete{{key={}}}", key),
+            #[cfg(test)]
+            Request::Panic => write!(f, "Panic"),
+        }
+    }
+}
+
+/// Error returned when a command line like `set foo bar` can not be parsed into a [`Request`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input contains no command at all.
+    Empty,
+    /// The first word is not a known command.
+    UnknownCommand(String),
+    /// A required field of the command is absent.
+    MissingField {
+        command: &'static str,
+        field: &'static str,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty command, expected `set <key> <value>`"),
+            ParseError::UnknownCommand(cmd) => {
+                write!(f, "unknown command `{}`, expected `set <key> <value>`", cmd)
+            }
+            ParseError::MissingField { command, field } => {
+                write!(f, "`{}` is missing field `{}`", command, field)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses `set <key> <value>`, the command is case insensitive.
+///
+/// The key is a single word, the value is the rest of the line with surrounding whitespace
+/// trimmed, so it may contain spaces: `set greeting hello world`.
+impl FromStr for Request {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (command, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+
+        match command.to_ascii_lowercase().as_str() {
+            "" => Err(ParseError::Empty),
+            "set" => {
+                let rest = rest.trim_start();
+                let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let missing = |field| ParseError::MissingField {
+                    command: "set",
+                    field,
+                };
+
+                if key.is_empty() {
+                    return Err(missing("key"));
+                }
+                let value = value.trim();
+                if value.is_empty() {
+                    return Err(missing("value"));
+                }
+
+                Ok(Request::Set {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+            }
+            _ => Err(ParseError::UnknownCommand(command.to_string())),
+        }
+    }
 }
 
 /**
@@ -64,16 +181,38 @@ pub enum Request {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Response {
     pub value: Option<String>,
+    /// Set if applying the request panicked, see [`apply_request`].
+    ///
+    /// A panic is reported here rather than as a [`StorageError`]: openraft shuts the node down
+    /// on any `StorageError` from the state machine, and every node would hit the same panic
+    /// for the same committed entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StoredSnapshot {
     pub meta: SnapshotMeta<NodeId, Node>,
 
-    /// The data of the state machine at the time of this snapshot.
+    /// The data of the state machine at the time of this snapshot, gzip compressed.
+    ///
+    /// Snapshots written before compression was introduced hold the raw json, they are told
+    /// apart by the missing [`GZIP_MAGIC`].
     pub data: Vec<u8>,
+
+    /// `uncompressed_size / compressed_size` of `data`.
+    ///
+    /// `SnapshotMeta` is defined by openraft, so the ratio is kept next to it instead.
+    #[serde(default)]
+    pub compression_ratio: f64,
 }
 
+/// Gzip level used for snapshot data when the store is not configured otherwise.
+pub const DEFAULT_SNAPSHOT_COMPRESSION_LEVEL: u32 = 6;
+
+/// The first two bytes of every gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
 #[derive(Debug, Clone)]
 pub struct StateMachineStore {
     pub data: StateMachineData,
@@ -86,6 +225,17 @@ pub struct StateMachineStore {
 
     /// State machine stores snapshot in db.
     db: Arc<DB>,
+
+    /// Gzip level (0-9) used when building a snapshot.
+    snapshot_compression_level: u32,
+
+    /// Apply latency of every write, see [`MetricsCollector`].
+    pub metrics: Arc<MetricsCollector>,
+
+    /// Every applied entry is appended here before it changes the state machine.
+    ///
+    /// A failed audit write is a storage error, which stops the node, see `apply`.
+    pub audit: Arc<AuditLogger>,
 }
 
 #[derive(Debug, Clone)]
@@ -107,6 +257,9 @@ impl RaftSnapshotBuilder<TypeConfig> for StateMachineStore {
             let kvs = self.data.kvs.read().await;
             serde_json::to_vec(&*kvs).map_err(|e| StorageIOError::read_state_machine(&e))?
         };
+        let compressed = compress(&kv_json, self.snapshot_compression_level)
+            .await
+            .map_err(|e| StorageIOError::read_state_machine(&e))?;
 
         let snapshot_id = if let Some(last) = last_applied_log {
             format!("{}-{}-{}", last.leader_id, last.index, self.snapshot_idx)
@@ -122,20 +275,33 @@ impl RaftSnapshotBuilder<TypeConfig> for StateMachineStore {
 
         let snapshot = StoredSnapshot {
             meta: meta.clone(),
-            data: kv_json.clone(),
+            compression_ratio: compression_ratio(kv_json.len(), compressed.len()),
+            data: compressed.clone(),
         };
 
+        tracing::info!(
+            "built snapshot {}: {} bytes, compressed to {} bytes (ratio {:.2})",
+            meta.snapshot_id,
+            kv_json.len(),
+            compressed.len(),
+            snapshot.compression_ratio
+        );
+
         self.set_current_snapshot_(snapshot)?;
 
         Ok(Snapshot {
             meta,
-            snapshot: Box::new(Cursor::new(kv_json)),
+            snapshot: Box::new(Cursor::new(compressed)),
         })
     }
 }
 
 impl StateMachineStore {
-    async fn new(db: Arc<DB>) -> Result<StateMachineStore, StorageError<NodeId>> {
+    async fn new(
+        db: Arc<DB>,
+        audit: Arc<AuditLogger>,
+        snapshot_compression_level: u32,
+    ) -> Result<StateMachineStore, StorageError<NodeId>> {
         let mut sm = Self {
             data: StateMachineData {
                 last_applied_log_id: None,
@@ -144,21 +310,32 @@ impl StateMachineStore {
             },
             snapshot_idx: 0,
             db,
+            snapshot_compression_level: snapshot_compression_level.min(9),
+            metrics: Default::default(),
+            audit,
         };
 
         let snapshot = sm.get_current_snapshot_()?;
         if let Some(snap) = snapshot {
-            sm.update_state_machine_(snap).await?;
+            sm.update_state_machine_(&snap).await?;
         }
 
         Ok(sm)
     }
 
+    /// Replace the state machine with the snapshot, returns the uncompressed size of its data.
     async fn update_state_machine_(
         &mut self,
-        snapshot: StoredSnapshot,
-    ) -> Result<(), StorageError<NodeId>> {
-        let kvs: BTreeMap<String, String> = serde_json::from_slice(&snapshot.data)
+        snapshot: &StoredSnapshot,
+    ) -> Result<usize, StorageError<NodeId>> {
+        let kv_json = if snapshot.data.starts_with(&GZIP_MAGIC) {
+            decompress(&snapshot.data)
+                .await
+                .map_err(|e| StorageIOError::read_snapshot(Some(snapshot.meta.signature()), &e))?
+        } else {
+            snapshot.data.clone()
+        };
+        let kvs: BTreeMap<String, String> = serde_json::from_slice(&kv_json)
             .map_err(|e| StorageIOError::read_snapshot(Some(snapshot.meta.signature()), &e))?;
 
         self.data.last_applied_log_id = snapshot.meta.last_log_id;
@@ -166,7 +343,7 @@ impl StateMachineStore {
         let mut x = self.data.kvs.write().await;
         *x = kvs;
 
-        Ok(())
+        Ok(kv_json.len())
     }
 
     fn get_current_snapshot_(&self) -> StorageResult<Option<StoredSnapshot>> {
@@ -229,30 +406,59 @@ impl RaftStateMachine<TypeConfig> for StateMachineStore {
         I: IntoIterator<Item = typ::Entry> + OptionalSend,
         I::IntoIter: OptionalSend,
     {
-        let entries = entries.into_iter();
-        let mut replies = Vec::with_capacity(entries.size_hint().0);
+        let entries: Vec<typ::Entry> = entries.into_iter().collect();
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            tracing::debug!(
+                "apply {} entries:\n{}",
+                entries.len(),
+                raft_log_dump(&entries)
+            );
+        }
+        let mut replies = Vec::with_capacity(entries.len());
+
+        // Fail-stop: if the audit trail can not be written, e.g. the disk is full or the file
+        // can not be rotated, nothing is applied and the error stops this node's state machine.
+        // Applying without auditing would break the trail's guarantee; the node has to be
+        // restarted once the disk is fixed.
+        self.audit
+            .log_all(&entries)
+            .await
+            .map_err(|e| StorageIOError::write_state_machine(&e))?;
 
         for ent in entries {
             self.data.last_applied_log_id = Some(ent.log_id);
 
-            let mut resp_value = None;
+            let mut resp = Response {
+                value: None,
+                error: None,
+            };
 
             match ent.payload {
                 EntryPayload::Blank => {}
-                EntryPayload::Normal(req) => match req {
-                    Request::Set { key, value } => {
-                        resp_value = Some(value.clone());
-
-                        let mut st = self.data.kvs.write().await;
-                        st.insert(key, value);
+                EntryPayload::Normal(req) => {
+                    let started = Instant::now();
+                    let mut st = self.data.kvs.write().await;
+                    // The entry is committed and every node applies it the same way, so a panic
+                    // must not stop the node: report it to the client and keep applying.
+                    match panic::catch_unwind(AssertUnwindSafe(|| apply_request(&mut st, req))) {
+                        Ok((op, value)) => {
+                            self.metrics.record_operation(op, started.elapsed());
+                            resp.value = value;
+                        }
+                        Err(payload) => {
+                            let msg = panic_message(&*payload);
+                            tracing::error!(log_id = %ent.log_id, "apply panicked: {}", msg);
+                            resp.error = Some(format!("apply panicked: {}", msg));
+                        }
                     }
-                },
+                    self.metrics.record_apply_duration(started.elapsed());
+                }
                 EntryPayload::Membership(mem) => {
                     self.data.last_membership = StoredMembership::new(Some(ent.log_id), mem);
                 }
             }
 
-            replies.push(Response { value: resp_value });
+            replies.push(resp);
         }
         Ok(replies)
     }
@@ -273,12 +479,15 @@ impl RaftStateMachine<TypeConfig> for StateMachineStore {
         meta: &SnapshotMeta<NodeId, Node>,
         snapshot: Box<SnapshotData>,
     ) -> Result<(), StorageError<NodeId>> {
-        let new_snapshot = StoredSnapshot {
+        let mut new_snapshot = StoredSnapshot {
             meta: meta.clone(),
             data: snapshot.into_inner(),
+            compression_ratio: 0.0,
         };
 
-        self.update_state_machine_(new_snapshot.clone()).await?;
+        let uncompressed_size = self.update_state_machine_(&new_snapshot).await?;
+        new_snapshot.compression_ratio =
+            compression_ratio(uncompressed_size, new_snapshot.data.len());
 
         self.set_current_snapshot_(new_snapshot)?;
 
@@ -296,9 +505,56 @@ impl RaftStateMachine<TypeConfig> for StateMachineStore {
     }
 }
 
+/// Applies one client request to the key-value map, returns what it did and the response
+/// value.
+///
+/// Runs under [`panic::catch_unwind`], a panic leaves `kvs` with whatever it changed so far.
+fn apply_request(kvs: &mut BTreeMap<String, String>, req: Request) -> (Operation, Option<String>) {
+    match req {
+        Request::Set { key, value } => match kvs.insert(key, value.clone()) {
+            None => (Operation::Create, Some(value)),
+            Some(_) => (Operation::Update, Some(value)),
+        },
+        Request::Delete { key } => (Operation::Delete, kvs.remove(&key)),
+        #[cfg(test)]
+        Request::Panic => panic!("injected panic"),
+    }
+}
+
+/// The message of a caught panic, for `panic!("...")` with or without format arguments.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+async fn compress(data: &[u8], level: u32) -> io::Result<Vec<u8>> {
+    let mut encoder = GzipEncoder::with_quality(Vec::new(), Level::Precise(level as i32));
+    encoder.write_all(data).await?;
+    encoder.shutdown().await?;
+    Ok(encoder.into_inner())
+}
+
+async fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut decoder = GzipDecoder::new(data);
+    let mut out = Vec::new();
+    decoder.read_to_end(&mut out).await?;
+    Ok(out)
+}
+
+fn compression_ratio(uncompressed_size: usize, compressed_size: usize) -> f64 {
+    uncompressed_size as f64 / compressed_size.max(1) as f64
+}
+
 #[derive(Debug, Clone)]
 pub struct LogStore {
     db: Arc<DB>,
+    /// Entries with an index below this are purged. Shared with [`LogCompactionFilterFactory`].
+    compaction_watermark: Arc<AtomicU64>,
 }
 type StorageResult<T> = Result<T, StorageError<NodeId>>;
 
@@ -306,12 +562,59 @@ type StorageResult<T> = Result<T, StorageError<NodeId>>;
 /// Note that we're using big endian encoding to ensure correct sorting of keys
 fn id_to_bin(id: u64) -> Vec<u8> {
     let mut buf = Vec::with_capacity(8);
-    buf.write_u64::<BigEndian>(id).unwrap();
+    WriteBytesExt::write_u64::<BigEndian>(&mut buf, id).unwrap();
     buf
 }
 
 fn bin_to_id(buf: &[u8]) -> u64 {
-    (&buf[0..8]).read_u64::<BigEndian>().unwrap()
+    ReadBytesExt::read_u64::<BigEndian>(&mut &buf[0..8]).unwrap()
+}
+
+/// Key in the `meta` column family holding the compaction watermark, see [`LogStore::purge`].
+const COMPACTION_WATERMARK: &[u8] = b"compaction_watermark";
+
+/// Drops purged log entries while RocksDB compacts the `logs` column family.
+///
+/// `purge` only moves the watermark; the entries themselves disappear whenever RocksDB
+/// rewrites the files holding them, instead of through a range delete on every purge.
+pub struct LogCompactionFilter {
+    /// Entries with an index below this are dropped.
+    watermark: u64,
+}
+
+impl CompactionFilter for LogCompactionFilter {
+    fn filter(&mut self, _level: u32, key: &[u8], _value: &[u8]) -> CompactionDecision {
+        if bin_to_id(key) < self.watermark {
+            CompactionDecision::Remove
+        } else {
+            CompactionDecision::Keep
+        }
+    }
+
+    fn name(&self) -> &CStr {
+        c"raft-log-compaction-filter"
+    }
+}
+
+/// Hands every compaction run its own [`LogCompactionFilter`] with the watermark of that
+/// moment. A filter set directly with `Options::set_compaction_filter` would be shared by
+/// concurrent compaction threads.
+struct LogCompactionFilterFactory {
+    watermark: Arc<AtomicU64>,
+}
+
+impl CompactionFilterFactory for LogCompactionFilterFactory {
+    type Filter = LogCompactionFilter;
+
+    fn create(&mut self, _context: CompactionFilterContext) -> Self::Filter {
+        LogCompactionFilter {
+            watermark: self.watermark.load(Ordering::Acquire),
+        }
+    }
+
+    fn name(&self) -> &CStr {
+        c"raft-log-compaction-filter-factory"
+    }
 }
 
 impl LogStore {
@@ -323,6 +626,10 @@ impl LogStore {
         self.db.cf_handle("logs").unwrap()
     }
 
+    fn meta(&self) -> &ColumnFamily {
+        self.db.cf_handle("meta").unwrap()
+    }
+
     fn flush(
         &self,
         subject: ErrorSubject<NodeId>,
@@ -342,16 +649,24 @@ impl LogStore {
             .and_then(|v| serde_json::from_slice(&v).ok()))
     }
 
+    /// Record `log_id` as purged and move the compaction watermark past it in one write.
     fn set_last_purged_(&self, log_id: LogId<u64>) -> StorageResult<()> {
+        let watermark = log_id.index + 1;
+
+        let mut batch = WriteBatch::default();
+        batch.put_cf(
+            self.store(),
+            b"last_purged_log_id",
+            serde_json::to_vec(&log_id).unwrap().as_slice(),
+        );
+        batch.put_cf(self.meta(), COMPACTION_WATERMARK, id_to_bin(watermark));
         self.db
-            .put_cf(
-                self.store(),
-                b"last_purged_log_id",
-                serde_json::to_vec(&log_id).unwrap().as_slice(),
-            )
+            .write(batch)
             .map_err(|e| StorageIOError::write(&e))?;
 
         self.flush(ErrorSubject::Store, ErrorVerb::Write)?;
+        self.compaction_watermark
+            .fetch_max(watermark, Ordering::AcqRel);
         Ok(())
     }
 
@@ -379,6 +694,27 @@ impl LogStore {
             .and_then(|v| serde_json::from_slice(&v).ok()))
     }
 
+    /// Writes `entries` in one batch and syncs the RocksDB WAL, so that entries reported to
+    /// raft as flushed are still there after a crash.
+    fn append_(&self, entries: impl IntoIterator<Item = Entry<TypeConfig>>) -> StorageResult<()> {
+        let mut batch = WriteBatch::default();
+        for entry in entries {
+            let id = id_to_bin(entry.log_id.index);
+            assert_eq!(bin_to_id(&id), entry.log_id.index);
+            batch.put_cf(
+                self.logs(),
+                id,
+                serde_json::to_vec(&entry).map_err(|e| StorageIOError::write_logs(&e))?,
+            );
+        }
+        self.db
+            .write(batch)
+            .map_err(|e| StorageIOError::write_logs(&e))?;
+
+        self.flush(ErrorSubject::Logs, ErrorVerb::Write)?;
+        Ok(())
+    }
+
     fn set_vote_(&self, vote: &Vote<NodeId>) -> StorageResult<()> {
         self.db
             .put_cf(self.store(), b"vote", serde_json::to_vec(vote).unwrap())
@@ -401,35 +737,232 @@ impl LogStore {
     }
 }
 
+/// Lazily decodes log entries in `range`, one RocksDB key at a time.
+///
+/// Entries are stored as JSON, so each item is still deserialized, but nothing is buffered:
+/// a caller that stops early (e.g. `.take(n)`) never reads the rest of the range.
+pub struct LogEntryIter<'a, RB: RangeBounds<u64>> {
+    inner: rocksdb::DBIteratorWithThreadMode<'a, DB>,
+    range: RB,
+    done: bool,
+}
+
+impl<'a, RB: RangeBounds<u64>> Iterator for LogEntryIter<'a, RB> {
+    type Item = StorageResult<Entry<TypeConfig>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let (id, val) = match self.inner.next()? {
+            Ok(kv) => kv,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(StorageIOError::read_logs(&e).into()));
+            }
+        };
+
+        let id = bin_to_id(&id);
+        if !self.range.contains(&id) {
+            self.done = true;
+            return None;
+        }
+
+        let entry: StorageResult<Entry<_>> =
+            serde_json::from_slice(&val).map_err(|e| StorageError::IO {
+                source: StorageIOError::read_logs(&e),
+            });
+        assert_eq!(Ok(id), entry.as_ref().map(|e| e.log_id.index));
+        Some(entry)
+    }
+}
+
+/// Streams the log entries in a range as NDJSON, one JSON entry per line.
+///
+/// Created by [`LogStore::into_reader`]. Only one entry is held in memory at a time: `read`
+/// hands out as much of the current line as fits into `buf`, and loads the next entry once the
+/// current one is used up.
+pub struct LogStoreReader {
+    store: LogStore,
+    /// Index to continue from, `None` once the range is exhausted.
+    next: Option<u64>,
+    end: u64,
+    line: Vec<u8>,
+    /// Bytes of `line` already returned.
+    pos: usize,
+}
+
+impl LogStoreReader {
+    /// Loads the next entry of the range into `line`, returns `false` at the end of the range.
+    fn load_next(&mut self) -> io::Result<bool> {
+        let Some(next) = self.next else {
+            return Ok(false);
+        };
+        let Some(entry) = self
+            .store
+            .iter_range(next..=self.end)
+            .next()
+            .transpose()
+            .map_err(io::Error::other)?
+        else {
+            self.next = None;
+            return Ok(false);
+        };
+
+        self.next = entry.log_id.index.checked_add(1).filter(|i| *i <= self.end);
+        self.line.clear();
+        serde_json::to_writer(&mut self.line, &entry)?;
+        self.line.push(b'\n');
+        self.pos = 0;
+        Ok(true)
+    }
+}
+
+impl io::Read for LogStoreReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.pos == self.line.len() && !self.load_next()? {
+            return Ok(0);
+        }
+
+        let n = buf.len().min(self.line.len() - self.pos);
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl LogStore {
+    /// Reads the entries in `range` as NDJSON, e.g. to pipe the log into `jq` for debugging:
+    ///
+    /// ```text
+    /// {"log_id":{"leader_id":{"term":1,"node_id":0},"index":3},"payload":{"Normal":{"Set":{..}}}}
+    /// ```
+    ///
+    /// Purged entries that have not been compacted away yet are skipped, like in
+    /// [`LogStore::iter_range`].
+    pub fn into_reader(self, range: RangeInclusive<u64>) -> LogStoreReader {
+        let (start, end) = range.into_inner();
+        LogStoreReader {
+            store: self,
+            next: (start <= end).then_some(start),
+            end,
+            line: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Iterate the log entries in `range` without collecting them into a `Vec`.
+    ///
+    /// Purged entries that have not been compacted away yet are skipped.
+    pub fn iter_range<RB: RangeBounds<u64>>(&self, range: RB) -> LogEntryIter<'_, RB> {
+        let start = match range.start_bound() {
+            std::ops::Bound::Included(x) => *x,
+            std::ops::Bound::Excluded(x) => *x + 1,
+            std::ops::Bound::Unbounded => 0,
+        };
+        let start = id_to_bin(start.max(self.compaction_watermark.load(Ordering::Acquire)));
+        let inner = self.db.iterator_cf(
+            self.logs(),
+            rocksdb::IteratorMode::From(&start, Direction::Forward),
+        );
+        LogEntryIter {
+            inner,
+            range,
+            done: false,
+        }
+    }
+
+    /// The first entry proposed in `term`, or `None` if the log has no entry of that term.
+    ///
+    /// Terms never decrease along the log, so this is a binary search over the indexes with one
+    /// point lookup per probe instead of a scan from the start.
+    pub fn first_entry_with_term(&self, term: u64) -> StorageResult<Option<Entry<TypeConfig>>> {
+        let Some((first, last)) = self.index_bounds()? else {
+            return Ok(None);
+        };
+
+        // Smallest index in [first, last] whose term is >= `term`, or `last + 1`.
+        let (mut lo, mut hi) = (first, last + 1);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            // The log is contiguous, a hole can only come from a concurrent truncate/purge:
+            // treat it like a later term.
+            let mid_term = self.entry_at(mid)?.map(|e| e.log_id.leader_id.term);
+            if mid_term.is_some_and(|t| t < term) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        if lo > last {
+            return Ok(None);
+        }
+        Ok(self
+            .entry_at(lo)?
+            .filter(|e| e.log_id.leader_id.term == term))
+    }
+
+    /// The entry at roughly `fraction` of the way through the log, e.g. `0.5` for the middle.
+    ///
+    /// `fraction` is clamped to `[0.0, 1.0]`. Returns `None` for an empty log or a NaN fraction.
+    pub fn entry_at_approximate_index(
+        &self,
+        fraction: f64,
+    ) -> StorageResult<Option<Entry<TypeConfig>>> {
+        if fraction.is_nan() {
+            return Ok(None);
+        }
+        let Some((first, last)) = self.index_bounds()? else {
+            return Ok(None);
+        };
+
+        let len = last - first + 1;
+        let offset = ((fraction.clamp(0.0, 1.0) * len as f64) as u64).min(len - 1);
+        self.entry_at(first + offset)
+    }
+
+    /// Index of the first (not purged) and the last entry in the log.
+    fn index_bounds(&self) -> StorageResult<Option<(u64, u64)>> {
+        let Some(first) = self.iter_range(..).next() else {
+            return Ok(None);
+        };
+        let first = first?.log_id.index;
+
+        let last = self
+            .db
+            .iterator_cf(self.logs(), rocksdb::IteratorMode::End)
+            .next()
+            .transpose()
+            .map_err(|e| StorageIOError::read_logs(&e))?
+            .map_or(first, |(key, _)| bin_to_id(&key));
+
+        Ok(Some((first, last)))
+    }
+
+    fn entry_at(&self, index: u64) -> StorageResult<Option<Entry<TypeConfig>>> {
+        let Some(val) = self
+            .db
+            .get_cf(self.logs(), id_to_bin(index))
+            .map_err(|e| StorageIOError::read_logs(&e))?
+        else {
+            return Ok(None);
+        };
+        let entry = serde_json::from_slice(&val).map_err(|e| StorageIOError::read_logs(&e))?;
+        Ok(Some(entry))
+    }
+}
+
 impl RaftLogReader<TypeConfig> for LogStore {
     async fn try_get_log_entries<RB: RangeBounds<u64> + Clone + Debug + OptionalSend>(
         &mut self,
         range: RB,
     ) -> StorageResult<Vec<Entry<TypeConfig>>> {
-        let start = match range.start_bound() {
-            std::ops::Bound::Included(x) => id_to_bin(*x),
-            std::ops::Bound::Excluded(x) => id_to_bin(*x + 1),
-            std::ops::Bound::Unbounded => id_to_bin(0),
-        };
-        self.db
-            .iterator_cf(
-                self.logs(),
-                rocksdb::IteratorMode::From(&start, Direction::Forward),
-            )
-            .map(|res| {
-                let (id, val) = res.unwrap();
-                let entry: StorageResult<Entry<_>> =
-                    serde_json::from_slice(&val).map_err(|e| StorageError::IO {
-                        source: StorageIOError::read_logs(&e),
-                    });
-                let id = bin_to_id(&id);
-
-                assert_eq!(Ok(id), entry.as_ref().map(|e| e.log_id.index));
-                (id, entry)
-            })
-            .take_while(|(id, _)| range.contains(id))
-            .map(|x| x.1)
-            .collect()
+        self.iter_range(range).collect()
     }
 }
 
@@ -452,10 +985,9 @@ impl RaftLogStorage<TypeConfig> for LogStore {
 
         let last_purged_log_id = self.get_last_purged_()?;
 
-        let last_log_id = match last {
-            None => last_purged_log_id,
-            Some(x) => Some(x),
-        };
+        // Purged entries stay in the `logs` column family until compaction, and after
+        // installing a snapshot the last purged log id can be ahead of every stored entry.
+        let last_log_id = std::cmp::max(last, last_purged_log_id);
         Ok(LogState {
             last_purged_log_id,
             last_log_id,
@@ -490,18 +1022,7 @@ impl RaftLogStorage<TypeConfig> for LogStore {
         I: IntoIterator<Item = Entry<TypeConfig>> + Send,
         I::IntoIter: Send,
     {
-        for entry in entries {
-            let id = id_to_bin(entry.log_id.index);
-            assert_eq!(bin_to_id(&id), entry.log_id.index);
-            self.db
-                .put_cf(
-                    self.logs(),
-                    id,
-                    serde_json::to_vec(&entry).map_err(|e| StorageIOError::write_logs(&e))?,
-                )
-                .map_err(|e| StorageIOError::write_logs(&e))?;
-        }
-
+        self.append_(entries)?;
         callback.log_io_completed(Ok(()));
 
         Ok(())
@@ -522,12 +1043,9 @@ impl RaftLogStorage<TypeConfig> for LogStore {
     async fn purge(&mut self, log_id: LogId<NodeId>) -> Result<(), StorageError<NodeId>> {
         tracing::debug!("delete_log: [0, {:?}]", log_id);
 
-        self.set_last_purged_(log_id)?;
-        let from = id_to_bin(0);
-        let to = id_to_bin(log_id.index + 1);
-        self.db
-            .delete_range_cf(self.logs(), &from, &to)
-            .map_err(|e| StorageIOError::write_logs(&e).into())
+        // Nothing is deleted here: readers skip everything below the watermark, and
+        // `LogCompactionFilter` drops the entries during background compaction.
+        self.set_last_purged_(log_id)
     }
 
     async fn get_log_reader(&mut self) -> Self::LogReader {
@@ -535,19 +1053,691 @@ impl RaftLogStorage<TypeConfig> for LogStore {
     }
 }
 
-pub(crate) async fn new_storage<P: AsRef<Path>>(db_path: P) -> (LogStore, StateMachineStore) {
+pub(crate) async fn new_storage<P: AsRef<Path>>(
+    db_path: P,
+    config: &AppConfig,
+) -> Result<(LogStore, StateMachineStore), StorageError<NodeId>> {
     let mut db_opts = Options::default();
     db_opts.create_missing_column_families(true);
     db_opts.create_if_missing(true);
 
+    let compaction_watermark = Arc::new(AtomicU64::new(0));
+    let mut logs_opts = Options::default();
+    logs_opts.set_compaction_filter_factory(LogCompactionFilterFactory {
+        watermark: compaction_watermark.clone(),
+    });
+
     let store = ColumnFamilyDescriptor::new("store", Options::default());
-    let logs = ColumnFamilyDescriptor::new("logs", Options::default());
+    let logs = ColumnFamilyDescriptor::new("logs", logs_opts);
+    let meta = ColumnFamilyDescriptor::new("meta", Options::default());
 
-    let db = DB::open_cf_descriptors(&db_opts, db_path, vec![store, logs]).unwrap();
+    let db = DB::open_cf_descriptors(&db_opts, &db_path, vec![store, logs, meta])
+        .map_err(|e| StorageIOError::read(&e))?;
     let db = Arc::new(db);
 
-    let log_store = LogStore { db: db.clone() };
-    let sm_store = StateMachineStore::new(db).await.unwrap();
+    let watermark = db
+        .get_cf(db.cf_handle("meta").unwrap(), COMPACTION_WATERMARK)
+        .map_err(|e| StorageIOError::read(&e))?
+        .map_or(0, |v| bin_to_id(&v));
+    compaction_watermark.store(watermark, Ordering::Release);
+
+    let log_store = LogStore {
+        db: db.clone(),
+        compaction_watermark,
+    };
+    let audit = AuditLogger::open(db_path.as_ref().join(AUDIT_LOG_FILE))
+        .await
+        .map_err(|e| StorageIOError::write_state_machine(&e))?;
+    let sm_store =
+        StateMachineStore::new(db, Arc::new(audit), config.snapshot_compression_level).await?;
+
+    Ok((log_store, sm_store))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use openraft::CommittedLeaderId;
+
+    use super::*;
+
+    async fn log_store_with_entries(dir: &tempfile::TempDir, n: u64) -> LogStore {
+        let (log_store, _sm) = new_storage(dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+        for i in 1..=n {
+            let entry = Entry::<TypeConfig> {
+                log_id: LogId::new(CommittedLeaderId::new(1, 0), i),
+                payload: EntryPayload::Blank,
+            };
+            log_store
+                .db
+                .put_cf(
+                    log_store.logs(),
+                    id_to_bin(i),
+                    serde_json::to_vec(&entry).unwrap(),
+                )
+                .unwrap();
+        }
+        log_store
+    }
+
+    #[tokio::test]
+    async fn test_iter_range_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log_store = log_store_with_entries(&dir, 20).await;
+
+        fn ids<RB: RangeBounds<u64>>(it: LogEntryIter<'_, RB>) -> Vec<u64> {
+            it.map(|e| e.unwrap().log_id.index).collect()
+        }
+        assert_eq!(ids(log_store.iter_range(5..8)), vec![5, 6, 7]);
+        assert_eq!(ids(log_store.iter_range(18..)), vec![18, 19, 20]);
+        assert_eq!(ids(log_store.iter_range(..=2)), vec![1, 2]);
+        assert_eq!(ids(log_store.iter_range(30..40)), Vec::<u64>::new());
+
+        let entries = log_store.try_get_log_entries(3..=6).await.unwrap();
+        assert_eq!(
+            entries.iter().map(|e| e.log_id.index).collect::<Vec<_>>(),
+            vec![3, 4, 5, 6]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_into_reader() {
+        use std::io::BufRead;
+        use std::io::BufReader;
+        use std::io::Read;
+
+        let dir = tempfile::tempdir().unwrap();
+        let log_store = log_store_with_entries(&dir, 10).await;
+        let set = Entry::<TypeConfig> {
+            log_id: LogId::new(CommittedLeaderId::new(1, 0), 11),
+            payload: EntryPayload::Normal(Request::Set {
+                key: "foo".to_string(),
+                value: "bar".to_string(),
+            }),
+        };
+        log_store
+            .db
+            .put_cf(
+                log_store.logs(),
+                id_to_bin(11),
+                serde_json::to_vec(&set).unwrap(),
+            )
+            .unwrap();
+
+        let lines: Vec<String> = BufReader::new(log_store.clone().into_reader(8..=20))
+            .lines()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(lines.len(), 4);
+        let entries: Vec<Entry<TypeConfig>> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            entries.iter().map(|e| e.log_id.index).collect::<Vec<_>>(),
+            vec![8, 9, 10, 11]
+        );
+        let value: serde_json::Value = serde_json::from_str(&lines[3]).unwrap();
+        assert_eq!(value["payload"]["Normal"]["Set"]["key"], "foo");
+
+        // A buffer smaller than one line gets the line in pieces; the bytes are the same.
+        let mut reader = log_store.clone().into_reader(1..=11);
+        let mut chunked = Vec::new();
+        let mut buf = [0u8; 7];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            chunked.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        let mut whole = Vec::new();
+        log_store
+            .clone()
+            .into_reader(1..=11)
+            .read_to_end(&mut whole)
+            .unwrap();
+        assert_eq!(chunked, whole);
+        assert_eq!(whole.iter().filter(|b| **b == b'\n').count(), 11);
+
+        // Past the end of the log, and an empty range: nothing to read.
+        let len = |reader| BufReader::new(reader).bytes().count();
+        assert_eq!(len(log_store.clone().into_reader(30..=40)), 0);
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 5..=4;
+        assert_eq!(len(log_store.into_reader(reversed)), 0);
+    }
+
+    #[tokio::test]
+    async fn test_first_entry_with_term() {
+        let dir = tempfile::tempdir().unwrap();
+        let (log_store, _sm) = new_storage(dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+
+        let put = |term: u64, index: u64| {
+            let entry = Entry::<TypeConfig> {
+                log_id: LogId::new(CommittedLeaderId::new(term, 0), index),
+                payload: EntryPayload::Blank,
+            };
+            log_store
+                .db
+                .put_cf(
+                    log_store.logs(),
+                    id_to_bin(index),
+                    serde_json::to_vec(&entry).unwrap(),
+                )
+                .unwrap();
+        };
+
+        // Empty log.
+        assert!(log_store.first_entry_with_term(1).unwrap().is_none());
+        assert!(log_store.entry_at_approximate_index(0.5).unwrap().is_none());
+
+        // Single entry.
+        put(2, 1);
+        let index_of = |e: Option<Entry<TypeConfig>>| e.map(|e| e.log_id.index);
+        assert_eq!(
+            index_of(log_store.first_entry_with_term(2).unwrap()),
+            Some(1)
+        );
+        assert_eq!(index_of(log_store.first_entry_with_term(1).unwrap()), None);
+        assert_eq!(index_of(log_store.first_entry_with_term(3).unwrap()), None);
+        for fraction in [0.0, 0.5, 1.0] {
+            assert_eq!(
+                index_of(log_store.entry_at_approximate_index(fraction).unwrap()),
+                Some(1)
+            );
+        }
 
-    (log_store, sm_store)
+        // Terms 2 (1..=3), 3 (4..=10), 5 (11..=100): term 4 never had a leader that got an
+        // entry in.
+        for index in 2..=100 {
+            put(
+                match index {
+                    1..=3 => 2,
+                    4..=10 => 3,
+                    _ => 5,
+                },
+                index,
+            );
+        }
+        assert_eq!(
+            index_of(log_store.first_entry_with_term(2).unwrap()),
+            Some(1)
+        );
+        assert_eq!(
+            index_of(log_store.first_entry_with_term(3).unwrap()),
+            Some(4)
+        );
+        assert_eq!(index_of(log_store.first_entry_with_term(4).unwrap()), None);
+        assert_eq!(
+            index_of(log_store.first_entry_with_term(5).unwrap()),
+            Some(11)
+        );
+        assert_eq!(index_of(log_store.first_entry_with_term(6).unwrap()), None);
+
+        let approx = |f: f64| index_of(log_store.entry_at_approximate_index(f).unwrap());
+        assert_eq!(approx(0.0), Some(1));
+        assert_eq!(approx(0.5), Some(51));
+        assert_eq!(approx(1.0), Some(100));
+        assert_eq!(approx(-1.0), Some(1));
+        assert_eq!(approx(f64::NAN), None);
+    }
+
+    #[tokio::test]
+    async fn test_appended_entries_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let (log_store, sm) = new_storage(dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+
+        let entries = (1..=50).map(|i| Entry::<TypeConfig> {
+            log_id: LogId::new(CommittedLeaderId::new(1, 0), i),
+            payload: EntryPayload::Normal(Request::Set {
+                key: format!("key-{}", i),
+                value: format!("value-{}", i),
+            }),
+        });
+        log_store.append_(entries).unwrap();
+
+        // No shutdown, the store is just gone: all that is left is what reached the disk.
+        drop((log_store, sm));
+        let (mut log_store, _sm) = new_storage(dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+
+        let state = log_store.get_log_state().await.unwrap();
+        assert_eq!(state.last_log_id.unwrap().index, 50);
+        let entries = log_store.try_get_log_entries(1..=50).await.unwrap();
+        assert_eq!(entries.len(), 50);
+        match &entries[49].payload {
+            EntryPayload::Normal(Request::Set { key, .. }) => assert_eq!(key, "key-50"),
+            payload => panic!("unexpected payload: {:?}", payload),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compaction_drops_purged_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log_store = log_store_with_entries(&dir, 1000).await;
+
+        fn stored_ids(log_store: &LogStore) -> Vec<u64> {
+            log_store
+                .db
+                .iterator_cf(log_store.logs(), rocksdb::IteratorMode::Start)
+                .map(|res| bin_to_id(&res.unwrap().0))
+                .collect()
+        }
+
+        log_store
+            .purge(LogId::new(CommittedLeaderId::new(1, 0), 500))
+            .await
+            .unwrap();
+
+        // Purged entries are still on disk, but no longer visible.
+        assert_eq!(stored_ids(&log_store).len(), 1000);
+        assert_eq!(
+            log_store
+                .iter_range(..)
+                .next()
+                .unwrap()
+                .unwrap()
+                .log_id
+                .index,
+            501
+        );
+        let state = log_store.get_log_state().await.unwrap();
+        assert_eq!(state.last_purged_log_id.unwrap().index, 500);
+        assert_eq!(state.last_log_id.unwrap().index, 1000);
+
+        log_store
+            .db
+            .compact_range_cf(log_store.logs(), None::<&[u8]>, None::<&[u8]>);
+        assert_eq!(stored_ids(&log_store), (501..=1000).collect::<Vec<_>>());
+
+        // The watermark survives a restart.
+        drop(log_store);
+        let (log_store, _sm) = new_storage(dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(log_store.compaction_watermark.load(Ordering::Acquire), 501);
+    }
+
+    #[tokio::test]
+    #[ignore = "benchmark: cargo test --release bench_iter_range -- --ignored --nocapture"]
+    async fn bench_iter_range_vs_try_get_log_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let n = 100_000;
+        let mut log_store = log_store_with_entries(&dir, n).await;
+
+        let start = Instant::now();
+        let all = log_store.try_get_log_entries(1..=n).await.unwrap();
+        let collect_all = start.elapsed();
+        assert_eq!(all.len() as u64, n);
+
+        let start = Instant::now();
+        let count = log_store.iter_range(1..=n).filter(|e| e.is_ok()).count();
+        let iter_all = start.elapsed();
+        assert_eq!(count as u64, n);
+
+        // Where the lazy iterator pays off: the caller only needs a prefix of the range.
+        let start = Instant::now();
+        let head = log_store
+            .try_get_log_entries(1..=n)
+            .await
+            .unwrap()
+            .into_iter()
+            .take(100)
+            .count();
+        let collect_head = start.elapsed();
+
+        let start = Instant::now();
+        let lazy_head = log_store.iter_range(1..=n).take(100).count();
+        let iter_head = start.elapsed();
+        assert_eq!(head, lazy_head);
+
+        println!(
+            "full range:  try_get_log_entries {:?}, iter_range {:?}",
+            collect_all, iter_all
+        );
+        println!(
+            "first 100:   try_get_log_entries {:?}, iter_range {:?}",
+            collect_head, iter_head
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compressed_snapshot_install_on_fresh_node() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_log_store, mut sm) = new_storage(dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+
+        // ~1MB of state
+        {
+            let mut kvs = sm.data.kvs.write().await;
+            for i in 0..16_384 {
+                kvs.insert(format!("key-{:08}", i), format!("value-{:050}", i));
+            }
+        }
+        let expected = sm.data.kvs.read().await.clone();
+        let uncompressed_size = serde_json::to_vec(&expected).unwrap().len();
+        assert!(uncompressed_size >= 1 << 20);
+
+        let snapshot = sm.build_snapshot().await.unwrap();
+        let compressed_size = snapshot.snapshot.get_ref().len();
+        assert!(compressed_size < uncompressed_size);
+
+        let stored = sm.get_current_snapshot_().unwrap().unwrap();
+        let ratio = uncompressed_size as f64 / compressed_size as f64;
+        assert!(stored.compression_ratio > 1.0);
+        assert!((stored.compression_ratio - ratio).abs() < 1e-9);
+
+        let fresh_dir = tempfile::tempdir().unwrap();
+        let (_log_store, mut fresh) = new_storage(fresh_dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+        fresh
+            .install_snapshot(&snapshot.meta, snapshot.snapshot)
+            .await
+            .unwrap();
+        assert_eq!(*fresh.data.kvs.read().await, expected);
+        let installed = fresh.get_current_snapshot_().unwrap().unwrap();
+        assert!((installed.compression_ratio - ratio).abs() < 1e-9);
+
+        // Reopening the node restores the state from the compressed snapshot in rocksdb.
+        drop(fresh);
+        let (_log_store, reopened) = new_storage(fresh_dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(*reopened.data.kvs.read().await, expected);
+    }
+
+    #[tokio::test]
+    async fn test_uncompressed_snapshot_from_older_node() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_log_store, sm) = new_storage(dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+
+        let mut expected = BTreeMap::new();
+        expected.insert("foo".to_string(), "bar".to_string());
+        sm.set_current_snapshot_(StoredSnapshot {
+            meta: SnapshotMeta {
+                last_log_id: Some(LogId::new(CommittedLeaderId::new(1, 0), 3)),
+                last_membership: Default::default(),
+                snapshot_id: "1-0-3-0".to_string(),
+            },
+            data: serde_json::to_vec(&expected).unwrap(),
+            compression_ratio: 0.0,
+        })
+        .unwrap();
+
+        // A node written before snapshot compression still starts from its raw json snapshot.
+        drop(sm);
+        let (_log_store, reopened) = new_storage(dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(*reopened.data.kvs.read().await, expected);
+        assert_eq!(reopened.data.last_applied_log_id.map(|l| l.index), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_operation_latency_histograms() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_log_store, mut sm) = new_storage(dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+
+        let set = |i, value: &str| Request::Set {
+            key: format!("key-{}", i),
+            value: value.to_string(),
+        };
+        let requests = (0..100)
+            .map(|i| set(i, "created"))
+            .chain((0..100).map(|i| set(i, "updated")))
+            .chain((0..100).map(|i| Request::Delete {
+                key: format!("key-{}", i),
+            }));
+        let entries: Vec<typ::Entry> = requests
+            .zip(1..)
+            .map(|(req, index)| Entry {
+                log_id: LogId::new(CommittedLeaderId::new(1, 0), index),
+                payload: EntryPayload::Normal(req),
+            })
+            .collect();
+
+        let replies = sm.apply(entries).await.unwrap();
+        // A delete answers with the value it removed.
+        assert_eq!(replies[250].value.as_deref(), Some("updated"));
+        assert!(sm.data.kvs.read().await.is_empty());
+
+        let snapshot = sm.metrics.snapshot();
+        assert_eq!(snapshot.operations.map(|h| h.count), [100, 100, 100]);
+        assert_eq!(snapshot.total_writes, 300);
+    }
+
+    #[test]
+    fn test_request_from_str() {
+        let set = |key: &str, value: &str| Request::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let parse = |s: &str| s.parse::<Request>().map(|r| r.to_string());
+
+        assert_eq!(parse("set foo bar"), Ok(set("foo", "bar").to_string()));
+        assert_eq!(
+            parse("  SET   greeting   hello world  "),
+            Ok(set("greeting", "hello world").to_string())
+        );
+
+        assert_eq!(parse(""), Err(ParseError::Empty));
+        assert_eq!(parse("   "), Err(ParseError::Empty));
+        assert_eq!(
+            parse("delete foo"),
+            Err(ParseError::UnknownCommand("delete".to_string()))
+        );
+        assert_eq!(
+            parse("set"),
+            Err(ParseError::MissingField {
+                command: "set",
+                field: "key"
+            })
+        );
+        assert_eq!(
+            parse("set foo  "),
+            Err(ParseError::MissingField {
+                command: "set",
+                field: "value"
+            })
+        );
+        assert_eq!(
+            ParseError::MissingField {
+                command: "set",
+                field: "value"
+            }
+            .to_string(),
+            "`set` is missing field `value`"
+        );
+    }
+}
+
+#[cfg(test)]
+mod panic_tests {
+    use openraft::CommittedLeaderId;
+
+    use super::*;
+
+    fn normal(index: u64, req: Request) -> typ::Entry {
+        Entry {
+            log_id: LogId::new(CommittedLeaderId::new(1, 0), index),
+            payload: EntryPayload::Normal(req),
+        }
+    }
+
+    fn set(index: u64) -> typ::Entry {
+        normal(
+            index,
+            Request::Set {
+                key: format!("key-{}", index),
+                value: format!("value-{}", index),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_panicking_request_is_isolated() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_log_store, mut sm) = new_storage(dir.path(), &AppConfig::default())
+            .await
+            .unwrap();
+
+        let replies = sm
+            .apply(vec![set(1), normal(2, Request::Panic), set(3)])
+            .await
+            .unwrap();
+
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0].value.as_deref(), Some("value-1"));
+        assert_eq!(replies[0].error, None);
+        assert_eq!(replies[1].value, None);
+        assert_eq!(
+            replies[1].error.as_deref(),
+            Some("apply panicked: injected panic")
+        );
+        // The entries after the panicking one are still applied.
+        assert_eq!(replies[2].value.as_deref(), Some("value-3"));
+
+        // The state machine keeps working, and the panicking entry counts as applied.
+        let replies = sm.apply(vec![set(4)]).await.unwrap();
+        assert_eq!(replies[0].value.as_deref(), Some("value-4"));
+
+        let (last_applied, _) = sm.applied_state().await.unwrap();
+        assert_eq!(last_applied.map(|log_id| log_id.index), Some(4));
+        let kvs = sm.data.kvs.read().await;
+        assert_eq!(
+            kvs.keys().cloned().collect::<Vec<_>>(),
+            vec!["key-1", "key-3", "key-4"]
+        );
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let payload = panic::catch_unwind(|| panic!("static")).unwrap_err();
+        assert_eq!(panic_message(&*payload), "static");
+
+        let n = 7;
+        let payload = panic::catch_unwind(|| panic!("formatted {}", n)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "formatted 7");
+
+        let payload = panic::catch_unwind(|| panic::panic_any(42)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "unknown panic");
+    }
+}
+
+#[cfg(test)]
+mod apply_proptests {
+    use openraft::CommittedLeaderId;
+    use proptest::prelude::*;
+    use proptest::test_runner::TestRunner;
+
+    use super::*;
+
+    /// A write to one of 10 keys, so that the operations of a sequence keep hitting each other.
+    fn request() -> impl Strategy<Value = Request> {
+        prop_oneof![
+            (1..=10u8, "[a-z]{0,8}").prop_map(|(id, value)| Request::Set {
+                key: id.to_string(),
+                value,
+            }),
+            (1..=10u8).prop_map(|id| Request::Delete {
+                key: id.to_string()
+            }),
+        ]
+    }
+
+    /// 1 to 100 requests, split into the batches passed to one `apply` call each.
+    fn batches() -> impl Strategy<Value = Vec<Vec<Request>>> {
+        prop::collection::vec(prop::collection::vec(request(), 1..=10), 1..=10)
+    }
+
+    /// Applies `batches` and checks the state machine against a plain map after every batch.
+    async fn check_invariants(
+        sm: &mut StateMachineStore,
+        batches: Vec<Vec<Request>>,
+    ) -> Result<(), TestCaseError> {
+        let mut model = BTreeMap::new();
+        let mut index = 0;
+        let mut last_applied = None;
+
+        for batch in batches {
+            let mut expected = Vec::with_capacity(batch.len());
+            let mut entries = Vec::with_capacity(batch.len());
+            for req in batch {
+                expected.push(match &req {
+                    Request::Set { key, value } => {
+                        model.insert(key.clone(), value.clone());
+                        Some(value.clone())
+                    }
+                    // No key exists after its delete, which answers with the previous value.
+                    Request::Delete { key } => model.remove(key),
+                    Request::Panic => unreachable!(),
+                });
+                index += 1;
+                entries.push(Entry {
+                    log_id: LogId::new(CommittedLeaderId::new(1, 0), index),
+                    payload: EntryPayload::Normal(req),
+                });
+            }
+
+            let replies = sm.apply(entries).await.unwrap();
+            let values: Vec<_> = replies.into_iter().map(|r| r.value).collect();
+            prop_assert_eq!(values, expected);
+
+            // Every key holds the value of its last `Set`, deleted keys are gone.
+            prop_assert_eq!(&*sm.data.kvs.read().await, &model);
+
+            let (applied, _) = sm.applied_state().await.unwrap();
+            prop_assert!(
+                applied > last_applied,
+                "{:?} after {:?}",
+                applied,
+                last_applied
+            );
+            prop_assert_eq!(applied.map(|log_id| log_id.index), Some(index));
+            last_applied = applied;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn prop_apply_keeps_invariants() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let (_log_store, sm) = rt
+            .block_on(new_storage(dir.path(), &AppConfig::default()))
+            .unwrap();
+
+        let mut runner = TestRunner::new(ProptestConfig::with_cases(1000));
+        runner
+            .run(&batches(), |batches| {
+                // The state lives in memory and no snapshot is ever built, so a new store on
+                // the same db starts empty: a fresh state machine without opening rocksdb
+                // again for every case.
+                let mut sm = rt
+                    .block_on(StateMachineStore::new(
+                        sm.db.clone(),
+                        sm.audit.clone(),
+                        DEFAULT_SNAPSHOT_COMPRESSION_LEVEL,
+                    ))
+                    .unwrap();
+                rt.block_on(check_invariants(&mut sm, batches))
+            })
+            .unwrap();
+    }
 }
@@ -0,0 +1,2 @@
+pub mod log_dump;
+pub mod watcher;
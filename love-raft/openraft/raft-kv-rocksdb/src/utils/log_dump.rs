@@ -0,0 +1,102 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fmt::Write;
+
+use openraft::EntryPayload;
+
+use crate::typ::Entry;
+
+/// Formats a raft log entry on one line for debugging, e.g.:
+///
+/// ```text
+/// [Term 2, Index 42] Set{key=foo, value=bar}
+/// [Term 2, Index 43] <Blank>
+/// [Term 2, Index 44] Membership({1, 2, 3})
+/// ```
+///
+/// `Entry` and `Display` are both foreign to this crate, so the impl lives on this wrapper.
+pub struct EntryDisplay<'a>(pub &'a Entry);
+
+impl fmt::Display for EntryDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let log_id = &self.0.log_id;
+        write!(
+            f,
+            "[Term {}, Index {}] ",
+            log_id.leader_id.term, log_id.index
+        )?;
+
+        match &self.0.payload {
+            EntryPayload::Blank => write!(f, "<Blank>"),
+            EntryPayload::Normal(req) => write!(f, "{}", req),
+            EntryPayload::Membership(mem) => {
+                let voters: BTreeSet<_> = mem.voter_ids().collect();
+                let voters: Vec<_> = voters.iter().map(|id| id.to_string()).collect();
+                write!(f, "Membership({{{}}})", voters.join(", "))
+            }
+        }
+    }
+}
+
+/// Formats entries as a numbered list, one entry per line.
+pub fn raft_log_dump(entries: &[Entry]) -> String {
+    let mut out = String::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let _ = writeln!(out, "{:>4}. {}", i + 1, EntryDisplay(entry));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use maplit::btreeset;
+    use openraft::CommittedLeaderId;
+    use openraft::LogId;
+    use openraft::Membership;
+
+    use super::*;
+    use crate::store::Request;
+    use crate::Node;
+
+    fn entry(index: u64, payload: EntryPayload<crate::TypeConfig>) -> Entry {
+        Entry {
+            log_id: LogId::new(CommittedLeaderId::new(2, 1), index),
+            payload,
+        }
+    }
+
+    #[test]
+    fn test_entry_display() {
+        let set = entry(
+            42,
+            EntryPayload::Normal(Request::Set {
+                key: "foo".to_string(),
+                value: "bar".to_string(),
+            }),
+        );
+        assert_eq!(
+            EntryDisplay(&set).to_string(),
+            "[Term 2, Index 42] Set{key=foo, value=bar}"
+        );
+
+        let blank = entry(43, EntryPayload::Blank);
+        assert_eq!(
+            EntryDisplay(&blank).to_string(),
+            "[Term 2, Index 43] <Blank>"
+        );
+
+        let mem = Membership::<u64, Node>::new(vec![btreeset! {3, 1, 2}], BTreeMap::new());
+        let membership = entry(44, EntryPayload::Membership(mem));
+        assert_eq!(
+            EntryDisplay(&membership).to_string(),
+            "[Term 2, Index 44] Membership({1, 2, 3})"
+        );
+
+        assert_eq!(
+            raft_log_dump(&[blank, set]),
+            "   1. [Term 2, Index 43] <Blank>\n   2. [Term 2, Index 42] Set{key=foo, value=bar}\n"
+        );
+    }
+}
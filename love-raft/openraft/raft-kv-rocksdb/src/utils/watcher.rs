@@ -0,0 +1,143 @@
+use tokio::sync::watch;
+
+/// The receiving half of a [`Watcher`].
+pub type WatchReceiver<T> = watch::Receiver<T>;
+
+/// A value that other tasks can observe and react to, built on [`tokio::sync::watch`].
+///
+/// Unlike a bare `watch::Sender`, updates that do not change the value are dropped, so
+/// subscribers are only woken up on real changes. This matters when the source is polled or
+/// pushed frequently, e.g. raft metrics, which change on every heartbeat even if the leader,
+/// the applied log id and the membership stay the same.
+#[derive(Debug)]
+pub struct Watcher<T> {
+    tx: watch::Sender<T>,
+}
+
+impl<T> Watcher<T>
+where
+    T: Clone + PartialEq,
+{
+    pub fn new(init: T) -> Self {
+        Self {
+            tx: watch::Sender::new(init),
+        }
+    }
+
+    /// Returns a clone of the current value.
+    pub fn get(&self) -> T {
+        self.tx.borrow().clone()
+    }
+
+    /// Store `value` and notify subscribers, unless it equals the current value.
+    ///
+    /// Returns `true` if the value changed.
+    pub fn send_if_changed(&self, value: T) -> bool {
+        self.tx.send_if_modified(|current| {
+            if *current == value {
+                false
+            } else {
+                *current = value;
+                true
+            }
+        })
+    }
+
+    /// Subscribe to changes. The current value is considered seen by the new receiver.
+    pub fn subscribe(&self) -> WatchReceiver<T> {
+        self.tx.subscribe()
+    }
+
+    /// Wait until the value satisfies `predicate` and return it.
+    ///
+    /// Returns immediately if the current value already matches. Otherwise the task sleeps until
+    /// the next change, and the predicate is only re-evaluated on changes.
+    pub async fn wait_for<P>(&self, predicate: P) -> T
+    where
+        P: Fn(&T) -> bool,
+    {
+        let mut rx = self.subscribe();
+        let value = rx
+            .wait_for(|v| predicate(v))
+            .await
+            // `self` owns the sender, it can not be dropped while we are borrowing it.
+            .expect("watch sender is alive while borrowed");
+        value.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_if_changed() {
+        let w = Watcher::new(None::<u64>);
+        let mut rx = w.subscribe();
+
+        assert!(!w.send_if_changed(None));
+        assert!(!rx.has_changed().unwrap());
+
+        assert!(w.send_if_changed(Some(1)));
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), Some(1));
+
+        // Same value again: receivers are not woken up.
+        assert!(!w.send_if_changed(Some(1)));
+        assert!(!rx.has_changed().unwrap());
+        assert_eq!(w.get(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let w = Watcher::new(0u64);
+        let mut rx = w.subscribe();
+
+        let seen = tokio::spawn(async move {
+            let mut seen = vec![];
+            while rx.changed().await.is_ok() {
+                let v = *rx.borrow_and_update();
+                seen.push(v);
+                if v == 3 {
+                    break;
+                }
+            }
+            seen
+        });
+
+        for v in [1, 1, 2, 2, 3] {
+            w.send_if_changed(v);
+            // Give the receiver a chance to observe every distinct value.
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(seen.await.unwrap(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for() {
+        let w = Arc::new(Watcher::new(0u64));
+
+        // Already satisfied: returns without waiting.
+        assert_eq!(w.wait_for(|v| *v == 0).await, 0);
+
+        let waiter = {
+            let w = w.clone();
+            tokio::spawn(async move { w.wait_for(|v| *v >= 5).await })
+        };
+
+        for v in 1..=10 {
+            w.send_if_changed(v);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let got = tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(got >= 5, "got {}", got);
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use openraft::LogId;
+use openraft::RaftMetrics;
+use raft_kv_rocksdb::client::ExampleClient;
+use raft_kv_rocksdb::Node;
+use raft_kv_rocksdb::NodeId;
+use tokio::sync::Barrier;
+use tokio::task::JoinSet;
+
+/// How long the whole cluster may take to reach a barrier before the test fails.
+pub const BARRIER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often each node's metrics are fetched while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Waits until every node reports metrics satisfying a condition, instead of sleeping for a
+/// fixed time and hoping the cluster got there.
+///
+/// The nodes run inside [`raft_kv_rocksdb::start_example_raft_node`] and are only reachable
+/// over HTTP, so each node gets a task polling its `/cluster/metrics`. A task reaches the
+/// [`Barrier`] once its node is ready; the test is the last party and waits at most
+/// [`BARRIER_TIMEOUT`].
+pub struct ClusterBarrier {
+    nodes: Vec<(NodeId, String)>,
+}
+
+impl ClusterBarrier {
+    /// `nodes` are `(node_id, http_addr)` pairs.
+    pub fn new(nodes: impl IntoIterator<Item = (NodeId, String)>) -> Self {
+        Self {
+            nodes: nodes.into_iter().collect(),
+        }
+    }
+
+    /// Every node answers HTTP requests.
+    pub async fn wait_for_startup(&self) -> Result<(), String> {
+        self.wait_until("HTTP servers to start", |_| true).await
+    }
+
+    /// Every node knows a leader.
+    pub async fn wait_for_leader(&self) -> Result<(), String> {
+        self.wait_until("leader election", |m| m.current_leader.is_some())
+            .await
+    }
+
+    /// Every node has applied `log_id`, e.g. the log id returned by a write.
+    pub async fn wait_for_applied(&self, log_id: LogId<NodeId>) -> Result<(), String> {
+        self.wait_until(&format!("log {} to be applied", log_id), move |m| {
+            m.last_applied >= Some(log_id)
+        })
+        .await
+    }
+
+    pub async fn wait_until<F>(&self, what: &str, ready: F) -> Result<(), String>
+    where
+        F: Fn(&RaftMetrics<NodeId, Node>) -> bool + Send + Sync + 'static,
+    {
+        let ready = Arc::new(ready);
+        // One party per node, plus the test itself.
+        let barrier = Arc::new(Barrier::new(self.nodes.len() + 1));
+        let pending: Arc<Mutex<BTreeSet<NodeId>>> =
+            Arc::new(Mutex::new(self.nodes.iter().map(|(id, _)| *id).collect()));
+
+        // Dropping the set aborts the pollers of nodes that never got ready.
+        let mut pollers = JoinSet::new();
+        for (id, addr) in self.nodes.clone() {
+            let ready = ready.clone();
+            let barrier = barrier.clone();
+            let pending = pending.clone();
+            pollers.spawn(async move {
+                let client = ExampleClient::new(id, addr);
+                loop {
+                    // Errors are expected while the server is still starting up.
+                    if let Ok(m) = client.metrics().await {
+                        if ready(&m) {
+                            break;
+                        }
+                    }
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+                pending.lock().unwrap().remove(&id);
+                barrier.wait().await;
+            });
+        }
+
+        match tokio::time::timeout(BARRIER_TIMEOUT, barrier.wait()).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!(
+                "timed out after {:?} waiting for {}, nodes not ready: {:?}",
+                BARRIER_TIMEOUT,
+                what,
+                pending.lock().unwrap()
+            )),
+        }
+    }
+}
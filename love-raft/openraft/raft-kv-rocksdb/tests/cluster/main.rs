@@ -1,3 +1,8 @@
 #![allow(clippy::uninlined_format_args)]
 
+mod cluster_barrier;
 mod test_cluster;
+mod test_compaction;
+mod test_fault_injection;
+mod test_lease_read;
+mod test_node_manager;
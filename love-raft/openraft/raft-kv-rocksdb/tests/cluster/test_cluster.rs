@@ -3,7 +3,6 @@ use std::collections::BTreeMap;
 #[allow(deprecated)]
 use std::panic::PanicInfo;
 use std::thread;
-use std::time::Duration;
 
 use maplit::btreemap;
 use maplit::btreeset;
@@ -11,9 +10,12 @@ use raft_kv_rocksdb::client::ExampleClient;
 use raft_kv_rocksdb::start_example_raft_node;
 use raft_kv_rocksdb::store::Request;
 use raft_kv_rocksdb::Node;
+use raft_kv_rocksdb::NodeId;
 use tokio::runtime::Handle;
 use tracing_subscriber::EnvFilter;
 
+use crate::cluster_barrier::ClusterBarrier;
+
 #[allow(deprecated)]
 pub fn log_panic(panic: &PanicInfo) {
     let backtrace = { format!("{:?}", Backtrace::force_capture()) };
@@ -92,6 +94,7 @@ async fn test_cluster() -> Result<(), Box<dyn std::error::Error>> {
             d1.path(),
             get_addr(1),
             get_rpc_addr(1),
+            None,
         ));
         println!("x: {:?}", x);
     });
@@ -103,6 +106,7 @@ async fn test_cluster() -> Result<(), Box<dyn std::error::Error>> {
             d2.path(),
             get_addr(2),
             get_rpc_addr(2),
+            None,
         ));
         println!("x: {:?}", x);
     });
@@ -113,12 +117,14 @@ async fn test_cluster() -> Result<(), Box<dyn std::error::Error>> {
             d3.path(),
             get_addr(3),
             get_rpc_addr(3),
+            None,
         ));
         println!("x: {:?}", x);
     });
 
     // Wait for server to start up.
-    tokio::time::sleep(Duration::from_millis(3_000)).await;
+    let barrier = ClusterBarrier::new((1..=3).map(|id| (id as NodeId, get_addr(id))));
+    barrier.wait_for_startup().await?;
 
     // --- Create a client to the first node, as a control handle to the cluster.
 
@@ -173,6 +179,9 @@ async fn test_cluster() -> Result<(), Box<dyn std::error::Error>> {
     println!("=== change-membership to 1,2,3");
     let _x = leader.change_membership(&btreeset! {1,2,3}).await?;
 
+    // --- Every member knows who the leader is.
+    barrier.wait_for_leader().await?;
+
     // --- After change-membership, some cluster state will be seen in the metrics.
     //
     // ```text
@@ -198,16 +207,16 @@ async fn test_cluster() -> Result<(), Box<dyn std::error::Error>> {
     // --- Try to write some application data through the leader.
 
     println!("=== write `foo=bar`");
-    let _x = leader
+    let x = leader
         .write(&Request::Set {
             key: "foo".to_string(),
             value: "bar".to_string(),
         })
         .await?;
 
-    // --- Wait for a while to let the replication get done.
+    // --- Wait for the replication to get done.
 
-    tokio::time::sleep(Duration::from_millis(500)).await;
+    barrier.wait_for_applied(x.log_id).await?;
 
     // --- Read it on every node.
 
@@ -215,6 +224,29 @@ async fn test_cluster() -> Result<(), Box<dyn std::error::Error>> {
     let x = leader.read(&("foo".to_string())).await?;
     assert_eq!("bar", x);
 
+    // --- Every node is healthy, but only the leader is ready.
+
+    println!("=== health of every node");
+    let http = reqwest::Client::new();
+    for id in 1..=3 {
+        let health = http
+            .get(format!("http://{}/health", get_addr(id)))
+            .send()
+            .await?;
+        assert_eq!(health.status(), reqwest::StatusCode::OK);
+
+        let ready = http
+            .get(format!("http://{}/health/ready", get_addr(id)))
+            .send()
+            .await?;
+        let expected = if id == 1 {
+            reqwest::StatusCode::OK
+        } else {
+            reqwest::StatusCode::SERVICE_UNAVAILABLE
+        };
+        assert_eq!(ready.status(), expected, "node {}", id);
+    }
+
     println!("=== read `foo` on node 2");
     let client2 = ExampleClient::new(2, get_addr(2));
     let x = client2.read(&("foo".to_string())).await?;
@@ -228,14 +260,14 @@ async fn test_cluster() -> Result<(), Box<dyn std::error::Error>> {
     // --- A write to non-leader will be automatically forwarded to a known leader
 
     println!("=== read `foo` on node 2");
-    let _x = client2
+    let x = client2
         .write(&Request::Set {
             key: "foo".to_string(),
             value: "wow".to_string(),
         })
         .await?;
 
-    tokio::time::sleep(Duration::from_millis(500)).await;
+    barrier.wait_for_applied(x.log_id).await?;
 
     // --- Read it on every node.
 
@@ -0,0 +1,95 @@
+use std::thread;
+
+use raft_kv_rocksdb::client::ExampleClient;
+use raft_kv_rocksdb::network::Network;
+use raft_kv_rocksdb::start_raft_node_with_network;
+use raft_kv_rocksdb::store::Request;
+use raft_kv_rocksdb::AppConfig;
+use raft_kv_rocksdb::NodeId;
+use tokio::runtime::Handle;
+
+use crate::cluster_barrier::ClusterBarrier;
+
+const COMPACTION_THRESHOLD: u64 = 100;
+
+fn get_addr(node_id: NodeId) -> String {
+    format!("127.0.0.1:3130{}", node_id)
+}
+
+fn get_rpc_addr(node_id: NodeId) -> String {
+    format!("127.0.0.1:3230{}", node_id)
+}
+
+/// Write far more entries than the compaction threshold and check that the log is purged
+/// behind the snapshots while every value stays readable.
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn test_log_compaction() -> Result<(), Box<dyn std::error::Error>> {
+    let _ = tracing_subscriber::fmt()
+        .with_target(true)
+        .with_thread_ids(true)
+        .with_level(true)
+        .with_ansi(false)
+        .try_init();
+
+    let dir = tempfile::TempDir::new()?;
+    let handle = Handle::current();
+    thread::spawn(move || {
+        let x = handle.block_on(start_raft_node_with_network(
+            1,
+            dir.path(),
+            get_addr(1),
+            get_rpc_addr(1),
+            Network::default(),
+            None,
+            AppConfig {
+                compaction_threshold: COMPACTION_THRESHOLD,
+                ..Default::default()
+            },
+        ));
+        println!("x: {:?}", x);
+    });
+
+    let barrier = ClusterBarrier::new([(1, get_addr(1))]);
+    barrier.wait_for_startup().await?;
+
+    let leader = ExampleClient::new(1, get_addr(1));
+    leader.init().await?;
+    barrier.wait_for_leader().await?;
+
+    let mut last_log_id = None;
+    for i in 0..2000 {
+        let x = leader
+            .write(&Request::Set {
+                key: format!("key-{}", i),
+                value: format!("value-{}", i),
+            })
+            .await?;
+        last_log_id = Some(x.log_id);
+    }
+    let last_index = last_log_id.unwrap().index;
+
+    // A snapshot is built every threshold entries, so the last one is less than a threshold
+    // behind, and the logs it covers are purged except the last threshold of them.
+    barrier
+        .wait_until("a snapshot of the last writes", move |m| {
+            m.snapshot.map(|s| s.index + COMPACTION_THRESHOLD) > Some(last_index)
+                && m.purged.map(|p| p.index + COMPACTION_THRESHOLD) >= m.snapshot.map(|s| s.index)
+        })
+        .await?;
+
+    let metrics = leader.metrics().await?;
+    let purged = metrics.purged.unwrap();
+    assert!(
+        last_index - purged.index < 2 * COMPACTION_THRESHOLD,
+        "last log: {}, purged: {}",
+        last_index,
+        purged
+    );
+
+    for i in [0, 999, 1999] {
+        let x = leader.read(&format!("key-{}", i)).await?;
+        assert_eq!(format!("value-{}", i), x);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::thread;
+
+use maplit::btreeset;
+use raft_kv_rocksdb::client::ExampleClient;
+use raft_kv_rocksdb::network::fault_injector::FaultConfig;
+use raft_kv_rocksdb::network::fault_injector::FaultInjectingNetwork;
+use raft_kv_rocksdb::start_raft_node_with_network;
+use raft_kv_rocksdb::store::Request;
+use raft_kv_rocksdb::NodeId;
+use tokio::runtime::Handle;
+
+use crate::cluster_barrier::ClusterBarrier;
+
+fn get_addr(node_id: NodeId) -> String {
+    format!("127.0.0.1:3110{}", node_id)
+}
+
+fn get_rpc_addr(node_id: NodeId) -> String {
+    format!("127.0.0.1:3210{}", node_id)
+}
+
+/// Cut `isolated` off from every other node, in both directions.
+pub fn isolate(faults: &BTreeMap<NodeId, FaultConfig>, isolated: NodeId) {
+    for (id, f) in faults {
+        if *id == isolated {
+            faults
+                .keys()
+                .filter(|x| **x != isolated)
+                .for_each(|x| f.partition(*x));
+        } else {
+            f.partition(isolated);
+        }
+    }
+}
+
+/// Partition node 3 away, write through the majority, heal the partition and check that node 3
+/// catches up.
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn test_partition_and_heal() -> Result<(), Box<dyn std::error::Error>> {
+    let _ = tracing_subscriber::fmt()
+        .with_target(true)
+        .with_thread_ids(true)
+        .with_level(true)
+        .with_ansi(false)
+        .try_init();
+
+    let faults: BTreeMap<NodeId, FaultConfig> =
+        (1..=3).map(|id| (id, FaultConfig::default())).collect();
+
+    // --- Start 3 raft node in 3 threads, each with its own fault config.
+    let handle = Handle::current();
+    for id in 1..=3 {
+        let dir = tempfile::TempDir::new()?;
+        let network = FaultInjectingNetwork {
+            faults: faults[&id].clone(),
+            liveness: Default::default(),
+        };
+        let handle = handle.clone();
+        thread::spawn(move || {
+            let x = handle.block_on(start_raft_node_with_network(
+                id,
+                dir.path(),
+                get_addr(id),
+                get_rpc_addr(id),
+                network,
+                None,
+                Default::default(),
+            ));
+            println!("x: {:?}", x);
+        });
+    }
+
+    // Wait for server to start up.
+    let barrier = ClusterBarrier::new((1..=3).map(|id| (id, get_addr(id))));
+    barrier.wait_for_startup().await?;
+
+    let leader = ExampleClient::new(1, get_addr(1));
+    leader.init().await?;
+    leader
+        .add_learner((2, get_addr(2), get_rpc_addr(2)))
+        .await?;
+    leader
+        .add_learner((3, get_addr(3), get_rpc_addr(3)))
+        .await?;
+    leader.change_membership(&btreeset! {1,2,3}).await?;
+    barrier.wait_for_leader().await?;
+
+    // --- Partition node 3. Nodes 1 and 2 are still a majority and keep committing.
+
+    println!("=== partition node 3");
+    isolate(&faults, 3);
+
+    let mut last_log_id = None;
+    for i in 0..10 {
+        let x = leader
+            .write(&Request::Set {
+                key: format!("key-{}", i),
+                value: format!("value-{}", i),
+            })
+            .await?;
+        last_log_id = Some(x.log_id);
+    }
+
+    let client3 = ExampleClient::new(3, get_addr(3));
+    assert_eq!("", client3.read(&"key-0".to_string()).await?);
+
+    // --- Heal and wait for node 3 to receive the entries it missed.
+
+    println!("=== heal partition");
+    faults.values().for_each(|f| f.heal());
+
+    ClusterBarrier::new([(3, get_addr(3))])
+        .wait_for_applied(last_log_id.unwrap())
+        .await?;
+
+    for i in 0..10 {
+        let x = client3.read(&format!("key-{}", i)).await?;
+        assert_eq!(format!("value-{}", i), x);
+    }
+
+    Ok(())
+}
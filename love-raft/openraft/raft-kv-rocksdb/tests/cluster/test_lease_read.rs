@@ -0,0 +1,117 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use maplit::btreeset;
+use raft_kv_rocksdb::client::ExampleClient;
+use raft_kv_rocksdb::network::Network;
+use raft_kv_rocksdb::start_raft_node_with_network;
+use raft_kv_rocksdb::store::Request;
+use raft_kv_rocksdb::AppConfig;
+use raft_kv_rocksdb::NodeId;
+use tokio::runtime::Handle;
+
+use crate::cluster_barrier::ClusterBarrier;
+
+const CONCURRENT_READS: usize = 1000;
+
+fn get_addr(cluster: u16, node_id: NodeId) -> String {
+    format!("127.0.0.1:31{}0{}", cluster, node_id)
+}
+
+fn get_rpc_addr(cluster: u16, node_id: NodeId) -> String {
+    format!("127.0.0.1:32{}0{}", cluster, node_id)
+}
+
+/// Start 3 nodes on the ports of `cluster` and return a client of the leader, node 1.
+async fn start_cluster(
+    cluster: u16,
+    app_config: AppConfig,
+) -> Result<ExampleClient, Box<dyn std::error::Error>> {
+    let handle = Handle::current();
+    for id in 1..=3 {
+        let dir = tempfile::TempDir::new()?;
+        let handle = handle.clone();
+        let app_config = app_config.clone();
+        thread::spawn(move || {
+            let x = handle.block_on(start_raft_node_with_network(
+                id,
+                dir.path(),
+                get_addr(cluster, id),
+                get_rpc_addr(cluster, id),
+                Network::default(),
+                None,
+                app_config,
+            ));
+            println!("x: {:?}", x);
+        });
+    }
+
+    let barrier = ClusterBarrier::new((1..=3).map(|id| (id, get_addr(cluster, id))));
+    barrier.wait_for_startup().await?;
+
+    let leader = ExampleClient::new(1, get_addr(cluster, 1));
+    leader.init().await?;
+    for id in 2..=3 {
+        leader
+            .add_learner((id, get_addr(cluster, id), get_rpc_addr(cluster, id)))
+            .await?;
+    }
+    leader.change_membership(&btreeset! {1,2,3}).await?;
+    barrier.wait_for_leader().await?;
+    Ok(leader)
+}
+
+/// Latency of 1000 concurrent consistent reads with the leader lease and with a quorum
+/// round-trip per read.
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+#[ignore = "benchmark: cargo test --release --test cluster bench_lease_read -- --ignored --nocapture"]
+async fn bench_lease_read() -> Result<(), Box<dyn std::error::Error>> {
+    for (cluster, read_lease) in [(4, true), (5, false)] {
+        let app_config = AppConfig {
+            read_lease,
+            ..Default::default()
+        };
+        let leader = Arc::new(start_cluster(cluster, app_config).await?);
+        leader
+            .write(&Request::Set {
+                key: "foo".to_string(),
+                value: "bar".to_string(),
+            })
+            .await?;
+
+        let started = Instant::now();
+        let reads: Vec<_> = (0..CONCURRENT_READS)
+            .map(|_| {
+                let leader = leader.clone();
+                tokio::spawn(async move {
+                    let started = Instant::now();
+                    let value = leader.consistent_read(&"foo".to_string()).await;
+                    (started.elapsed(), value)
+                })
+            })
+            .collect();
+
+        let mut latencies = Vec::with_capacity(CONCURRENT_READS);
+        for read in reads {
+            let (latency, value) = read.await?;
+            assert_eq!(value?, "bar");
+            latencies.push(latency);
+        }
+        let elapsed = started.elapsed();
+
+        latencies.sort();
+        let mean = latencies.iter().sum::<Duration>() / latencies.len() as u32;
+        println!(
+            "read_lease={}: {:.0} reads/s, mean {:?}, p50 {:?}, p99 {:?}",
+            read_lease,
+            CONCURRENT_READS as f64 / elapsed.as_secs_f64(),
+            mean,
+            latencies[latencies.len() / 2],
+            latencies[latencies.len() * 99 / 100],
+        );
+    }
+
+    Ok(())
+}
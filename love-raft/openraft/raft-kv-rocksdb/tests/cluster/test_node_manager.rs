@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use maplit::btreeset;
+use raft_kv_rocksdb::client::ExampleClient;
+use raft_kv_rocksdb::network::fault_injector::FaultConfig;
+use raft_kv_rocksdb::network::fault_injector::FaultInjectingNetwork;
+use raft_kv_rocksdb::start_raft_node_with_network;
+use raft_kv_rocksdb::store::Request;
+use raft_kv_rocksdb::NodeId;
+use tokio::runtime::Handle;
+
+use crate::cluster_barrier::ClusterBarrier;
+use crate::test_fault_injection::isolate;
+
+const NODE_TIMEOUT: Duration = Duration::from_secs(3);
+
+fn get_addr(node_id: NodeId) -> String {
+    format!("127.0.0.1:3120{}", node_id)
+}
+
+fn get_rpc_addr(node_id: NodeId) -> String {
+    format!("127.0.0.1:3220{}", node_id)
+}
+
+/// Node 3 stops answering; after `NODE_TIMEOUT` the leader removes it from the membership and
+/// the remaining two nodes keep serving writes.
+///
+/// The nodes run as threads of the test process and can not be stopped one by one, so node 3
+/// is cut off from the others in both directions instead, which is all the leader can observe
+/// of a stopped process anyway.
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn test_dead_node_removed_from_membership() -> Result<(), Box<dyn std::error::Error>> {
+    let _ = tracing_subscriber::fmt()
+        .with_target(true)
+        .with_thread_ids(true)
+        .with_level(true)
+        .with_ansi(false)
+        .try_init();
+
+    let faults: BTreeMap<NodeId, FaultConfig> =
+        (1..=3).map(|id| (id, FaultConfig::default())).collect();
+
+    let handle = Handle::current();
+    for id in 1..=3 {
+        let dir = tempfile::TempDir::new()?;
+        let network = FaultInjectingNetwork {
+            faults: faults[&id].clone(),
+            liveness: Default::default(),
+        };
+        let handle = handle.clone();
+        thread::spawn(move || {
+            let x = handle.block_on(start_raft_node_with_network(
+                id,
+                dir.path(),
+                get_addr(id),
+                get_rpc_addr(id),
+                network,
+                Some(NODE_TIMEOUT),
+                Default::default(),
+            ));
+            println!("x: {:?}", x);
+        });
+    }
+
+    let barrier = ClusterBarrier::new((1..=3).map(|id| (id, get_addr(id))));
+    barrier.wait_for_startup().await?;
+
+    let leader = ExampleClient::new(1, get_addr(1));
+    leader.init().await?;
+    leader
+        .add_learner((2, get_addr(2), get_rpc_addr(2)))
+        .await?;
+    leader
+        .add_learner((3, get_addr(3), get_rpc_addr(3)))
+        .await?;
+    leader.change_membership(&btreeset! {1,2,3}).await?;
+    barrier.wait_for_leader().await?;
+
+    // --- All nodes answer: nobody is removed, however long we wait.
+
+    tokio::time::sleep(NODE_TIMEOUT * 2).await;
+    let voters = |m: &openraft::RaftMetrics<NodeId, raft_kv_rocksdb::Node>| {
+        m.membership_config
+            .membership()
+            .voter_ids()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(voters(&leader.metrics().await?), vec![1, 2, 3]);
+
+    // --- Node 3 goes silent.
+
+    println!("=== stop node 3");
+    let stopped_at = Instant::now();
+    isolate(&faults, 3);
+
+    let survivors = ClusterBarrier::new([(1, get_addr(1)), (2, get_addr(2))]);
+    survivors
+        .wait_until("node 3 to be removed", |m| {
+            m.membership_config.nodes().all(|(id, _)| *id != 3)
+        })
+        .await?;
+    assert!(stopped_at.elapsed() >= NODE_TIMEOUT);
+
+    let metrics = leader.metrics().await?;
+    assert_eq!(voters(&metrics), vec![1, 2]);
+    assert_eq!(metrics.current_leader, Some(1));
+
+    // --- Two voters left, both needed for a quorum, and writes still commit.
+
+    let x = leader
+        .write(&Request::Set {
+            key: "after-removal".to_string(),
+            value: "ok".to_string(),
+        })
+        .await?;
+    survivors.wait_for_applied(x.log_id).await?;
+    assert_eq!(
+        "ok",
+        ExampleClient::new(2, get_addr(2))
+            .read(&"after-removal".to_string())
+            .await?
+    );
+
+    Ok(())
+}
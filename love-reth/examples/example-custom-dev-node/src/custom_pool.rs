@@ -0,0 +1,154 @@
+// --- 自定义交易池：在默认的以太坊校验之前加一道最低 gas price 门槛 ---
+//
+// reth 的节点由一组组件拼起来（pool / network / executor / consensus / payload），
+// EthereumNode::components() 给出的是默认组合，其中任何一个都可以单独替换：
+//   EthereumNode::components().pool(CustomPoolBuilder::default())
+//
+// 交易进池之前都会经过 TransactionValidator::validate_transaction。
+// CustomTxValidator 包住默认的校验器，先检查价格，不够就直接返回 Invalid，够了再交给默认校验器
+
+use reth_ethereum::{
+    EthPrimitives,
+    chainspec::ChainSpec,
+    node::{
+        api::{FullNodeTypes, NodeTypes},
+        builder::{BuilderContext, components::PoolBuilder},
+    },
+    pool::{
+        CoinbaseTipOrdering, EthPooledTransaction, EthTransactionValidator, Pool, PoolConfig,
+        PoolTransaction, TransactionOrigin, TransactionValidationOutcome,
+        TransactionValidationTaskExecutor, TransactionValidator,
+        blobstore::InMemoryBlobStore,
+        error::InvalidPoolTransactionError,
+        maintain::{MaintainPoolConfig, maintain_transaction_pool_future},
+    },
+    provider::CanonStateSubscriptions,
+};
+
+/// 1 gwei
+pub const GWEI: u128 = 1_000_000_000;
+
+/// 最低价格：max_fee_per_gas（legacy 交易就是 gas_price）低于 100 gwei 的交易一律不收
+pub const MIN_GAS_PRICE: u128 = 100 * GWEI;
+
+/// 在 inner 之前检查最低价格的校验器
+#[derive(Debug, Clone)]
+pub struct CustomTxValidator<V> {
+    inner: V,
+}
+
+impl<V> CustomTxValidator<V> {
+    pub fn new(inner: V) -> Self {
+        Self { inner }
+    }
+}
+
+impl<V> TransactionValidator for CustomTxValidator<V>
+where
+    V: TransactionValidator,
+{
+    type Transaction = V::Transaction;
+    type Block = V::Block;
+
+    async fn validate_transaction(
+        &self,
+        origin: TransactionOrigin,
+        transaction: Self::Transaction,
+    ) -> TransactionValidationOutcome<Self::Transaction> {
+        if transaction.max_fee_per_gas() < MIN_GAS_PRICE {
+            // RPC 层会把 Underpriced 转成 “transaction underpriced” 错误返回给 eth_sendRawTransaction
+            return TransactionValidationOutcome::Invalid(
+                transaction,
+                InvalidPoolTransactionError::Underpriced,
+            );
+        }
+        self.inner.validate_transaction(origin, transaction).await
+    }
+}
+
+type CustomValidator<Provider> = CustomTxValidator<
+    TransactionValidationTaskExecutor<EthTransactionValidator<Provider, EthPooledTransaction>>,
+>;
+
+pub type CustomPool<Provider> =
+    Pool<CustomValidator<Provider>, CoinbaseTipOrdering<EthPooledTransaction>, InMemoryBlobStore>;
+
+/// 和默认的 EthereumPoolBuilder 基本一样，只是把校验器换成了 CustomTxValidator
+#[derive(Debug, Clone, Default)]
+pub struct CustomPoolBuilder {
+    pool_config: PoolConfig,
+}
+
+impl<Node> PoolBuilder<Node> for CustomPoolBuilder
+where
+    Node: FullNodeTypes<Types: NodeTypes<ChainSpec = ChainSpec, Primitives = EthPrimitives>>,
+{
+    type Pool = CustomPool<Node::Provider>;
+
+    async fn build_pool(self, ctx: &BuilderContext<Node>) -> eyre::Result<Self::Pool> {
+        let blob_store = InMemoryBlobStore::default();
+        let validator = TransactionValidationTaskExecutor::eth_builder(ctx.provider().clone())
+            .with_head_timestamp(ctx.head().timestamp)
+            .kzg_settings(ctx.kzg_settings()?)
+            .with_additional_tasks(ctx.config().txpool.additional_validation_tasks)
+            .build_with_tasks(ctx.task_executor().clone(), blob_store.clone());
+
+        let pool = Pool::new(
+            CustomTxValidator::new(validator),
+            CoinbaseTipOrdering::default(),
+            blob_store,
+            self.pool_config,
+        );
+
+        // 维护任务：新块上链后把已打包的交易移出池子，否则 dev 节点会反复打包同一笔交易
+        ctx.task_executor().spawn_critical(
+            "txpool maintenance task",
+            maintain_transaction_pool_future(
+                ctx.provider().clone(),
+                pool.clone(),
+                ctx.provider().canonical_state_stream(),
+                ctx.task_executor().clone(),
+                MaintainPoolConfig::default(),
+            ),
+        );
+
+        Ok(pool)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reth_ethereum::pool::test_utils::{MockTransaction, MockTransactionValidator};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reject_below_min_gas_price() {
+        let validator = CustomTxValidator::new(MockTransactionValidator::default());
+
+        let cheap = MockTransaction::eip1559().with_max_fee(MIN_GAS_PRICE - 1);
+        let outcome = validator
+            .validate_transaction(TransactionOrigin::External, cheap)
+            .await;
+        assert!(matches!(
+            outcome,
+            TransactionValidationOutcome::Invalid(_, InvalidPoolTransactionError::Underpriced)
+        ));
+
+        let legacy = MockTransaction::legacy().with_gas_price(MIN_GAS_PRICE - 1);
+        let outcome = validator
+            .validate_transaction(TransactionOrigin::Local, legacy)
+            .await;
+        assert!(matches!(outcome, TransactionValidationOutcome::Invalid(..)));
+
+        // 达到门槛的交易交给 inner（这里的 mock 校验器什么都放行）
+        let enough = MockTransaction::eip1559().with_max_fee(MIN_GAS_PRICE);
+        let outcome = validator
+            .validate_transaction(TransactionOrigin::External, enough)
+            .await;
+        assert!(matches!(
+            outcome,
+            TransactionValidationOutcome::Valid { .. }
+        ));
+    }
+}
@@ -6,12 +6,12 @@
 use std::sync::Arc;
 
 use alloy_genesis::Genesis;
-use alloy_primitives::{b256, hex};
+use alloy_primitives::hex;
 use futures_util::StreamExt;
 use reth_ethereum::{
     chainspec::ChainSpec,
     node::{
-        EthereumNode,
+        EthereumAddOns, EthereumNode,
         builder::{NodeBuilder, NodeHandle},
         core::{args::RpcServerArgs, node_config::NodeConfig},
     },
@@ -19,6 +19,11 @@ use reth_ethereum::{
     rpc::{EthApi, api::eth::helpers::EthTransactions, eth::EthApiServer},
     tasks::TaskManager,
 };
+use custom_pool::CustomPoolBuilder;
+use reorg::ReorgDetector;
+
+mod custom_pool;
+mod reorg;
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
@@ -33,12 +38,28 @@ async fn main() -> eyre::Result<()> {
 
     let NodeHandle {node, node_exit_future: _} = NodeBuilder::new(node_config)
     .testing_node(tasks.executor())
-    .node(EthereumNode::default())
+    // 默认的以太坊组件，只把交易池换成带最低 gas price 门槛的版本
+    .with_types::<EthereumNode>()
+    .with_components(EthereumNode::components().pool(CustomPoolBuilder::default()))
+    .with_add_ons(EthereumAddOns::default())
     .launch_with_debug_capabilities()
     .await?;
 
     let mut notifications = node.provider.canonical_state_stream();
 
+    // 另开一个订阅给 reorg 检测器，检测到重组就打印出来
+    let (detector, mut reorgs) = ReorgDetector::new();
+    tokio::spawn(detector.run(node.provider.canonical_state_stream()));
+    tokio::spawn(async move {
+        while let Some(reorg) = reorgs.recv().await {
+            println!(
+                "chain reorg: reverted {} blocks, {} new blocks",
+                reorg.reverted_blocks.len(),
+                reorg.new_blocks.len()
+            );
+        }
+    });
+
     // submit tx through rpc 
     let raw_tx = hex!(
         "02f876820a28808477359400847735940082520894ab0840c0e43688012c1adb0f5e3fc665188f83d28a029d394a5d630544000080c080a0a044076b7e67b5deecc63f61a8d7913fab86ca365b344b5759d1fe3563b4c39ea019eab979dd000da04dfc72bb0377c092d30fd9e1cab5ae487de49586cc8b0090"
@@ -46,9 +67,16 @@ async fn main() -> eyre::Result<()> {
 
     let eth_api = node.rpc_registry.eth_api();
 
-    let hash = eth_api.send_raw_transaction(raw_tx.into()).await?;
-    
-        let expected = b256!("0xb1c6512f4fc202c04355fbda66755e0e344b152e633010e8fd75ecec09b63398");
+    // 这笔交易的 max_fee_per_gas 是 2 gwei，低于 CustomTxValidator 的 100 gwei 门槛，进不了池子
+    let err = eth_api
+        .send_raw_transaction(raw_tx.into())
+        .await
+        .expect_err("underpriced transaction must be rejected");
+    println!("eth_sendRawTransaction rejected: {err}");
+    eyre::ensure!(
+        err.to_string().contains("underpriced"),
+        "unexpected error: {err}"
+    );
 
 
 
@@ -0,0 +1,177 @@
+// --- 链重组（reorg）检测 ---
+//
+// 订阅 canonical state 通知，自己维护一份 “高度 -> 区块” 的规范链视图。每来一批新块就检查：
+//   1. 同一高度上已经有一个 hash 不同的块：说明这个高度被换掉了，从这个高度开始的旧块都被回滚
+//   2. 新块的 parent_hash 和我们记录的 number - 1 的 hash 对不上：说明连 number - 1 都被换掉了
+// 任意一种情况都发出一个 ChainReorg 事件。
+//
+// reth 自己发的 CanonStateNotification::Reorg 已经带了新旧两条链，这里不直接用它，
+// 只看 committed() 这一侧，用 hash 链自己判断，和一个只能看到新块的外部消费者的处境一样
+
+use std::collections::BTreeMap;
+
+use alloy_primitives::B256;
+use futures_util::{Stream, StreamExt};
+use reth_ethereum::primitives::SealedHeader;
+use reth_ethereum::provider::CanonStateNotification;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainReorg {
+    /// 被回滚的旧块，按高度从低到高
+    pub reverted_blocks: Vec<SealedHeader>,
+    /// 替换上来的新块，按高度从低到高
+    pub new_blocks: Vec<SealedHeader>,
+}
+
+pub struct ReorgDetector {
+    /// 当前规范链：block_number -> header（header 里带着 hash）
+    canonical: BTreeMap<u64, SealedHeader>,
+    events: mpsc::UnboundedSender<ChainReorg>,
+}
+
+impl ReorgDetector {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<ChainReorg>) {
+        let (events, rx) = mpsc::unbounded_channel();
+        let detector = ReorgDetector {
+            canonical: BTreeMap::new(),
+            events,
+        };
+        (detector, rx)
+    }
+
+    /// 记录的某个高度的 hash
+    pub fn hash_at(&self, number: u64) -> Option<B256> {
+        self.canonical.get(&number).map(|header| header.hash())
+    }
+
+    /// 一直消费通知，直到流结束
+    pub async fn run<S>(mut self, notifications: S)
+    where
+        S: Stream<Item = CanonStateNotification>,
+    {
+        let mut notifications = std::pin::pin!(notifications);
+        while let Some(notification) = notifications.next().await {
+            self.on_committed(notification.committed().headers());
+        }
+    }
+
+    /// 处理一批新提交的块（按高度递增），发现重组时发出事件，并把事件也返回给调用方
+    pub fn on_committed(
+        &mut self,
+        headers: impl IntoIterator<Item = SealedHeader>,
+    ) -> Option<ChainReorg> {
+        let mut reorg: Option<ChainReorg> = None;
+
+        for header in headers {
+            let number = header.number;
+
+            if let Some(reorg) = reorg.as_mut() {
+                // 分叉点之后的块都属于新链
+                reorg.new_blocks.push(header.clone());
+            } else if let Some(fork_at) = self.fork_point(&header) {
+                // 从分叉点开始的旧块全部作废
+                let reverted = self.canonical.split_off(&fork_at);
+                reorg = Some(ChainReorg {
+                    reverted_blocks: reverted.into_values().collect(),
+                    new_blocks: vec![header.clone()],
+                });
+            }
+
+            self.canonical.insert(number, header);
+        }
+
+        if let Some(reorg) = &reorg {
+            // 没人接收也不影响继续检测
+            let _ = self.events.send(reorg.clone());
+        }
+        reorg
+    }
+
+    /// 这个新块和已知的规范链冲突时，返回从哪个高度开始的旧块要回滚
+    fn fork_point(&self, header: &SealedHeader) -> Option<u64> {
+        let number = header.number;
+
+        // 同一高度已经有别的块
+        if self
+            .canonical
+            .get(&number)
+            .is_some_and(|old| old.hash() != header.hash())
+        {
+            return Some(number);
+        }
+
+        // 父块对不上：number - 1 也被换掉了
+        let parent = self.canonical.get(&number.checked_sub(1)?)?;
+        (parent.hash() != header.parent_hash).then_some(number - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reth_ethereum::primitives::Header;
+
+    use super::*;
+
+    /// 从 parent 往后接 n 个块，extra_data 不同的两条链 hash 就不同
+    fn extend(parent: Option<&SealedHeader>, n: u64, tag: u8) -> Vec<SealedHeader> {
+        let mut out: Vec<SealedHeader> = Vec::new();
+        let mut prev = parent.cloned();
+        for _ in 0..n {
+            let header = Header {
+                number: prev.as_ref().map_or(0, |p| p.number + 1),
+                parent_hash: prev.as_ref().map_or(B256::ZERO, |p| p.hash()),
+                extra_data: vec![tag].into(),
+                ..Default::default()
+            };
+            let sealed = SealedHeader::seal_slow(header);
+            prev = Some(sealed.clone());
+            out.push(sealed);
+        }
+        out
+    }
+
+    #[test]
+    fn test_detect_fork_at_block_5() {
+        let (mut detector, mut events) = ReorgDetector::new();
+
+        // 0..=7 正常出块，一块一块提交
+        let main = extend(None, 8, 0);
+        for header in &main {
+            assert_eq!(detector.on_committed([header.clone()]), None);
+        }
+        assert_eq!(detector.hash_at(7), Some(main[7].hash()));
+
+        // 从 block 4 后面分叉：新链的 5', 6', 7', 8', 9'
+        let fork = extend(Some(&main[4]), 5, 1);
+        let reorg = detector.on_committed(fork.clone()).unwrap();
+
+        assert_eq!(reorg.reverted_blocks, main[5..].to_vec());
+        assert!(!reorg.reverted_blocks.is_empty());
+        assert_eq!(reorg.new_blocks, fork);
+        assert_eq!(events.try_recv().unwrap(), reorg);
+
+        // 规范链已经切到新链上
+        assert_eq!(detector.hash_at(4), Some(main[4].hash()));
+        assert_eq!(detector.hash_at(5), Some(fork[0].hash()));
+        assert_eq!(detector.hash_at(9), Some(fork[4].hash()));
+
+        // 继续在新链上出块，不再报 reorg
+        let next = extend(fork.last(), 1, 1);
+        assert_eq!(detector.on_committed(next), None);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_detect_parent_mismatch() {
+        let (mut detector, _events) = ReorgDetector::new();
+        let main = extend(None, 6, 0);
+        detector.on_committed(main.clone());
+
+        // 新链从 block 3 就分叉了，但我们只看到它的 block 6'：它的父块 5' 和记录的 5 对不上
+        let fork = extend(Some(&main[2]), 4, 1);
+        let reorg = detector.on_committed([fork[3].clone()]).unwrap();
+        assert_eq!(reorg.reverted_blocks, vec![main[5].clone()]);
+        assert_eq!(reorg.new_blocks, vec![fork[3].clone()]);
+    }
+}
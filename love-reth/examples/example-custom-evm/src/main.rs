@@ -34,7 +34,10 @@ use reth_ethereum::{
 use alloy_evm::revm::precompile::PrecompileError;
 use reth_tracing::{RethTracer, Tracer};
 
+mod multisig;
 mod practice_lib;
+#[cfg(feature = "randomness")]
+mod randomness;
 
 /// 单元结构体，空的结构体
 /// rust 中，结构体中不一定要存数据，它也可以仅仅用来承载行为
@@ -78,6 +81,8 @@ impl EvmFactory for MyEvmFactory {
         input: alloy_evm::EvmEnv<Self::Spec, Self::BlockEnv>, // 环境参数（区块信息、配置）
     ) -> Self::Evm<DB, alloy_evm::revm::inspector::NoOpInspector> {
         let spec = input.cfg_env.spec; // 获取当前区块的硬分叉版本
+        #[cfg(feature = "randomness")]
+        let chain_id = input.cfg_env.chain_id;
 
         // A. 构建器模式 builder pattern 构建  evm 上下文
         let mut evm = Context::mainnet()
@@ -94,6 +99,14 @@ impl EvmFactory for MyEvmFactory {
         if spec == SpecId::PRAGUE {
             // 加载我们要注入的私货，prague_cuscom
             evm = evm.with_precompiles(PrecompilesMap::from_static(prague_custom()));
+
+            // 随机数预编译会让执行结果不确定，主网上无论如何都不注册，见 randomness.rs 开头的警告
+            #[cfg(feature = "randomness")]
+            if chain_id != Chain::mainnet().id() {
+                evm = evm.with_precompiles(PrecompilesMap::from_static(
+                    randomness::prague_custom_with_randomness(),
+                ));
+            }
         }
 
         // D. 返回封装好的 EVM
@@ -198,8 +211,15 @@ pub fn prague_custom() -> &'static Precompiles {
             }, // ⬆️⬆️⬆️ 逻辑结束 ⬆️⬆️⬆️
         );
 
+        // M-of-N 多签校验，逻辑比较长，放在 multisig 模块里，这里直接传函数指针
+        let multisig = Precompile::new(
+            PrecompileId::custom("multisig"),
+            multisig::MULTISIG_ADDRESS,
+            multisig::multisig_precompile,
+        );
+
         // 4. 将自定义的合约加入列表
-        precompiles.extend([precompile]);
+        precompiles.extend([precompile, multisig]);
         precompiles
     })
 }
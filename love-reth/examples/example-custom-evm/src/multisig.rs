@@ -0,0 +1,150 @@
+use std::collections::HashSet;
+
+use alloy_evm::revm::precompile::{PrecompileError, PrecompileOutput, PrecompileResult};
+use alloy_primitives::{Address, B256, Bytes, Signature, address};
+
+/// M-of-N 多签校验预编译合约的地址
+pub const MULTISIG_ADDRESS: Address = address!("0x0000000000000000000000000000000000000995");
+
+const BASE_GAS: u64 = 3000;
+const PER_SIGNATURE_GAS: u64 = 3000;
+
+/// m: u8 + n: u8 + msg_hash: [u8; 32]
+const HEADER_LEN: usize = 2 + 32;
+/// r(32) + s(32) + v(1)
+const SIGNATURE_LEN: usize = 65;
+
+/// M-of-N ECDSA 多签校验
+///
+/// 输入格式：`[m: u8, n: u8, msg_hash: [u8; 32], sig1: [u8; 65], ..., sigN: [u8; 65]]`
+///
+/// 对每个签名用 ecrecover 恢复出签名者地址，去重后如果至少有 m 个不同的签名者，返回 `[0x01]`，否则 `[0x00]`。
+/// 恢复失败的签名直接忽略（不计数），而不是让整个调用失败，和 ecrecover 预编译的习惯一致。
+///
+/// gas: 3000 + 3000 * n，按声明的 n 收费，防止用超长输入白嫖计算
+pub fn multisig_precompile(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    if input.len() < HEADER_LEN {
+        return Err(PrecompileError::Other(
+            "Input must be at least 34 bytes".into(),
+        ));
+    }
+
+    let m = input[0] as usize;
+    let n = input[1] as usize;
+
+    // 1. 先算 gas，gas 不够什么都不用做
+    let gas_used = BASE_GAS + PER_SIGNATURE_GAS * n as u64;
+    if gas_used > gas_limit {
+        return Err(PrecompileError::OutOfGas);
+    }
+
+    // 2. 校验输入格式
+    if input.len() != HEADER_LEN + n * SIGNATURE_LEN {
+        return Err(PrecompileError::Other(
+            format!(
+                "Expected {} signatures ({} bytes), got {} bytes",
+                n,
+                HEADER_LEN + n * SIGNATURE_LEN,
+                input.len()
+            )
+            .into(),
+        ));
+    }
+    if m == 0 || m > n {
+        return Err(PrecompileError::Other(
+            format!("Invalid threshold {m}-of-{n}").into(),
+        ));
+    }
+
+    let msg_hash = B256::from_slice(&input[2..HEADER_LEN]);
+
+    // 3. 逐个恢复签名者，HashSet 去重：同一个人签两次只算一票
+    let signers: HashSet<Address> = input[HEADER_LEN..]
+        .chunks_exact(SIGNATURE_LEN)
+        .filter_map(|raw| Signature::from_raw(raw).ok())
+        .filter_map(|sig| sig.recover_address_from_prehash(&msg_hash).ok())
+        .collect();
+
+    let passed = signers.len() >= m;
+    Ok(PrecompileOutput::new(
+        gas_used,
+        Bytes::from(vec![passed as u8]),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::keccak256;
+    use alloy_signer::SignerSync;
+    use alloy_signer_local::PrivateKeySigner;
+
+    use super::*;
+
+    fn encode(m: u8, msg_hash: B256, sigs: &[[u8; 65]]) -> Vec<u8> {
+        let mut input = vec![m, sigs.len() as u8];
+        input.extend_from_slice(msg_hash.as_slice());
+        for sig in sigs {
+            input.extend_from_slice(sig);
+        }
+        input
+    }
+
+    fn sign(signer: &PrivateKeySigner, msg_hash: B256) -> [u8; 65] {
+        signer.sign_hash_sync(&msg_hash).unwrap().as_bytes()
+    }
+
+    #[test]
+    fn test_multisig() {
+        let msg_hash = keccak256("transfer 100 ETH to bob");
+        let signers: Vec<PrivateKeySigner> = (0..3).map(|_| PrivateKeySigner::random()).collect();
+        let sigs: Vec<[u8; 65]> = signers.iter().map(|s| sign(s, msg_hash)).collect();
+
+        // 2-of-3，三个人都签了
+        let out = multisig_precompile(&encode(2, msg_hash, &sigs), 100_000).unwrap();
+        assert_eq!(out.bytes.as_ref(), &[0x01]);
+        assert_eq!(out.gas_used, 3000 + 3000 * 3);
+
+        // 3-of-3，但 signer0 签了两次：去重后只有 2 个人
+        let dup = [sigs[0], sigs[0], sigs[1]];
+        let out = multisig_precompile(&encode(3, msg_hash, &dup), 100_000).unwrap();
+        assert_eq!(out.bytes.as_ref(), &[0x00]);
+
+        // 签的是别的消息：按 msg_hash 恢复出来的是三个不相干的地址，只能说明“有 3 个不同的签名者”
+        // 这个预编译只负责数人头，签名者是否在白名单里要由调用它的合约自己检查
+        let other = keccak256("something else");
+        let wrong: Vec<[u8; 65]> = signers.iter().map(|s| sign(s, other)).collect();
+        let out = multisig_precompile(&encode(2, msg_hash, &wrong), 100_000).unwrap();
+        assert_eq!(out.bytes.as_ref(), &[0x01]);
+
+        // 阈值非法：0-of-3、4-of-3
+        assert!(multisig_precompile(&encode(0, msg_hash, &sigs), 100_000).is_err());
+        assert!(multisig_precompile(&encode(4, msg_hash, &sigs), 100_000).is_err());
+
+        // 垃圾签名会被忽略，不会让整个调用失败
+        let garbage = [sigs[0], [0xff; 65]];
+        let out = multisig_precompile(&encode(2, msg_hash, &garbage), 100_000).unwrap();
+        assert_eq!(out.bytes.as_ref(), &[0x00]);
+        let out = multisig_precompile(&encode(1, msg_hash, &garbage), 100_000).unwrap();
+        assert_eq!(out.bytes.as_ref(), &[0x01]);
+    }
+
+    #[test]
+    fn test_multisig_gas_and_format() {
+        let msg_hash = keccak256("hello");
+        let sig = sign(&PrivateKeySigner::random(), msg_hash);
+
+        // gas 刚好够 / 差 1
+        let input = encode(1, msg_hash, &[sig]);
+        assert!(multisig_precompile(&input, 6000).is_ok());
+        assert!(matches!(
+            multisig_precompile(&input, 5999),
+            Err(PrecompileError::OutOfGas)
+        ));
+
+        // 长度和声明的 n 对不上
+        let mut truncated = input.clone();
+        truncated.pop();
+        assert!(multisig_precompile(&truncated, 100_000).is_err());
+        assert!(multisig_precompile(&[1, 1], 100_000).is_err());
+    }
+}
@@ -109,3 +109,129 @@ mod once_lock_tests {
 
     }
 }
+
+/// 第一次用到时才分配内存的 Vec
+///
+/// 注意：里面要往 Vec 里 push，需要内部可变性，所以是 RefCell<Vec<T>>。
+/// RefCell 不是 Sync，包了它的 OnceLock 也就不能跨线程共享了，OnceLock 的线程安全在这里用不上，
+/// 单线程版的 std::cell::OnceCell 就够了，而且不用原子操作。
+/// 想跨线程共享就得换成 OnceLock<Mutex<Vec<T>>>
+#[cfg(test)]
+#[allow(dead_code)]
+mod deferred_vec_tests {
+    use std::cell::{OnceCell, RefCell};
+    use std::collections::BinaryHeap;
+    use std::time::Instant;
+
+    // push 之前没有调用过 get_or_init_with_capacity 时，默认预留的容量
+    const DEFAULT_CAPACITY: usize = 16;
+
+    struct DeferredVec<T> {
+        inner: OnceCell<RefCell<Vec<T>>>,
+    }
+
+    impl<T> DeferredVec<T> {
+        const fn new() -> Self {
+            Self {
+                inner: OnceCell::new(),
+            }
+        }
+
+        // 只有第一次调用的 capacity 有效，之后直接返回已经分配好的那个
+        fn get_or_init_with_capacity(&self, capacity: usize) -> &RefCell<Vec<T>> {
+            self.inner
+                .get_or_init(|| RefCell::new(Vec::with_capacity(capacity)))
+        }
+
+        // &self 就能 push：可变性在 RefCell 里，运行时检查借用
+        fn push(&self, item: T) {
+            self.get_or_init_with_capacity(DEFAULT_CAPACITY)
+                .borrow_mut()
+                .push(item);
+        }
+
+        fn is_allocated(&self) -> bool {
+            self.inner.get().is_some()
+        }
+
+        fn len(&self) -> usize {
+            self.inner.get().map_or(0, |v| v.borrow().len())
+        }
+    }
+
+    #[test]
+    fn test_deferred_vec_allocates_on_first_use() {
+        let v: DeferredVec<u64> = DeferredVec::new();
+        assert!(!v.is_allocated());
+        assert_eq!(v.len(), 0);
+
+        // 第一次用的时候才分配
+        assert!(v.get_or_init_with_capacity(64).borrow().capacity() >= 64);
+        assert!(v.is_allocated());
+
+        // 已经初始化过了，这次的 capacity 不起作用
+        assert!(v.get_or_init_with_capacity(1024).borrow().capacity() < 1024);
+
+        v.push(1);
+        v.push(2);
+        assert_eq!(*v.get_or_init_with_capacity(0).borrow(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_push_without_init() {
+        let v = DeferredVec::new();
+        v.push("a");
+        assert!(v.is_allocated());
+        assert!(v.get_or_init_with_capacity(0).borrow().capacity() >= DEFAULT_CAPACITY);
+    }
+
+    // 结论：BlockBuilder 的 frontier 用不着这个
+    // Vec::new() / BinaryHeap::new() 本来就不分配内存（capacity 为 0，指针是悬空的占位值），
+    // 第一次 push 时才分配。DeferredVec 省不下任何东西，反而多了 OnceCell 的标记和 RefCell 的借用计数
+    #[test]
+    fn test_binary_heap_new_does_not_allocate() {
+        let heap: BinaryHeap<u64> = BinaryHeap::new();
+        assert_eq!(heap.capacity(), 0);
+
+        assert!(std::mem::size_of::<DeferredVec<u64>>() > std::mem::size_of::<BinaryHeap<u64>>());
+    }
+
+    #[test]
+    #[ignore = "benchmark: cargo test --release bench_deferred_vec -- --ignored --nocapture"]
+    fn bench_deferred_vec_vs_binary_heap() {
+        const N: usize = 10_000_000;
+
+        // 创建空容器：两个都不分配，都只是在栈上写几个字
+        let start = Instant::now();
+        for _ in 0..N {
+            std::hint::black_box(BinaryHeap::<u64>::new());
+        }
+        let heap_new = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..N {
+            std::hint::black_box(DeferredVec::<u64>::new());
+        }
+        let deferred_new = start.elapsed();
+
+        // 第一次 push：两个都要分配一次
+        let start = Instant::now();
+        for i in 0..N as u64 {
+            let mut heap = BinaryHeap::new();
+            heap.push(i);
+            std::hint::black_box(heap);
+        }
+        let heap_push = start.elapsed();
+
+        let start = Instant::now();
+        for i in 0..N as u64 {
+            let v = DeferredVec::new();
+            v.push(i);
+            std::hint::black_box(v);
+        }
+        let deferred_push = start.elapsed();
+
+        println!("创建  BinaryHeap::new: {heap_new:?}, DeferredVec::new: {deferred_new:?}");
+        println!("首次 push  BinaryHeap: {heap_push:?}, DeferredVec: {deferred_push:?}");
+    }
+}
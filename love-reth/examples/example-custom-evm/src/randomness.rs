@@ -0,0 +1,94 @@
+// ⚠️⚠️⚠️ 警告：这个预编译让 EVM 的执行结果不再是确定的 ⚠️⚠️⚠️
+//
+// 共识要求每个节点重放同一个块得到完全相同的状态根。这个预编译每次调用返回的都是本机的随机数，
+// 同一笔交易在出块节点和验证节点上执行，结果不一样，state root 对不上，块会被拒绝；
+// 出块节点自己重启后重放历史块也会算出不同的状态。
+// 只能用在单节点的本地测试链上（比如给 zkEVM 的 prover 造随机输入），绝对不能用在任何需要共识的链上。
+// 链上真正需要随机数时用 RANDAO（block.prevrandao）或者 VRF 预言机。
+//
+// 所以它默认不编译（feature = "randomness"），打开之后也只在非主网的 chain id 上注册，见 MyEvmFactory::create_evm
+
+use std::sync::OnceLock;
+
+use alloy_evm::revm::precompile::{
+    Precompile, PrecompileError, PrecompileId, PrecompileOutput, PrecompileResult, Precompiles,
+};
+use alloy_primitives::{Address, Bytes, address};
+
+/// 随机数预编译合约的地址
+pub const RANDOMNESS_ADDRESS: Address = address!("0x0000000000000000000000000000000000000994");
+
+/// 一次最多返回的字节数
+pub const MAX_RANDOM_BYTES: usize = 256;
+
+const BASE_GAS: u64 = 100;
+const PER_WORD_GAS: u64 = 3;
+
+/// 返回 N 个随机字节，N = 输入的长度（输入的内容不看），最多 256
+///
+/// gas: 100 + 3 * ceil(N / 32)
+pub fn randomness_precompile(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    let len = input.len().min(MAX_RANDOM_BYTES);
+
+    let gas_used = BASE_GAS + PER_WORD_GAS * len.div_ceil(32) as u64;
+    if gas_used > gas_limit {
+        return Err(PrecompileError::OutOfGas);
+    }
+
+    // 直接从操作系统取随机数（Linux 上是 getrandom 系统调用）
+    let mut out = vec![0u8; len];
+    getrandom::getrandom(&mut out)
+        .map_err(|e| PrecompileError::Other(format!("getrandom failed: {e}").into()))?;
+
+    Ok(PrecompileOutput::new(gas_used, Bytes::from(out)))
+}
+
+/// `prague_custom()` 再加上随机数预编译，同样只初始化一次
+pub fn prague_custom_with_randomness() -> &'static Precompiles {
+    static INSTANCE: OnceLock<Precompiles> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| {
+        let mut precompiles = crate::prague_custom().clone();
+        precompiles.extend([Precompile::new(
+            PrecompileId::custom("randomness"),
+            RANDOMNESS_ADDRESS,
+            randomness_precompile,
+        )]);
+        precompiles
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_calls_differ() {
+        let a = randomness_precompile(&[0; 32], 100_000).unwrap();
+        let b = randomness_precompile(&[0; 32], 100_000).unwrap();
+        assert_eq!(a.bytes.len(), 32);
+        // 两次都一样的概率是 2^-256
+        assert_ne!(a.bytes, b.bytes);
+        assert_eq!(a.gas_used, 100 + 3);
+    }
+
+    #[test]
+    fn test_length_cap_and_gas() {
+        let out = randomness_precompile(&[0; 1000], 100_000).unwrap();
+        assert_eq!(out.bytes.len(), MAX_RANDOM_BYTES);
+        // 按截断后的 256 字节收费
+        assert_eq!(out.gas_used, 100 + 3 * 8);
+
+        assert!(
+            randomness_precompile(&[], 100_000)
+                .unwrap()
+                .bytes
+                .is_empty()
+        );
+
+        assert!(matches!(
+            randomness_precompile(&[0; 33], 105),
+            Err(PrecompileError::OutOfGas)
+        ));
+    }
+}
@@ -0,0 +1,71 @@
+// cargo bench --bench bloom
+//
+// example-db-access 是个 bin crate，bench 里没法 `use example_db_access::bloom`，直接把模块源码引进来
+// 模块里的单元测试在这里用不上，对应的 import 会报 unused
+#[allow(dead_code, unused_imports)]
+#[path = "../src/bloom.rs"]
+mod bloom;
+
+use std::hint::black_box;
+
+use alloy_primitives::{Address, keccak256};
+use bloom::{BLOOM_BYTES, BloomFilterSIMD, RawBloom};
+use criterion::{Criterion, criterion_group, criterion_main};
+use reth_ethereum::rpc::eth::primitives::Filter;
+
+const CHECKS: usize = 1_000_000;
+
+/// 1024 个随机 bloom 循环使用，凑够 100 万次检查（100 万个 256 字节的 bloom 要 256MB，没必要）
+fn random_blooms() -> Vec<RawBloom> {
+    let mut x = 0x9e37_79b9_7f4a_7c15u64;
+    (0..1024)
+        .map(|_| {
+            let mut bloom = [0u8; BLOOM_BYTES];
+            for byte in bloom.iter_mut() {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                *byte = x as u8;
+            }
+            bloom
+        })
+        .collect()
+}
+
+fn bench_bloom(c: &mut Criterion) {
+    let filter = Filter::new()
+        .address(Address::repeat_byte(0x11))
+        .event_signature(keccak256("Transfer(address,address,uint256)"));
+    let simd = BloomFilterSIMD::from_filter(&filter);
+    let blooms = random_blooms();
+
+    let mut group = c.benchmark_group("bloom_1m_checks");
+    group.sample_size(10);
+
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            blooms
+                .iter()
+                .cycle()
+                .take(CHECKS)
+                .filter(|bloom| simd.matches_bloom_scalar(black_box(bloom)))
+                .count()
+        })
+    });
+
+    group.bench_function("simd", |b| {
+        b.iter(|| {
+            blooms
+                .iter()
+                .cycle()
+                .take(CHECKS)
+                .filter(|bloom| simd.matches_bloom(black_box(bloom)))
+                .count()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_bloom);
+criterion_main!(benches);
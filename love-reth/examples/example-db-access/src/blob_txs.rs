@@ -0,0 +1,99 @@
+// --- EIP-4844 blob 交易 ---
+//
+// type 3 的交易多了一个 blob_versioned_hashes 字段：每个 blob 的 KZG commitment 的 hash（首字节是版本号 0x01）。
+// blob 本身不上链（只在共识层保留一段时间），执行层的交易里只留下这些 hash。
+// 其他类型的交易 blob_versioned_hashes() 返回 None
+
+use std::ops::RangeBounds;
+
+use alloy_consensus::{Transaction, Typed2718};
+use alloy_primitives::B256;
+use reth_ethereum::TransactionSigned;
+use reth_ethereum::provider::TransactionsProvider;
+
+/// EIP-2718 里 blob 交易的类型号
+pub const BLOB_TX_TYPE: u8 = 3;
+
+/// 查询一段区块里所有的 blob 交易，以及每笔交易带的 blob versioned hashes
+pub fn get_blob_transactions<T>(
+    provider: &T,
+    blocks: impl RangeBounds<u64>,
+) -> eyre::Result<Vec<(TransactionSigned, Vec<B256>)>>
+where
+    T: TransactionsProvider<Transaction = TransactionSigned>,
+{
+    let blocks = provider.transactions_by_block_range(blocks)?;
+    Ok(blob_transactions(blocks.into_iter().flatten()).collect())
+}
+
+/// [start_block, end_block] 里 blob 交易的数量
+pub fn count_blob_transactions_in_range<T>(
+    provider: &T,
+    start_block: u64,
+    end_block: u64,
+) -> eyre::Result<u64>
+where
+    T: TransactionsProvider<Transaction = TransactionSigned>,
+{
+    let blocks = provider.transactions_by_block_range(start_block..=end_block)?;
+    Ok(blob_transactions(blocks.into_iter().flatten()).count() as u64)
+}
+
+/// 只留下 type 3 的交易，顺便把 blob versioned hashes 拿出来
+fn blob_transactions(
+    txs: impl IntoIterator<Item = TransactionSigned>,
+) -> impl Iterator<Item = (TransactionSigned, Vec<B256>)> {
+    txs.into_iter()
+        .filter(|tx| tx.ty() == BLOB_TX_TYPE)
+        .map(|tx| {
+            let hashes = tx.blob_versioned_hashes().unwrap_or_default().to_vec();
+            (tx, hashes)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_consensus::{Signed, TxEip1559, TxEip4844, TxLegacy};
+    use alloy_primitives::{Signature, b256};
+
+    use super::*;
+
+    fn legacy() -> TransactionSigned {
+        Signed::new_unhashed(TxLegacy::default(), Signature::test_signature()).into()
+    }
+
+    fn eip1559() -> TransactionSigned {
+        Signed::new_unhashed(TxEip1559::default(), Signature::test_signature()).into()
+    }
+
+    fn blob(hashes: Vec<B256>) -> TransactionSigned {
+        let tx = TxEip4844 {
+            blob_versioned_hashes: hashes,
+            ..Default::default()
+        };
+        Signed::new_unhashed(tx, Signature::test_signature()).into()
+    }
+
+    #[test]
+    fn test_filter_blob_transactions() {
+        let h1 = b256!("0x0100000000000000000000000000000000000000000000000000000000000001");
+        let h2 = b256!("0x0100000000000000000000000000000000000000000000000000000000000002");
+
+        // 模拟 provider.transactions_by_block_range 返回的三个块
+        let blocks = vec![
+            vec![legacy(), blob(vec![h1]), eip1559()],
+            vec![],
+            vec![blob(vec![h1, h2]), legacy()],
+        ];
+
+        let found: Vec<(TransactionSigned, Vec<B256>)> =
+            blob_transactions(blocks.into_iter().flatten()).collect();
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|(tx, _)| tx.is_eip4844()));
+        assert_eq!(found[0].1, vec![h1]);
+        assert_eq!(found[1].1, vec![h1, h2]);
+
+        // 没有 blob 交易的块
+        assert_eq!(blob_transactions([legacy(), eip1559()]).count(), 0);
+    }
+}
@@ -0,0 +1,168 @@
+// --- 给 BlockReader 套一层 LRU 缓存 ---
+//
+// provider.block(..) 每次都要在 MDBX 里查 header、body、ommers、withdrawals 好几张表再拼起来，
+// provider 本身不做任何缓存（见 main.rs 开头的注释）。反复查询最近几个块的应用，自己在外面缓存一下就行。
+//
+// 缓存的 key 是 block hash：hash 唯一确定一个块，reorg 之后同一个高度换了块，hash 也跟着变，
+// 不会读到旧块。按高度查时先用 block_hash(number) 查一下 CanonicalHeaders 表（很便宜），再按 hash 查缓存
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use alloy_primitives::B256;
+use lru::LruCache;
+use reth_ethereum::provider::BlockReader;
+
+/// 带 LRU 缓存的 BlockReader，clone 出来的副本共享同一个缓存
+#[derive(Debug, Clone)]
+pub struct CachingBlockProvider<P: BlockReader> {
+    inner: P,
+    cache: BlockCache<P::Block>,
+}
+
+impl<P> CachingBlockProvider<P>
+where
+    P: BlockReader,
+    P::Block: Clone,
+{
+    /// 最多缓存 `capacity` 个块
+    pub fn new(inner: P, capacity: NonZeroUsize) -> Self {
+        Self {
+            inner,
+            cache: BlockCache::new(capacity),
+        }
+    }
+
+    pub fn block_by_hash(&self, hash: B256) -> eyre::Result<Option<P::Block>> {
+        self.cache
+            .get_or_fetch(hash, || Ok(self.inner.block_by_hash(hash)?))
+    }
+
+    pub fn block_by_number(&self, number: u64) -> eyre::Result<Option<P::Block>> {
+        let Some(hash) = self.inner.block_hash(number)? else {
+            return Ok(None);
+        };
+        self.block_by_hash(hash)
+    }
+
+    /// hits / (hits + misses)，还没有查询过时为 0
+    pub fn hit_rate(&self) -> f64 {
+        self.cache.hit_rate()
+    }
+}
+
+#[derive(Debug)]
+struct BlockCache<B> {
+    blocks: Arc<Mutex<LruCache<B256, B>>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+// derive(Clone) 会要求 B: Clone，这里只是 clone 几个 Arc
+impl<B> Clone for BlockCache<B> {
+    fn clone(&self) -> Self {
+        Self {
+            blocks: self.blocks.clone(),
+            hits: self.hits.clone(),
+            misses: self.misses.clone(),
+        }
+    }
+}
+
+impl<B: Clone> BlockCache<B> {
+    fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            blocks: Arc::new(Mutex::new(LruCache::new(capacity))),
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn get_or_fetch(
+        &self,
+        hash: B256,
+        fetch: impl FnOnce() -> eyre::Result<Option<B>>,
+    ) -> eyre::Result<Option<B>> {
+        if let Some(block) = self.blocks.lock().unwrap().get(&hash) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(Some(block.clone()));
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // 读库的时候不拿锁，别的线程查缓存里已有的块不用等这次 IO
+        let block = fetch()?;
+        if let Some(block) = &block {
+            self.blocks.lock().unwrap().put(hash, block.clone());
+        }
+        Ok(block)
+    }
+
+    fn hit_rate(&self) -> f64 {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let total = hits + self.misses.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        hits as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hit_rate() {
+        let cache = BlockCache::new(NonZeroUsize::new(16).unwrap());
+        let mut fetched = 0;
+
+        // 10 个不同的块，一共查 100 次
+        for i in 0..100u8 {
+            let hash = B256::with_last_byte(i % 10);
+            let block = cache
+                .get_or_fetch(hash, || {
+                    fetched += 1;
+                    Ok(Some(format!("block {}", i % 10)))
+                })
+                .unwrap();
+            assert_eq!(block.unwrap(), format!("block {}", i % 10));
+        }
+
+        // 只有第一次查每个块时读了库
+        assert_eq!(fetched, 10);
+        assert!(cache.hit_rate() >= 0.9);
+
+        // 不存在的块不缓存，每次都算 miss
+        for _ in 0..2 {
+            let missing = cache.get_or_fetch(B256::repeat_byte(0xff), || Ok(None));
+            assert!(missing.unwrap().is_none());
+        }
+        assert_eq!(cache.misses.load(Ordering::Relaxed), 12);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = BlockCache::new(NonZeroUsize::new(2).unwrap());
+        let fetch = |n: u8| move || Ok(Some(n));
+
+        cache
+            .get_or_fetch(B256::with_last_byte(1), fetch(1))
+            .unwrap();
+        cache
+            .get_or_fetch(B256::with_last_byte(2), fetch(2))
+            .unwrap();
+        // 1 刚被访问过，放进 3 时挤掉的是 2
+        cache
+            .get_or_fetch(B256::with_last_byte(1), fetch(1))
+            .unwrap();
+        cache
+            .get_or_fetch(B256::with_last_byte(3), fetch(3))
+            .unwrap();
+
+        let mut blocks = cache.blocks.lock().unwrap();
+        assert!(blocks.contains(&B256::with_last_byte(1)));
+        assert!(!blocks.contains(&B256::with_last_byte(2)));
+        assert!(blocks.get(&B256::with_last_byte(3)).is_some());
+    }
+}
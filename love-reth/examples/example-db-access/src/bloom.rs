@@ -0,0 +1,155 @@
+// --- SIMD 版的 logs bloom 匹配 ---
+//
+// logs bloom 是 2048 bit = 256 字节。判断“filter 里的某个地址 / topic 可能在这个块里”就是：
+//   (block_bloom & mask) == mask      mask = 这个地址 / topic 自己算出来的 bloom（只有 3 个 bit 是 1）
+// 标量写法一次比 1 个字节，要比 256 次；用 wide::u8x32 一次比 32 字节，8 次就够了。
+// wide 在稳定版 Rust 上用 SSE/AVX/NEON 实现，不需要 nightly 的 std::simd。
+//
+// 匹配规则和 `Filter::matches_bloom` 一致：
+//   地址集合里任意一个命中 && 每个 topic 位置的集合里任意一个命中；空集合表示不限制
+
+use alloy_primitives::{Bloom, BloomInput};
+use reth_ethereum::rpc::eth::primitives::Filter;
+use wide::u8x32;
+
+pub const BLOOM_BYTES: usize = 256;
+
+pub type RawBloom = [u8; BLOOM_BYTES];
+
+/// 把 `Filter` 预先展开成若干组 mask，之后每个块只做按位比较
+pub struct BloomFilterSIMD {
+    /// 组与组之间是 AND，组内是 OR；空组表示这一项不限制
+    groups: Vec<Vec<RawBloom>>,
+}
+
+impl BloomFilterSIMD {
+    pub fn from_filter(filter: &Filter) -> Self {
+        let address = filter
+            .address
+            .iter()
+            .map(|addr| mask_of(addr.as_slice()))
+            .collect();
+        let topics = filter
+            .topics
+            .iter()
+            .map(|topic| topic.iter().map(|t| mask_of(t.as_slice())).collect());
+
+        Self::from_groups(std::iter::once(address).chain(topics).collect())
+    }
+
+    pub fn from_groups(groups: Vec<Vec<RawBloom>>) -> Self {
+        Self { groups }
+    }
+
+    /// 和 `Filter::matches_bloom` 语义相同，按 32 字节一组做 SIMD 比较
+    pub fn matches_bloom(&self, bloom: &RawBloom) -> bool {
+        self.matches_with(bloom, contains_simd)
+    }
+
+    /// 标量版本，用来对照正确性和做 benchmark
+    #[allow(dead_code)]
+    pub fn matches_bloom_scalar(&self, bloom: &RawBloom) -> bool {
+        self.matches_with(bloom, contains_scalar)
+    }
+
+    fn matches_with(&self, bloom: &RawBloom, contains: fn(&RawBloom, &RawBloom) -> bool) -> bool {
+        self.groups
+            .iter()
+            .all(|group| group.is_empty() || group.iter().any(|mask| contains(bloom, mask)))
+    }
+}
+
+/// 一个地址 / topic 单独算出来的 bloom（3 个 bit）
+pub fn mask_of(input: &[u8]) -> RawBloom {
+    let mut bloom = Bloom::ZERO;
+    bloom.accrue(BloomInput::Raw(input));
+    bloom.0.0
+}
+
+/// mask 的每一个 bit 在 bloom 里都是 1
+pub fn contains_scalar(bloom: &RawBloom, mask: &RawBloom) -> bool {
+    bloom.iter().zip(mask).all(|(b, m)| b & m == *m)
+}
+
+pub fn contains_simd(bloom: &RawBloom, mask: &RawBloom) -> bool {
+    // as_chunks 切出 8 个 [u8; 32]，每块装进一个 256 bit 的向量寄存器
+    let (blooms, _) = bloom.as_chunks::<32>();
+    let (masks, _) = mask.as_chunks::<32>();
+
+    blooms.iter().zip(masks).all(|(b, m)| {
+        let m = u8x32::from(*m);
+        (u8x32::from(*b) & m) == m
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{Address, B256, keccak256};
+
+    use super::*;
+
+    /// 简单的 xorshift，生成稀疏程度不同的随机 bloom
+    fn random_blooms(n: usize, density: u32) -> Vec<RawBloom> {
+        let mut x = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x
+        };
+        (0..n)
+            .map(|_| {
+                let mut bloom = [0u8; BLOOM_BYTES];
+                for _ in 0..density {
+                    let bit = (next() % 2048) as usize;
+                    bloom[bit / 8] |= 1 << (bit % 8);
+                }
+                bloom
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_simd_matches_filter() {
+        let contract = Address::random();
+        let other = Address::random();
+        let transfer = keccak256("Transfer(address,address,uint256)");
+        let filter = Filter::new()
+            .address(vec![contract, other])
+            .event_signature(transfer);
+        let simd = BloomFilterSIMD::from_filter(&filter);
+
+        // 块里有 contract 和 Transfer：命中
+        let mut bloom = Bloom::ZERO;
+        bloom.accrue(BloomInput::Raw(contract.as_slice()));
+        bloom.accrue(BloomInput::Raw(transfer.as_slice()));
+        assert!(filter.matches_bloom(bloom));
+        assert!(simd.matches_bloom(&bloom.0.0));
+
+        // 只有地址没有 topic：不命中
+        let mut bloom = Bloom::ZERO;
+        bloom.accrue(BloomInput::Raw(other.as_slice()));
+        assert!(!filter.matches_bloom(bloom));
+        assert!(!simd.matches_bloom(&bloom.0.0));
+
+        // 随机 bloom 上和 Filter::matches_bloom 逐个对照
+        for density in [50, 300, 1500] {
+            for raw in random_blooms(2000, density) {
+                let expected = filter.matches_bloom(Bloom::from(raw));
+                assert_eq!(simd.matches_bloom(&raw), expected);
+                assert_eq!(simd.matches_bloom_scalar(&raw), expected);
+            }
+        }
+
+        // 没有任何条件的 filter 什么都匹配
+        let any = BloomFilterSIMD::from_filter(&Filter::new());
+        assert!(any.matches_bloom(&[0; BLOOM_BYTES]));
+        assert_eq!(
+            mask_of(B256::ZERO.as_slice())
+                .iter()
+                .map(|b| b.count_ones())
+                .sum::<u32>(),
+            3
+        );
+    }
+}
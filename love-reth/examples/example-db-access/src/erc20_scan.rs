@@ -0,0 +1,249 @@
+// --- 扫一段区块里某个 ERC-20 代币的所有 Transfer ---
+//
+// 和 receipts_provider_example 里单个块的写法一样，分三步：
+//   1. 一次性把 start..=end 的区块头读出来（sealed_headers_range 顺着 Headers 表往后走，比逐个 header_by_number 便宜）
+//   2. 用 header 里的 logs bloom 预过滤：代币地址和 Transfer 事件签名都在 bloom 里的块才可能有结果
+//   3. 命中的块并行读 receipts + 交易（receipts 里没有 tx hash，要靠同一个下标对上交易）
+//
+// MDBX 的读是同步阻塞的，所以用 JoinSet::spawn_blocking 丢到阻塞线程池里，不占 tokio 的 worker。
+// 每个任务都要拿到一个 'static 的 provider，传进来的应该是 ProviderFactory 这种 clone 很便宜、
+// 每次查询自己开只读事务的类型，而不是绑在某个事务上的 DatabaseProvider
+//
+// Transfer(address indexed from, address indexed to, uint256 value) 的日志布局：
+//   topics = [keccak256("Transfer(address,address,uint256)"), from, to]   地址左边补 0 到 32 字节
+//   data   = value，32 字节大端
+// ERC-721 的 Transfer 签名一模一样，只是 tokenId 也是 indexed（4 个 topic、data 为空），按布局区分开
+
+use alloy_primitives::{Address, B256, Log, U256, keccak256};
+use reth_ethereum::primitives::AlloyBlockHeader;
+use reth_ethereum::provider::TransactionsProvider;
+use reth_ethereum::rpc::eth::primitives::Filter;
+use reth_ethereum::storage::{HeaderProvider, ReceiptProvider};
+use reth_ethereum::{Receipt, TransactionSigned};
+use tokio::task::JoinSet;
+
+use crate::bloom::BloomFilterSIMD;
+
+/// 一笔 ERC-20 转账
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+    pub block_num: u64,
+    pub tx_hash: B256,
+    pub from: Address,
+    pub to: Address,
+    pub amount: U256,
+}
+
+/// 找出 `start..=end` 里 `token_addr` 发出的全部 Transfer，按区块、块内日志顺序排列
+pub async fn scan_blocks_for_erc20_transfers<P>(
+    provider: P,
+    token_addr: Address,
+    start: u64,
+    end: u64,
+) -> eyre::Result<Vec<Transfer>>
+where
+    P: HeaderProvider
+        + ReceiptProvider<Receipt = Receipt>
+        + TransactionsProvider<Transaction = TransactionSigned>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    let filter = Filter::new()
+        .address(token_addr)
+        .event_signature(transfer_topic());
+    let bloom_filter = BloomFilterSIMD::from_filter(&filter);
+
+    // 1 + 2：一次读出所有区块头，只留下 bloom 命中的块号
+    let candidates: Vec<u64> = provider
+        .sealed_headers_range(start..=end)?
+        .iter()
+        .filter(|header| bloom_filter.matches_bloom(&header.logs_bloom().0.0))
+        .map(|header| header.number())
+        .collect();
+
+    // 3：命中的块并行读 receipts 和交易
+    let mut tasks = JoinSet::new();
+    for block_num in candidates {
+        let provider = provider.clone();
+        tasks.spawn_blocking(move || -> eyre::Result<Vec<Transfer>> {
+            let receipts = provider
+                .receipts_by_block(block_num.into())?
+                .ok_or(eyre::eyre!("receipts of block {block_num} not found"))?;
+            let txs = provider
+                .transactions_by_block(block_num.into())?
+                .ok_or(eyre::eyre!("transactions of block {block_num} not found"))?;
+            let tx_hashes: Vec<B256> = txs.iter().map(|tx| *tx.tx_hash()).collect();
+            block_transfers(block_num, token_addr, &receipts, &tx_hashes)
+        });
+    }
+
+    // 任务完成的顺序是乱的，收齐之后再按块号排；同一个块里的顺序在 block_transfers 里已经是对的
+    let mut transfers = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        transfers.extend(result??);
+    }
+    transfers.sort_by_key(|t| t.block_num);
+    Ok(transfers)
+}
+
+/// keccak256("Transfer(address,address,uint256)")
+pub fn transfer_topic() -> B256 {
+    keccak256("Transfer(address,address,uint256)")
+}
+
+/// 一个块里 `token` 的全部 Transfer；`tx_hashes[i]` 是 `receipts[i]` 对应的交易
+fn block_transfers(
+    block_num: u64,
+    token: Address,
+    receipts: &[Receipt],
+    tx_hashes: &[B256],
+) -> eyre::Result<Vec<Transfer>> {
+    eyre::ensure!(
+        receipts.len() == tx_hashes.len(),
+        "block {block_num}: {} receipts but {} transactions",
+        receipts.len(),
+        tx_hashes.len()
+    );
+
+    let transfers = receipts
+        .iter()
+        .zip(tx_hashes)
+        .flat_map(|(receipt, tx_hash)| {
+            receipt
+                .logs
+                .iter()
+                .filter(|log| log.address == token)
+                .filter_map(decode_transfer)
+                .map(|(from, to, amount)| Transfer {
+                    block_num,
+                    tx_hash: *tx_hash,
+                    from,
+                    to,
+                    amount,
+                })
+        })
+        .collect();
+    Ok(transfers)
+}
+
+/// 按 ERC-20 的布局解出 (from, to, amount)，不是这个布局的日志返回 None
+fn decode_transfer(log: &Log) -> Option<(Address, Address, U256)> {
+    let [topic0, from, to] = log.topics() else {
+        return None;
+    };
+    if *topic0 != transfer_topic() || log.data.data.len() != 32 {
+        return None;
+    }
+    Some((
+        Address::from_word(*from),
+        Address::from_word(*to),
+        U256::from_be_slice(&log.data.data),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::Bytes;
+
+    use super::*;
+
+    fn transfer_log(token: Address, from: Address, to: Address, amount: u64) -> Log {
+        Log::new_unchecked(
+            token,
+            vec![transfer_topic(), from.into_word(), to.into_word()],
+            Bytes::copy_from_slice(&U256::from(amount).to_be_bytes::<32>()),
+        )
+    }
+
+    #[test]
+    fn test_decode_transfer() {
+        let token = Address::with_last_byte(0xee);
+        let alice = Address::with_last_byte(1);
+        let bob = Address::with_last_byte(2);
+
+        let log = transfer_log(token, alice, bob, 1_000);
+        assert_eq!(
+            decode_transfer(&log),
+            Some((alice, bob, U256::from(1_000u64)))
+        );
+
+        // ERC-721：tokenId 也是 indexed，4 个 topic，data 为空
+        let nft = Log::new_unchecked(
+            token,
+            vec![
+                transfer_topic(),
+                alice.into_word(),
+                bob.into_word(),
+                B256::with_last_byte(7),
+            ],
+            Bytes::new(),
+        );
+        assert_eq!(decode_transfer(&nft), None);
+
+        // 别的事件
+        let approval = Log::new_unchecked(
+            token,
+            vec![
+                keccak256("Approval(address,address,uint256)"),
+                alice.into_word(),
+                bob.into_word(),
+            ],
+            log.data.data.clone(),
+        );
+        assert_eq!(decode_transfer(&approval), None);
+    }
+
+    #[test]
+    fn test_block_transfers() {
+        let token = Address::with_last_byte(0xee);
+        let other_token = Address::with_last_byte(0xdd);
+        let (alice, bob, carol) = (
+            Address::with_last_byte(1),
+            Address::with_last_byte(2),
+            Address::with_last_byte(3),
+        );
+
+        let receipts = vec![
+            Receipt {
+                logs: vec![
+                    transfer_log(token, alice, bob, 10),
+                    transfer_log(other_token, alice, bob, 99),
+                ],
+                ..Default::default()
+            },
+            // 没有日志的交易
+            Receipt::default(),
+            Receipt {
+                logs: vec![transfer_log(token, bob, carol, 5)],
+                ..Default::default()
+            },
+        ];
+        let tx_hashes: Vec<B256> = (1..=3).map(B256::with_last_byte).collect();
+
+        let transfers = block_transfers(42, token, &receipts, &tx_hashes).unwrap();
+        assert_eq!(
+            transfers,
+            vec![
+                Transfer {
+                    block_num: 42,
+                    tx_hash: tx_hashes[0],
+                    from: alice,
+                    to: bob,
+                    amount: U256::from(10u64),
+                },
+                Transfer {
+                    block_num: 42,
+                    tx_hash: tx_hashes[2],
+                    from: bob,
+                    to: carol,
+                    amount: U256::from(5u64),
+                },
+            ]
+        );
+
+        // receipts 和交易数量对不上说明数据有问题
+        assert!(block_transfers(42, token, &receipts, &tx_hashes[..2]).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+// --- EIP-1559 费用市场分析 ---
+//
+// London 之后每笔交易付的 gas 单价 = base_fee + priority_fee：base_fee 被销毁，priority_fee 给出块者。
+// 交易里写的是两个上限 max_fee_per_gas / max_priority_fee_per_gas，实际给出块者的部分是
+//   effective_priority_fee = min(max_priority_fee_per_gas, max_fee_per_gas - base_fee)
+// legacy / 2930 交易只有一个 gas_price，相当于两个上限都是 gas_price，小费就是 gas_price - base_fee。
+// London 之前的块没有 base_fee，按 0 算，整个 gas_price 都是小费。
+//
+// receipts 里只有 gas_used / logs，没有价格字段，所以除了区块头还要把块里的交易读出来
+
+use alloy_consensus::Transaction;
+use reth_ethereum::TransactionSigned;
+use reth_ethereum::primitives::AlloyBlockHeader;
+use reth_ethereum::provider::TransactionsProvider;
+use reth_ethereum::storage::{HeaderProvider, ReceiptProvider};
+
+/// 一个块的费用统计，单位都是 wei
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeeMarketStats {
+    pub base_fee_per_gas: u64,
+    pub avg_priority_fee: u64,
+    pub max_priority_fee: u64,
+    pub min_priority_fee: u64,
+    /// 95 分位的小费（nearest-rank：排序后取第 ceil(0.95 * n) 个）
+    pub percentile_95_priority_fee: u64,
+    pub tx_count: u64,
+}
+
+/// 统计 `block_num` 这个块里交易的小费分布
+pub fn analyze_fee_market<P>(provider: &P, block_num: u64) -> eyre::Result<FeeMarketStats>
+where
+    P: ReceiptProvider + HeaderProvider + TransactionsProvider<Transaction = TransactionSigned>,
+{
+    let header = provider
+        .header_by_number(block_num)?
+        .ok_or(eyre::eyre!("header {block_num} not found"))?;
+    let txs = provider
+        .transactions_by_block(block_num.into())?
+        .ok_or(eyre::eyre!("transactions of block {block_num} not found"))?;
+
+    fee_stats(header.base_fee_per_gas().unwrap_or_default(), &txs)
+}
+
+fn fee_stats<T: Transaction>(base_fee: u64, txs: &[T]) -> eyre::Result<FeeMarketStats> {
+    let mut tips = txs
+        .iter()
+        .map(|tx| {
+            // 已经上链的交易 max_fee 一定不低于 base_fee，否则是数据有问题
+            let tip = tx
+                .effective_tip_per_gas(base_fee)
+                .ok_or(eyre::eyre!("max fee below base fee {base_fee}"))?;
+            Ok(u64::try_from(tip).unwrap_or(u64::MAX))
+        })
+        .collect::<eyre::Result<Vec<u64>>>()?;
+
+    if tips.is_empty() {
+        return Ok(FeeMarketStats {
+            base_fee_per_gas: base_fee,
+            ..Default::default()
+        });
+    }
+
+    tips.sort_unstable();
+    let n = tips.len();
+    // 求和用 u128，避免一大堆小费加起来溢出
+    let sum: u128 = tips.iter().map(|&tip| tip as u128).sum();
+    // nearest-rank：第 ceil(0.95 * n) 个，下标再减 1
+    let p95 = (n * 95).div_ceil(100) - 1;
+
+    Ok(FeeMarketStats {
+        base_fee_per_gas: base_fee,
+        avg_priority_fee: (sum / n as u128) as u64,
+        max_priority_fee: tips[n - 1],
+        min_priority_fee: tips[0],
+        percentile_95_priority_fee: tips[p95],
+        tx_count: n as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_consensus::{TxEip1559, TxLegacy};
+
+    use super::*;
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn eip1559(max_fee: u128, max_priority_fee: u128) -> TxEip1559 {
+        TxEip1559 {
+            max_fee_per_gas: max_fee,
+            max_priority_fee_per_gas: max_priority_fee,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fee_stats() {
+        let base_fee = (10 * GWEI) as u64;
+        // 小费 1..=20 gwei：max_priority_fee 是真正的上限
+        let txs: Vec<TxEip1559> = (1..=20)
+            .map(|tip| eip1559(100 * GWEI, tip * GWEI))
+            .collect();
+        let stats = fee_stats(base_fee, &txs).unwrap();
+
+        assert_eq!(stats.base_fee_per_gas, base_fee);
+        assert_eq!(stats.tx_count, 20);
+        assert_eq!(stats.min_priority_fee, GWEI as u64);
+        assert_eq!(stats.max_priority_fee, (20 * GWEI) as u64);
+        // (1 + 20) / 2 = 10.5 gwei
+        assert_eq!(stats.avg_priority_fee, (21 * GWEI / 2) as u64);
+        // ceil(0.95 * 20) = 19
+        assert_eq!(stats.percentile_95_priority_fee, (19 * GWEI) as u64);
+    }
+
+    #[test]
+    fn test_tip_capped_by_max_fee() {
+        let base_fee = (10 * GWEI) as u64;
+        // max_fee - base_fee = 2 gwei < max_priority_fee，小费只有 2 gwei
+        let capped = fee_stats(base_fee, &[eip1559(12 * GWEI, 5 * GWEI)]).unwrap();
+        assert_eq!(capped.max_priority_fee, (2 * GWEI) as u64);
+        assert_eq!(capped.percentile_95_priority_fee, (2 * GWEI) as u64);
+
+        // legacy 交易：gas_price - base_fee
+        let legacy = TxLegacy {
+            gas_price: 15 * GWEI,
+            ..Default::default()
+        };
+        let stats = fee_stats(base_fee, &[legacy]).unwrap();
+        assert_eq!(stats.avg_priority_fee, (5 * GWEI) as u64);
+
+        // max_fee 连 base_fee 都不够的交易不可能上链
+        assert!(fee_stats(base_fee, &[eip1559(GWEI, GWEI)]).is_err());
+
+        // 空块
+        let empty = fee_stats::<TxLegacy>(base_fee, &[]).unwrap();
+        assert_eq!(empty.tx_count, 0);
+        assert_eq!(empty.base_fee_per_gas, base_fee);
+    }
+}
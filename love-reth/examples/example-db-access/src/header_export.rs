@@ -0,0 +1,107 @@
+// --- 区块头导出 / 导入（给轻客户端用）---
+//
+// 文件格式和 geth export 一样：把每个 RLP 编码后的记录直接首尾相接写进文件，不加任何额外的分隔符。
+// RLP 本身就是带长度前缀的（列表头里写着 payload 有多长），所以读的时候一条一条 decode，
+// 每次 decode 消耗掉正好一条记录的字节，剩下的就是下一条。
+//
+// header 的 RLP 里不包含 hash，hash 就是 keccak256(rlp(header))，导入时重新算一遍就能和导出前对上
+
+use std::path::Path;
+
+use alloy_consensus::Header;
+use alloy_rlp::{Decodable, Encodable};
+use reth_ethereum::primitives::SealedHeader;
+use reth_ethereum::storage::HeaderProvider;
+
+/// 把 [start, end] 的区块头导出到 `path`，同时返回每个区块头的 RLP 编码
+pub fn export_headers_as_rlp<P>(
+    provider: &P,
+    start: u64,
+    end: u64,
+    path: &Path,
+) -> eyre::Result<Vec<Vec<u8>>>
+where
+    P: HeaderProvider<Header = Header>,
+{
+    let headers = provider.sealed_headers_range(start..=end)?;
+    let records = encode_headers(&headers);
+    std::fs::write(path, records.concat())?;
+    Ok(records)
+}
+
+/// 读回 `export_headers_as_rlp`（或 geth）导出的文件
+pub fn import_headers_from_rlp(path: &Path) -> eyre::Result<Vec<SealedHeader>> {
+    decode_headers(&std::fs::read(path)?)
+}
+
+fn encode_headers(headers: &[SealedHeader]) -> Vec<Vec<u8>> {
+    headers
+        .iter()
+        .map(|header| {
+            let mut out = Vec::with_capacity(header.header().length());
+            header.header().encode(&mut out);
+            out
+        })
+        .collect()
+}
+
+fn decode_headers(mut buf: &[u8]) -> eyre::Result<Vec<SealedHeader>> {
+    let mut headers = Vec::new();
+    while !buf.is_empty() {
+        // decode 会把 buf 往前推进一条记录
+        let header = Header::decode(&mut buf)?;
+        headers.push(SealedHeader::seal_slow(header));
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::B256;
+
+    use super::*;
+
+    /// 10 个首尾相连的区块头
+    fn chain(n: u64) -> Vec<SealedHeader> {
+        let mut parent_hash = B256::ZERO;
+        (0..n)
+            .map(|number| {
+                let header = SealedHeader::seal_slow(Header {
+                    number,
+                    parent_hash,
+                    gas_limit: 30_000_000,
+                    gas_used: number * 21_000,
+                    timestamp: 1_700_000_000 + number * 12,
+                    base_fee_per_gas: Some(7),
+                    ..Default::default()
+                });
+                parent_hash = header.hash();
+                header
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rlp_round_trip() {
+        let headers = chain(10);
+        let records = encode_headers(&headers);
+        assert_eq!(records.len(), 10);
+
+        let path = std::env::temp_dir().join(format!("headers-{}.rlp", std::process::id()));
+        std::fs::write(&path, records.concat()).unwrap();
+        let imported = import_headers_from_rlp(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(imported.len(), headers.len());
+        for (a, b) in headers.iter().zip(&imported) {
+            assert_eq!(a.hash(), b.hash());
+            assert_eq!(a.header(), b.header());
+        }
+        // 导入后的 hash 链依然连得上
+        assert!(imported.windows(2).all(|w| w[1].parent_hash == w[0].hash()));
+
+        // 截断的文件：最后一条记录不完整，报错而不是悄悄丢掉
+        let bytes = records.concat();
+        assert!(decode_headers(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+// --- 带重试的 HTTP 客户端：调用外部服务（行情、区块浏览器 API 之类）用 ---
+//
+// reqwest 负责真正发请求，tower::retry::Retry 负责“失败了要不要再来一次”：
+//   Retry 每次收到结果都会问 Policy：要重试吗？要的话先等 Policy 给的 future（退避），再用 clone 出来的请求重发
+//   Policy 本身会随每个请求 clone 一份，所以 attempt 计数是每个请求独立的
+//
+// 重试规则：连接失败 / 超时 / 5xx 才重试，最多 3 次，退避 100ms、200ms、400ms
+// 4xx 是请求本身有问题，重试也没用
+//
+// main 里用它调外部节点的 JSON-RPC（eth_blockNumber），看本地数据库落后多少
+
+use std::time::Duration;
+
+use reqwest::{Client, Request, Response};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tower::retry::{Policy, Retry};
+use tower::util::BoxCloneService;
+use tower::{ServiceExt, service_fn};
+
+pub const MAX_RETRIES: usize = 3;
+pub const BASE_BACKOFF: Duration = Duration::from_millis(100);
+
+/// 第 attempt 次（从 0 开始）失败之后要不要重试
+pub fn should_retry(attempt: usize, result: &Result<Response, reqwest::Error>) -> bool {
+    if attempt >= MAX_RETRIES {
+        return false;
+    }
+    match result {
+        Ok(resp) => resp.status().is_server_error(),
+        Err(e) => e.is_connect() || e.is_timeout(),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    attempt: usize,
+    base_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(base_backoff: Duration) -> Self {
+        Self {
+            attempt: 0,
+            base_backoff,
+        }
+    }
+}
+
+impl Policy<Request, Response, reqwest::Error> for RetryPolicy {
+    // 退避就是睡一会儿，Retry 内部会 pin 住这个 future
+    type Future = tokio::time::Sleep;
+
+    fn retry(
+        &mut self,
+        _req: &mut Request,
+        result: &mut Result<Response, reqwest::Error>,
+    ) -> Option<Self::Future> {
+        if !should_retry(self.attempt, result) {
+            return None;
+        }
+        let backoff = self.base_backoff * 2u32.pow(self.attempt as u32);
+        self.attempt += 1;
+        Some(tokio::time::sleep(backoff))
+    }
+
+    fn clone_request(&mut self, req: &Request) -> Option<Request> {
+        // body 是流的时候 clone 不了，这种请求就只发一次
+        req.try_clone()
+    }
+}
+
+#[derive(Clone)]
+pub struct RetryingClient {
+    http: Client,
+    // Retry<RetryPolicy, ServiceFn<闭包>> 写不出类型名，装箱成 BoxCloneService
+    service: BoxCloneService<Request, Response, reqwest::Error>,
+}
+
+impl RetryingClient {
+    pub fn new() -> Self {
+        Self::with_backoff(BASE_BACKOFF)
+    }
+
+    pub fn with_backoff(base_backoff: Duration) -> Self {
+        let http = Client::new();
+        let inner = {
+            let http = http.clone();
+            service_fn(move |req: Request| http.execute(req))
+        };
+        let service = BoxCloneService::new(Retry::new(RetryPolicy::new(base_backoff), inner));
+        Self { http, service }
+    }
+
+    // GET 接口（行情、区块浏览器之类）main 里还没用到
+    #[allow(dead_code)]
+    pub async fn get_json<T: DeserializeOwned>(&self, url: &str) -> eyre::Result<T> {
+        self.send_json(self.http.get(url).build()?).await
+    }
+
+    pub async fn post_json<T: Serialize, R: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &T,
+    ) -> eyre::Result<R> {
+        self.send_json(self.http.post(url).json(body).build()?)
+            .await
+    }
+
+    /// 外部节点的最新高度，`url` 是它的 JSON-RPC 地址
+    pub async fn eth_block_number(&self, url: &str) -> eyre::Result<u64> {
+        #[derive(Deserialize)]
+        struct RpcResponse {
+            // "0x" 开头的十六进制
+            result: String,
+        }
+
+        let req = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_blockNumber",
+            "params": [],
+        });
+        let resp: RpcResponse = self.post_json(url, &req).await?;
+        let digits = resp.result.strip_prefix("0x").ok_or(eyre::eyre!(
+            "block number is not a hex quantity: {}",
+            resp.result
+        ))?;
+        Ok(u64::from_str_radix(digits, 16)?)
+    }
+
+    async fn send_json<R: DeserializeOwned>(&self, req: Request) -> eyre::Result<R> {
+        // 重试用完还是 5xx 的话，这里会变成错误
+        let resp = self
+            .service
+            .clone()
+            .oneshot(req)
+            .await?
+            .error_for_status()?;
+        Ok(resp.json().await?)
+    }
+}
+
+impl Default for RetryingClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Price {
+        symbol: String,
+        price: u64,
+    }
+
+    fn client() -> RetryingClient {
+        // 测试里把退避调小，不然要多等 700ms
+        RetryingClient::with_backoff(Duration::from_millis(1))
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let mut server = mockito::Server::new_async().await;
+        // 先返回两次 503，第三次成功；mockito 按注册顺序匹配，用完 expect 次数的 mock 会让给下一个
+        let failing = server
+            .mock("GET", "/price")
+            .with_status(503)
+            .expect(2)
+            .create_async()
+            .await;
+        let ok = server
+            .mock("GET", "/price")
+            .with_status(200)
+            .with_body(r#"{"symbol":"ETH","price":3000}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let price: Price = client()
+            .get_json(&format!("{}/price", server.url()))
+            .await
+            .unwrap();
+        assert_eq!(price.price, 3000);
+        failing.assert_async().await;
+        ok.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_give_up_after_max_retries() {
+        let mut server = mockito::Server::new_async().await;
+        // 1 次原始请求 + 3 次重试
+        let failing = server
+            .mock("POST", "/orders")
+            .with_status(500)
+            .expect(MAX_RETRIES + 1)
+            .create_async()
+            .await;
+
+        let body = Price {
+            symbol: "ETH".into(),
+            price: 1,
+        };
+        let res: eyre::Result<Price> = client()
+            .post_json(&format!("{}/orders", server.url()), &body)
+            .await;
+        assert!(res.is_err());
+        failing.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_on_client_error() {
+        let mut server = mockito::Server::new_async().await;
+        let not_found = server
+            .mock("GET", "/missing")
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let res: eyre::Result<Price> = client()
+            .get_json(&format!("{}/missing", server.url()))
+            .await;
+        assert!(res.is_err());
+        not_found.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_eth_block_number() {
+        let mut server = mockito::Server::new_async().await;
+        let rpc = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"method":"eth_blockNumber"}"#.into(),
+            ))
+            .with_status(200)
+            .with_body(r#"{"jsonrpc":"2.0","id":1,"result":"0x1312d00"}"#)
+            .create_async()
+            .await;
+
+        let number = client().eth_block_number(&server.url()).await.unwrap();
+        assert_eq!(number, 20_000_000);
+        rpc.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_connection_error() {
+        // 先占一个端口再关掉，连上去必然 connection refused
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let res: eyre::Result<Price> = client().get_json(&format!("http://{addr}/")).await;
+        assert!(res.is_err());
+
+        let refused = Client::new().get(format!("http://{addr}/")).send().await;
+        assert!(refused.as_ref().unwrap_err().is_connect());
+        assert!(should_retry(0, &refused));
+        assert!(!should_retry(MAX_RETRIES, &refused));
+    }
+}
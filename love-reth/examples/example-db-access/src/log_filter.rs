@@ -0,0 +1,252 @@
+// --- 惰性日志过滤：Bloom 预过滤 + 精确匹配，按需读取 receipts ---
+//
+// receipts_provider_example 里的写法是“先查 bloom，命中了再把整个块的 receipts 读出来逐条 match”，
+// 扫一个块没问题；扫一段区块并且只要前 N 条结果时，eager 的写法会把每个 bloom 命中的块都读一遍，
+// 改成迭代器之后配合 `.take(n)`，拿够了就停，后面的块的 receipts 根本不会去读
+
+use alloy_primitives::{Bloom, Log};
+use reth_ethereum::Receipt;
+use reth_ethereum::primitives::AlloyBlockHeader;
+use reth_ethereum::rpc::eth::primitives::Filter;
+use reth_ethereum::storage::{HeaderProvider, ReceiptProvider};
+
+/// 在一个块已经读出来的 receipts 上惰性地过滤日志，返回借用的 `&Log`
+///
+/// 块的 bloom 不命中时，迭代器一开始就是空的，receipts 一条都不会碰；
+/// 命中时（可能是假阳性）逐条 `filter.matches(log)`，每次 `next()` 只往前走到下一条匹配的日志
+pub struct LogFilterIterator<'a> {
+    filter: &'a Filter,
+    receipts: std::slice::Iter<'a, Receipt>,
+    logs: std::slice::Iter<'a, Log>,
+}
+
+impl<'a> LogFilterIterator<'a> {
+    pub fn new(bloom: Bloom, receipts: &'a [Receipt], filter: &'a Filter) -> Self {
+        let receipts: &'a [Receipt] = if filter.matches_bloom(bloom) {
+            receipts
+        } else {
+            &[]
+        };
+        Self {
+            filter,
+            receipts: receipts.iter(),
+            logs: Default::default(),
+        }
+    }
+}
+
+impl<'a> Iterator for LogFilterIterator<'a> {
+    type Item = &'a Log;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // 先把当前 receipt 剩下的日志过一遍，过完了再换下一个 receipt
+            if let Some(log) = self.logs.find(|log| self.filter.matches(log)) {
+                return Some(log);
+            }
+            self.logs = self.receipts.next()?.logs.iter();
+        }
+    }
+}
+
+/// 查询单个块里匹配 `filter` 的日志
+///
+/// 和 `LogFilterIterator` 不同，这里的 receipts 是从 provider 现读出来的（owned），
+/// 没法返回指向它的 `&'a Log`，所以迭代器产出的是 owned 的 `Log`。
+/// bloom 不命中时直接返回空迭代器，不会去读 receipts 表。
+///
+/// 多个块串起来用时，逐块 `?` 把读库错误抛出去，不要用 `.ok()` 把错误当成空块吞掉：
+///
+/// ```ignore
+/// let mut logs = Vec::new();
+/// for n in from..to {
+///     logs.extend(filtered_logs(&p, n, &f)?.take(100 - logs.len()));
+///     if logs.len() == 100 {
+///         break;
+///     }
+/// }
+/// ```
+///
+/// 凑够数量就停，后面的块不会再读 header / receipts
+pub fn filtered_logs<'a, P>(
+    provider: &'a P,
+    block_num: u64,
+    filter: &'a Filter,
+) -> eyre::Result<impl Iterator<Item = Log> + 'a>
+where
+    P: ReceiptProvider<Receipt = Receipt> + HeaderProvider,
+{
+    // bloom 不命中（或者块不存在）时不去读 receipts 表
+    let receipts = match provider.header_by_number(block_num)? {
+        Some(header) if filter.matches_bloom(header.logs_bloom()) => provider
+            .receipts_by_block(block_num.into())?
+            .unwrap_or_default(),
+        _ => vec![],
+    };
+
+    Ok(matching_logs(receipts, filter))
+}
+
+/// 精确匹配：把 receipts 拆成日志，只留下 `filter.matches` 的
+fn matching_logs(receipts: Vec<Receipt>, filter: &Filter) -> impl Iterator<Item = Log> + '_ {
+    receipts
+        .into_iter()
+        .flat_map(|receipt| receipt.logs)
+        .filter(move |log| filter.matches(log))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::Instant;
+
+    use alloy_primitives::{Address, B256, BloomInput, Bytes, keccak256};
+
+    use super::*;
+
+    /// 模拟的区块：header 里的 bloom + 块内所有 receipts
+    struct MockBlock {
+        bloom: Bloom,
+        receipts: Vec<Receipt>,
+    }
+
+    /// 和 `filtered_logs` 一样的逻辑，只是 receipts 从 `fetch` 拿，`fetch` 只在 bloom 命中时才调用
+    fn block_logs(
+        bloom: Bloom,
+        filter: &Filter,
+        fetch: impl FnOnce() -> Vec<Receipt>,
+    ) -> impl Iterator<Item = Log> + '_ {
+        let receipts = if filter.matches_bloom(bloom) {
+            fetch()
+        } else {
+            vec![]
+        };
+        matching_logs(receipts, filter)
+    }
+
+    fn transfer_filter(contract: Address) -> Filter {
+        Filter::new()
+            .address(contract)
+            .event_signature(keccak256("Transfer(address,address,uint256)"))
+    }
+
+    fn log(address: Address, topic0: B256) -> Log {
+        Log::new_unchecked(address, vec![topic0], Bytes::new())
+    }
+
+    /// 生成 `n` 个块：
+    /// - 偶数块的 bloom 里有 filter 的 address 和 topic0，但日志都是别的合约的 —— 假阳性，50%
+    /// - 奇数块 bloom 不命中
+    /// - 每 `hit_every` 个块里有一个块真的包含一条匹配的日志
+    fn mock_chain(n: usize, hit_every: usize, contract: Address, topic0: B256) -> Vec<MockBlock> {
+        (0..n)
+            .map(|i| {
+                let mut bloom = Bloom::ZERO;
+                let mut logs: Vec<Log> = (0..20)
+                    .map(|_| log(Address::random(), B256::random()))
+                    .collect();
+
+                if i % 2 == 0 {
+                    bloom.accrue(BloomInput::Raw(contract.as_slice()));
+                    bloom.accrue(BloomInput::Raw(topic0.as_slice()));
+                    if i % hit_every == 0 {
+                        logs.push(log(contract, topic0));
+                    }
+                }
+
+                let receipts = logs
+                    .chunks(4)
+                    .map(|logs| Receipt {
+                        logs: logs.to_vec(),
+                        ..Default::default()
+                    })
+                    .collect();
+                MockBlock { bloom, receipts }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_log_filter_iterator() {
+        let contract = Address::random();
+        let filter = transfer_filter(contract);
+        let topic0 = keccak256("Transfer(address,address,uint256)");
+
+        let blocks = mock_chain(10, 2, contract, topic0);
+
+        // bloom 不命中的块：空
+        assert_eq!(
+            LogFilterIterator::new(blocks[1].bloom, &blocks[1].receipts, &filter).count(),
+            0
+        );
+
+        // 真命中的块：恰好一条，并且借用的就是 receipts 里的那条日志
+        let hits: Vec<&Log> =
+            LogFilterIterator::new(blocks[0].bloom, &blocks[0].receipts, &filter).collect();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].address, contract);
+        let last = blocks[0].receipts.last().unwrap().logs.last().unwrap();
+        assert!(std::ptr::eq(hits[0], last));
+
+        // bloom 是空的，就算日志里有匹配的也不看（相信 bloom：bloom 没有假阴性）
+        assert_eq!(
+            LogFilterIterator::new(Bloom::ZERO, &blocks[0].receipts, &filter).count(),
+            0
+        );
+
+        // 整条链：5 个偶数块都是真命中
+        let total = blocks
+            .iter()
+            .flat_map(|b| LogFilterIterator::new(b.bloom, &b.receipts, &filter))
+            .count();
+        assert_eq!(total, 5);
+    }
+
+    /// 10000 个块，50% 假阳性，每 20 个块一条真实匹配，只要前 100 条
+    ///
+    /// `fetch` 里 clone 一遍 receipts，模拟从数据库读出来再解码的开销
+    #[test]
+    #[ignore = "benchmark，手动运行：cargo test --release bench_eager_vs_lazy -- --ignored --nocapture"]
+    fn bench_eager_vs_lazy() {
+        let contract = Address::random();
+        let filter = transfer_filter(contract);
+        let topic0 = keccak256("Transfer(address,address,uint256)");
+        let blocks = mock_chain(10_000, 20, contract, topic0);
+
+        let fetches = Cell::new(0usize);
+        let fetch = |b: &MockBlock| {
+            fetches.set(fetches.get() + 1);
+            b.receipts.clone()
+        };
+
+        // eager：每个块都先把结果 collect 出来，最后再截断到 100 条
+        let start = Instant::now();
+        let mut eager = Vec::new();
+        for b in &blocks {
+            eager.extend(block_logs(b.bloom, &filter, || fetch(b)));
+        }
+        eager.truncate(100);
+        let eager_elapsed = start.elapsed();
+        let eager_fetches = fetches.replace(0);
+
+        // lazy：迭代器链 + take(100)，拿够了就不再往后读
+        let start = Instant::now();
+        let lazy: Vec<Log> = blocks
+            .iter()
+            .flat_map(|b| block_logs(b.bloom, &filter, || fetch(b)))
+            .take(100)
+            .collect();
+        let lazy_elapsed = start.elapsed();
+        let lazy_fetches = fetches.get();
+
+        println!("eager: {eager_elapsed:?}, receipts 读取 {eager_fetches} 次");
+        println!("lazy : {lazy_elapsed:?}, receipts 读取 {lazy_fetches} 次");
+
+        assert_eq!(eager, lazy);
+        assert_eq!(lazy.len(), 100);
+        // bloom 把一半的块挡在外面
+        assert_eq!(eager_fetches, 5_000);
+        // 第 100 条匹配在第 1980 号块，之前的偶数块一共 991 个
+        assert_eq!(lazy_fetches, 991);
+    }
+}
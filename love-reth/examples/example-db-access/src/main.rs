@@ -6,8 +6,18 @@
 
 #![warn(unused_crate_dependencies)]
 
-use alloy_primitives::{Address, B256, keccak256};
+use alloy_primitives::{Address, B256, U256, keccak256};
+use blob_txs::{count_blob_transactions_in_range, get_blob_transactions};
+use block_cache::CachingBlockProvider;
+use bloom::BloomFilterSIMD;
+use erc20_scan::scan_blocks_for_erc20_transfers;
 use eyre::Ok;
+use fee_market::analyze_fee_market;
+use header_export::{export_headers_as_rlp, import_headers_from_rlp};
+use http_client::RetryingClient;
+use log_filter::{LogFilterIterator, filtered_logs};
+use mini_mpt::{KECCAK_EMPTY, TrieAccount};
+use replay::{replay_block, replay_mismatches};
 use reth_ethereum::chainspec::{ChainSpecProvider, EthChainSpec};
 use reth_ethereum::primitives::{AlloyBlockHeader, RecoveredBlock, SealedBlock};
 use reth_ethereum::provider::{
@@ -19,12 +29,29 @@ use reth_ethereum::{
     Block, Receipt, TransactionSigned, chainspec::ChainSpecBuilder, node::EthereumNode,
     primitives::SealedHeader, provider::providers::ReadOnlyConfig, storage::HeaderProvider,
 };
-
-mod rlp_practice;
+use state_export::stream_all_accounts;
+use state_proof::parallel_state_proof;
+
+mod blob_txs;
+mod block_cache;
+mod bloom;
+mod erc20_scan;
+mod fee_market;
+mod header_export;
+mod http_client;
+mod log_filter;
 mod mbdx_compress;
+mod mini_mpt;
+mod replay;
+mod rlp_practice;
+mod state_export;
+mod state_proof;
 
 // 引入 alloy-primitives 包，但不直接使用它
 
+/// USDT 合约部署所在的区块
+const USDT_DEPLOY_BLOCK: u64 = 4_634_748;
+
 // Providers are zero cost abstractions on top of an opened MDBX Transaction
 // exposing a familiar API to query the chain's information without requiring knowledge
 // of the inner tables.
@@ -48,13 +75,93 @@ fn main() -> eyre::Result<()> {
     // the `provider_rw` function and look for the `Writer` variants of the traits.
     let provider = factory.provider()?;
 
+    // 下面几个异步的例子共用一个运行时；MDBX 的读是阻塞的，它们都会 spawn_blocking，单线程就够了
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
     // Run basic queries against the DB
     let block_num = 100;
 
     header_provider_example(&provider, block_num)?;
+
+    // 导出 10 个区块头给轻客户端，再读回来校验 hash
+    let rlp_path = std::env::temp_dir().join("headers.rlp");
+    let records = export_headers_as_rlp(&provider, block_num, block_num + 9, &rlp_path)?;
+    let imported = import_headers_from_rlp(&rlp_path)?;
+    eyre::ensure!(
+        imported.len() == records.len(),
+        "header export round trip mismatch"
+    );
+    println!(
+        "exported {} headers to {}",
+        records.len(),
+        rlp_path.display()
+    );
     block_provider_example(&provider, block_num)?;
+
+    // 反复查询最近的 10 个块：只有第一轮读库，后面都走缓存
+    let cached = CachingBlockProvider::new(&provider, std::num::NonZeroUsize::new(64).unwrap());
+    for _ in 0..10 {
+        for number in block_num..block_num + 10 {
+            cached.block_by_number(number)?;
+        }
+    }
+    println!("block cache hit rate: {:.2}", cached.hit_rate());
     txs_provider_example(&provider)?;
     receipts_provider_example(&provider)?;
+    state_root_example(&provider, factory.chain_spec().as_ref())?;
+
+    // 把 genesis alloc 里这些账户的最新状态流式导出成 NDJSON
+    let addresses: Vec<Address> = factory
+        .chain_spec()
+        .genesis()
+        .alloc
+        .keys()
+        .copied()
+        .collect();
+    let ndjson_path = std::env::temp_dir().join("accounts.ndjson");
+    let latest = factory.latest()?;
+    let exported = rt.block_on(async {
+        let file = tokio::fs::File::create(&ndjson_path).await?;
+        let mut output = tokio::io::BufWriter::new(file);
+        stream_all_accounts(&latest, addresses, &mut output).await
+    })?;
+    println!("exported {exported} accounts to {}", ndjson_path.display());
+
+    // 扫一段区块里 USDT 的转账：factory 每次查询自己开只读事务，可以 clone 到各个阻塞任务里
+    // 区块范围从命令行传：example-db-access <start> [end]，默认是 USDT 部署之后的 1000 个块，
+    // 部署之前的块里当然一笔都没有
+    let mut args = std::env::args().skip(1).map(|arg| arg.parse::<u64>());
+    let scan_start = args.next().transpose()?.unwrap_or(USDT_DEPLOY_BLOCK);
+    let scan_end = args.next().transpose()?.unwrap_or(scan_start + 999);
+    let usdt = alloy_primitives::address!("dAC17F958D2ee523a2206206994597C13D831ec7");
+    let transfers = rt.block_on(scan_blocks_for_erc20_transfers(
+        factory.clone(),
+        usdt,
+        scan_start,
+        scan_end,
+    ))?;
+    println!(
+        "found {} USDT transfers in blocks {scan_start}..={scan_end}",
+        transfers.len()
+    );
+
+    // 在上一个块的状态上重放一个有交易的块，gas 要和 receipts 完全对上
+    let replay_num = 46_147;
+    let replayed = rt.block_on(replay_block(&factory, replay_num))?;
+    let receipts = provider
+        .receipts_by_block(replay_num.into())?
+        .ok_or(eyre::eyre!("receipts of block {replay_num} not found"))?;
+    let mismatches = replay_mismatches(&replayed, &receipts)?;
+    eyre::ensure!(
+        mismatches.is_empty(),
+        "replay of block {replay_num} diverged: {mismatches:?}"
+    );
+    println!(
+        "replayed {} transactions of block {replay_num}",
+        replayed.len()
+    );
 
     state_provider_example(factory.latest()?, &provider, provider.best_block_number()?)?;
     state_provider_example(
@@ -63,6 +170,50 @@ fn main() -> eyre::Result<()> {
         block_num,
     )?;
 
+    // 100 个账户的证明：一个一个算，和每个地址一个阻塞任务并行算，比一下耗时
+    let proof_addresses: Vec<Address> = factory
+        .chain_spec()
+        .genesis()
+        .alloc
+        .keys()
+        .take(100)
+        .copied()
+        .collect();
+    let best = provider.best_block_number()?;
+
+    // 设置了 ETH_RPC_URL 的话，和外部节点对一下最新高度，看本地数据库落后多少
+    if let Ok(rpc_url) = std::env::var("ETH_RPC_URL") {
+        let remote = rt.block_on(RetryingClient::new().eth_block_number(&rpc_url))?;
+        println!(
+            "best block: local={best}, remote={remote}, behind={}",
+            remote.saturating_sub(best)
+        );
+    }
+
+    let started = std::time::Instant::now();
+    let state = factory.history_by_block_number(best)?;
+    for address in &proof_addresses {
+        state.proof(Default::default(), *address, &[])?;
+    }
+    let serial = started.elapsed();
+
+    let started = std::time::Instant::now();
+    let proofs = rt.block_on(parallel_state_proof(
+        factory.clone(),
+        best,
+        proof_addresses.clone(),
+    ))?;
+    let parallel = started.elapsed();
+    eyre::ensure!(
+        proofs.iter().all(|proof| proof.is_ok()),
+        "some account proofs failed"
+    );
+    println!(
+        "{} account proofs: serial={serial:?}, parallel={parallel:?}, speedup={:.1}x",
+        proofs.len(),
+        serial.as_secs_f64() / parallel.as_secs_f64()
+    );
+
     // Closes the RO transaction opened in the `factory.provider()` call. This is optional and
     // would happen anyway at the end of the function scope.
     drop(provider);
@@ -104,6 +255,13 @@ fn txs_provider_example<T: TransactionsProvider<Transaction = TransactionSigned>
     let _txs_by_block_range: Vec<Vec<TransactionSigned>> =
         provider.transactions_by_block_range(100..200)?;
 
+    // 只要 type 3 的 blob 交易（EIP-4844，Cancun 之后才有）
+    for (tx, blob_hashes) in get_blob_transactions(&provider, 100..200)? {
+        println!("blob tx {} carries {} blobs", tx.hash(), blob_hashes.len());
+    }
+    let blob_count = count_blob_transactions_in_range(&provider, 100, 199)?;
+    println!("blob txs in [100, 200): {blob_count}");
+
     Ok(())
 }
 
@@ -234,18 +392,77 @@ fn receipts_provider_example<
     // 3. If the address & topics filters match do something. We use the outer check against the
     // bloom filter stored in the header to avoid having to query the receipts table when where
     // is no instance of any event that matches the filter in the header.
-    if filter.matches_bloom(bloom) {
+    // receipts 读出来之后用 `LogFilterIterator` 惰性地逐条 match，不用先 collect 一个 Vec
+    // 扫很多块时，filter 先展开成 mask，每个块的 bloom 用 SIMD 比较，结果和 filter.matches_bloom 一样
+    let bloom_filter = BloomFilterSIMD::from_filter(&filter);
+    if bloom_filter.matches_bloom(&bloom.0.0) {
         let receipts = provider
-            .receipt(header_num)?
-            .ok_or(eyre::eyre!("receipt not found"))?;
-        for log in &receipts.logs {
-            if filter.matches(log) {
-                // Do something with the log e.g. decode it.
-                println!("Matching log found! {log:?}");
-            }
+            .receipts_by_block(header_num.into())?
+            .ok_or(eyre::eyre!("no receipts found for block"))?;
+        for log in LogFilterIterator::new(bloom, &receipts, &filter) {
+            // Do something with the log e.g. decode it.
+            println!("Matching log found! {log:?}");
         }
     }
 
+    // 4. 扫一段区块，只要前 100 条：bloom 不命中的块不读 receipts，凑够 100 条之后后面的块都不碰
+    // 读库出错要直接返回，不能当成这个块没有日志；先 collect 每个块的 Result 又会把 10000 个块全读一遍，
+    // 所以写成循环，逐块用 ? 往外传
+    let mut first_100 = Vec::new();
+    for n in header_num..header_num + 10_000 {
+        let remaining = 100 - first_100.len();
+        first_100.extend(filtered_logs(&provider, n, &filter)?.take(remaining));
+        if first_100.len() == 100 {
+            break;
+        }
+    }
+    println!("found {} matching logs", first_100.len());
+
+    // 5. 这个块的小费分布
+    let stats = analyze_fee_market(&provider, header_num)?;
+    println!("fee market of block {header_num}: {stats:?}");
+
+    Ok(())
+}
+
+/// 用 `MiniMpt` 从 genesis 的 alloc 自己算一遍 state root，和数据库里 #0 区块头的 state_root 对比
+///
+/// 创世块的状态完全由 chain spec 里的 alloc 决定，不用遍历 PlainState 表，是最小的一个可以验证的例子
+fn state_root_example<H: HeaderProvider>(
+    headers: &H,
+    spec: &impl EthChainSpec,
+) -> eyre::Result<()> {
+    let header = headers
+        .header_by_number(0)?
+        .ok_or(eyre::eyre!("genesis header not found"))?;
+
+    let accounts = spec.genesis().alloc.iter().map(|(address, account)| {
+        // 每个合约账户的 storage 自己也是一棵 MPT
+        let storage = account.storage.iter().flatten();
+        let storage_root = mini_mpt::storage_root(
+            storage.map(|(slot, value)| (*slot, U256::from_be_bytes(value.0))),
+        );
+        let code_hash = account.code.as_ref().map_or(KECCAK_EMPTY, keccak256);
+
+        let trie_account = TrieAccount {
+            nonce: account.nonce.unwrap_or_default(),
+            balance: account.balance,
+            storage_root,
+            code_hash,
+        };
+        (*address, trie_account)
+    });
+    let computed = mini_mpt::state_root(accounts);
+
+    println!(
+        "genesis state root: computed={computed:?}, header={:?}",
+        header.state_root()
+    );
+    eyre::ensure!(
+        computed == header.state_root(),
+        "genesis state root mismatch"
+    );
+
     Ok(())
 }
 
@@ -0,0 +1,323 @@
+// --- 极简版 Merkle Patricia Trie：state root 是怎么算出来的 ---
+//
+// 以太坊的 state root = 一棵 MPT 的根哈希：
+//   key   = keccak256(address)，32 字节，按 nibble（半字节，4 bit）拆成 64 步路径
+//   value = rlp([nonce, balance, storage_root, code_hash])
+//
+// 节点只有三种（空节点单独算一种）：
+//   Leaf      [hex_prefix(剩余路径, leaf=true),  value]
+//   Extension [hex_prefix(共享路径, leaf=false), 子节点引用]
+//   Branch    [子节点引用 x 16, value]
+//
+// 子节点引用：子节点 RLP 编码后 < 32 字节就直接内嵌，否则放 keccak256(rlp)
+// 根节点不管多长都取 keccak256(rlp)
+//
+// 只实现了 insert + root，没有删除（以太坊里写入空 value 等于删除），也不持久化节点
+
+use alloy_primitives::{B256, U256, b256, keccak256};
+use alloy_rlp::{Header, RlpEncodable};
+
+/// 空树的根：keccak256(rlp(""))，即 keccak256(0x80)
+pub const EMPTY_ROOT_HASH: B256 =
+    b256!("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+
+/// 没有代码的账户的 code_hash：keccak256([])
+pub const KECCAK_EMPTY: B256 =
+    b256!("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+
+/// 存在 state trie 里的账户，字段顺序就是 RLP 编码的顺序
+#[derive(Debug, Clone, PartialEq, Eq, RlpEncodable)]
+pub struct TrieAccount {
+    pub nonce: u64,
+    pub balance: U256,
+    pub storage_root: B256,
+    pub code_hash: B256,
+}
+
+#[derive(Debug, Default)]
+enum Node {
+    #[default]
+    Empty,
+    /// path 是 nibble 序列（每个元素 0..16）
+    Leaf {
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: Box<Node>,
+    },
+    Branch {
+        children: Box<[Node; 16]>,
+        value: Option<Vec<u8>>,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct MiniMpt {
+    root: Node,
+}
+
+impl MiniMpt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 插入（或覆盖）一个 key，value 不能为空
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) {
+        let root = std::mem::take(&mut self.root);
+        self.root = insert(root, &to_nibbles(key), value.to_vec());
+    }
+
+    pub fn root(&self) -> B256 {
+        match self.root {
+            Node::Empty => EMPTY_ROOT_HASH,
+            ref node => keccak256(encode_node(node)),
+        }
+    }
+}
+
+/// 用 `(address, account)` 算 state root：key 是 keccak256(address)，value 是 rlp(account)
+pub fn state_root<A: AsRef<[u8]>>(accounts: impl IntoIterator<Item = (A, TrieAccount)>) -> B256 {
+    let mut trie = MiniMpt::new();
+    for (address, account) in accounts {
+        trie.insert(keccak256(address).as_slice(), &alloy_rlp::encode(&account));
+    }
+    trie.root()
+}
+
+/// 用 `(slot, value)` 算一个账户的 storage root：key 是 keccak256(slot)，value 是 rlp(value)
+/// 值为 0 的槽位在以太坊里等于不存在，不进树
+pub fn storage_root(slots: impl IntoIterator<Item = (B256, U256)>) -> B256 {
+    let mut trie = MiniMpt::new();
+    for (slot, value) in slots.into_iter().filter(|(_, v)| !v.is_zero()) {
+        trie.insert(keccak256(slot).as_slice(), &alloy_rlp::encode(value));
+    }
+    trie.root()
+}
+
+fn to_nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// 如果 prefix 非空，在 node 外面包一层 Extension
+fn with_prefix(prefix: &[u8], node: Node) -> Node {
+    if prefix.is_empty() {
+        node
+    } else {
+        Node::Extension {
+            path: prefix.to_vec(),
+            child: Box::new(node),
+        }
+    }
+}
+
+fn empty_branch() -> Node {
+    Node::Branch {
+        children: Box::new(std::array::from_fn(|_| Node::Empty)),
+        value: None,
+    }
+}
+
+fn insert(node: Node, path: &[u8], value: Vec<u8>) -> Node {
+    match node {
+        Node::Empty => Node::Leaf {
+            path: path.to_vec(),
+            value,
+        },
+
+        Node::Leaf {
+            path: leaf_path,
+            value: leaf_value,
+        } => {
+            if leaf_path == path {
+                return Node::Leaf {
+                    path: leaf_path,
+                    value,
+                };
+            }
+            // 两条路径在 common 处分叉：共享部分变成 Extension，分叉处放一个 Branch，两边各挂一个
+            let common = common_prefix_len(&leaf_path, path);
+            let branch = insert(empty_branch(), &leaf_path[common..], leaf_value);
+            let branch = insert(branch, &path[common..], value);
+            with_prefix(&path[..common], branch)
+        }
+
+        Node::Extension {
+            path: ext_path,
+            child,
+        } => {
+            let common = common_prefix_len(&ext_path, path);
+            if common == ext_path.len() {
+                // 整段共享路径都吻合，往下走
+                let child = insert(*child, &path[common..], value);
+                return Node::Extension {
+                    path: ext_path,
+                    child: Box::new(child),
+                };
+            }
+
+            // 在 ext_path[common] 处分叉：原来的 Extension 剩下的部分挂到新 Branch 的对应槽位
+            let mut branch = empty_branch();
+            if let Node::Branch { children, .. } = &mut branch {
+                children[ext_path[common] as usize] = with_prefix(&ext_path[common + 1..], *child);
+            }
+            let branch = insert(branch, &path[common..], value);
+            with_prefix(&ext_path[..common], branch)
+        }
+
+        Node::Branch {
+            mut children,
+            value: branch_value,
+        } => match path.split_first() {
+            None => Node::Branch {
+                children,
+                value: Some(value),
+            },
+            Some((&nibble, rest)) => {
+                let slot = &mut children[nibble as usize];
+                *slot = insert(std::mem::take(slot), rest, value);
+                Node::Branch {
+                    children,
+                    value: branch_value,
+                }
+            }
+        },
+    }
+}
+
+/// hex-prefix 编码：把 nibble 路径打包回字节，第一个 nibble 是标志位
+/// 0/1 = Extension 偶数/奇数长度，2/3 = Leaf 偶数/奇数长度；偶数长度时补一个 0 nibble
+fn hex_prefix(path: &[u8], is_leaf: bool) -> Vec<u8> {
+    let flag = if is_leaf { 2 } else { 0 };
+    let mut nibbles = if path.len() % 2 == 1 {
+        vec![flag + 1]
+    } else {
+        vec![flag, 0]
+    };
+    nibbles.extend_from_slice(path);
+    nibbles
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect()
+}
+
+/// 把已经编码好的若干 RLP item 拼成一个 RLP 列表
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload_length = items.iter().map(Vec::len).sum();
+    let mut out = Vec::with_capacity(payload_length + 3);
+    Header {
+        list: true,
+        payload_length,
+    }
+    .encode(&mut out);
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    alloy_rlp::encode(bytes)
+}
+
+fn encode_node(node: &Node) -> Vec<u8> {
+    match node {
+        Node::Empty => rlp_bytes(&[]),
+        Node::Leaf { path, value } => {
+            rlp_list(&[rlp_bytes(&hex_prefix(path, true)), rlp_bytes(value)])
+        }
+        Node::Extension { path, child } => {
+            rlp_list(&[rlp_bytes(&hex_prefix(path, false)), node_ref(child)])
+        }
+        Node::Branch { children, value } => {
+            let mut items: Vec<Vec<u8>> = children.iter().map(node_ref).collect();
+            items.push(rlp_bytes(value.as_deref().unwrap_or_default()));
+            rlp_list(&items)
+        }
+    }
+}
+
+/// 父节点里怎么引用子节点：短节点直接内嵌，长节点放 32 字节哈希
+fn node_ref(node: &Node) -> Vec<u8> {
+    let encoded = encode_node(node);
+    if encoded.len() < 32 {
+        encoded
+    } else {
+        rlp_bytes(keccak256(&encoded).as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{Address, address};
+
+    use super::*;
+
+    #[test]
+    fn test_empty_and_known_roots() {
+        assert_eq!(MiniMpt::new().root(), EMPTY_ROOT_HASH);
+        assert_eq!(keccak256([]), KECCAK_EMPTY);
+
+        // 以太坊 trie 测试里的经典例子，插入顺序不影响根
+        let items = [
+            ("doe", "reindeer"),
+            ("dog", "puppy"),
+            ("dogglesworth", "cat"),
+        ];
+        let expected = b256!("8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3");
+        for order in [[0, 1, 2], [2, 1, 0], [1, 2, 0]] {
+            let mut trie = MiniMpt::new();
+            for i in order {
+                trie.insert(items[i].0.as_bytes(), items[i].1.as_bytes());
+            }
+            assert_eq!(trie.root(), expected);
+        }
+
+        // 覆盖写
+        let mut trie = MiniMpt::new();
+        trie.insert(b"dog", b"kitten");
+        trie.insert(b"doe", b"reindeer");
+        trie.insert(b"dogglesworth", b"cat");
+        trie.insert(b"dog", b"puppy");
+        assert_eq!(trie.root(), expected);
+    }
+
+    #[test]
+    fn test_state_root() {
+        // 单个账户：根 = keccak256(rlp(Leaf))，Leaf 的路径是完整的 64 个 nibble
+        let addr = address!("0x000000000000000000000000000000000000dead");
+        let account = TrieAccount {
+            nonce: 0,
+            balance: U256::from(1_000_000_000u64),
+            storage_root: EMPTY_ROOT_HASH,
+            code_hash: KECCAK_EMPTY,
+        };
+        let leaf = rlp_list(&[
+            rlp_bytes(&hex_prefix(&to_nibbles(keccak256(addr).as_slice()), true)),
+            rlp_bytes(&alloy_rlp::encode(&account)),
+        ]);
+        assert_eq!(state_root([(addr, account.clone())]), keccak256(leaf));
+
+        // 多个账户，顺序无关
+        let accounts: Vec<(Address, TrieAccount)> = (0..200u64)
+            .map(|i| {
+                let account = TrieAccount {
+                    nonce: i,
+                    balance: U256::from(i * 7),
+                    ..account.clone()
+                };
+                (Address::with_last_byte(i as u8), account)
+            })
+            .collect();
+        let root = state_root(accounts.clone());
+        assert_eq!(state_root(accounts.into_iter().rev()), root);
+
+        assert_eq!(storage_root([]), EMPTY_ROOT_HASH);
+        assert_eq!(storage_root([(B256::ZERO, U256::ZERO)]), EMPTY_ROOT_HASH);
+    }
+}
@@ -0,0 +1,179 @@
+// --- 在历史状态上重放一个块里的交易，和数据库里的 receipts 对账 ---
+//
+// 第 N 个块执行之前的状态，就是第 N-1 个块执行完的状态：history_by_block_number(N - 1)。
+// 在它上面套一层 CacheDB：EVM 读的时候先查 CacheDB，没有再去数据库读；写只写进 CacheDB 的内存里，
+// 数据库本身是只读打开的，重放多少次都不会改动持久化的状态。
+// 交易要按顺序一笔一笔 transact_commit，后面的交易才能看到前面交易改过的余额、nonce、storage。
+//
+// 重放出来的每笔 gas_used 应该和 receipt 里记录的一模一样（receipt 里存的是累计值，相邻两笔相减）。
+// 对不上说明 EVM 的实现和出块时不一致 —— 比如 custom-evm 例子里的自定义预编译改了 gas 计算。
+//
+// 简化：块开头的系统调用（EIP-4788 beacon root、EIP-2935 历史区块 hash）没有执行，
+// 读这两个系统合约的交易重放结果可能不一样；块结尾的提款、奖励不影响块内交易，也不需要
+
+use alloy_primitives::{B256, Log};
+use reth_ethereum::chainspec::{ChainSpec, ChainSpecProvider};
+use reth_ethereum::evm::EthEvmConfig;
+use reth_ethereum::evm::primitives::{ConfigureEvm, Evm};
+use reth_ethereum::evm::revm::database::StateProviderDatabase;
+use reth_ethereum::evm::revm::db::CacheDB;
+use reth_ethereum::provider::{BlockReader, TransactionVariant};
+use reth_ethereum::storage::StateProviderFactory;
+use reth_ethereum::{Block, Receipt};
+
+/// 一笔交易的重放结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionResult {
+    pub tx_hash: B256,
+    pub gas_used: u64,
+    pub success: bool,
+    pub logs: Vec<Log>,
+}
+
+/// 重放结果和 receipt 对不上的一笔交易
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayMismatch {
+    pub tx_hash: B256,
+    pub replayed_gas: u64,
+    pub receipt_gas: u64,
+    pub replayed_success: bool,
+    pub receipt_success: bool,
+}
+
+/// 在 `block_num - 1` 的状态上按顺序重放 `block_num` 里的所有交易
+///
+/// 读库和执行都是同步的 CPU / IO 操作，放到阻塞线程池里跑，不占 tokio 的 worker
+pub async fn replay_block<P>(factory: &P, block_num: u64) -> eyre::Result<Vec<ExecutionResult>>
+where
+    P: StateProviderFactory
+        + BlockReader<Block = Block>
+        + ChainSpecProvider<ChainSpec = ChainSpec>
+        + Clone
+        + 'static,
+{
+    let factory = factory.clone();
+    tokio::task::spawn_blocking(move || replay_block_blocking(&factory, block_num)).await?
+}
+
+fn replay_block_blocking<P>(factory: &P, block_num: u64) -> eyre::Result<Vec<ExecutionResult>>
+where
+    P: StateProviderFactory + BlockReader<Block = Block> + ChainSpecProvider<ChainSpec = ChainSpec>,
+{
+    eyre::ensure!(block_num > 0, "genesis block has no transactions to replay");
+
+    let block = factory
+        .recovered_block(block_num.into(), TransactionVariant::WithHash)?
+        .ok_or(eyre::eyre!("block {block_num} not found"))?;
+
+    // 上一个块执行完的状态 = 这个块执行前的状态
+    let state = factory.history_by_block_number(block_num - 1)?;
+    let db = CacheDB::new(StateProviderDatabase::new(state));
+
+    // block env（coinbase、base_fee、prevrandao …）和硬分叉规则都从区块头 + chain spec 算出来
+    let evm_config = EthEvmConfig::new(factory.chain_spec());
+    let evm_env = evm_config.evm_env(block.header())?;
+    let mut evm = evm_config.evm_with_env(db, evm_env);
+
+    block
+        .transactions_recovered()
+        .map(|tx| -> eyre::Result<ExecutionResult> {
+            let tx_hash = *tx.tx_hash();
+            // 执行并把状态变化写进 CacheDB，下一笔交易能看到
+            let result = evm.transact_commit(tx)?;
+            Ok(ExecutionResult {
+                tx_hash,
+                gas_used: result.gas_used(),
+                success: result.is_success(),
+                logs: result.into_logs(),
+            })
+        })
+        .collect()
+}
+
+/// 逐笔比较重放结果和 receipts 里的 gas / 成功状态，返回对不上的交易；全部一致时返回空
+pub fn replay_mismatches(
+    replayed: &[ExecutionResult],
+    receipts: &[Receipt],
+) -> eyre::Result<Vec<ReplayMismatch>> {
+    eyre::ensure!(
+        replayed.len() == receipts.len(),
+        "replayed {} transactions but found {} receipts",
+        replayed.len(),
+        receipts.len()
+    );
+
+    // receipt 里的 cumulative_gas_used 是块内到这笔为止的累计值
+    let mut cumulative = 0;
+    let mismatches = replayed
+        .iter()
+        .zip(receipts)
+        .filter_map(|(result, receipt)| {
+            let receipt_gas = receipt.cumulative_gas_used - cumulative;
+            cumulative = receipt.cumulative_gas_used;
+
+            (result.gas_used != receipt_gas || result.success != receipt.success).then(|| {
+                ReplayMismatch {
+                    tx_hash: result.tx_hash,
+                    replayed_gas: result.gas_used,
+                    receipt_gas,
+                    replayed_success: result.success,
+                    receipt_success: receipt.success,
+                }
+            })
+        })
+        .collect();
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replayed(gas_used: u64, success: bool) -> ExecutionResult {
+        ExecutionResult {
+            tx_hash: B256::with_last_byte(gas_used as u8),
+            gas_used,
+            success,
+            logs: vec![],
+        }
+    }
+
+    fn receipt(cumulative_gas_used: u64, success: bool) -> Receipt {
+        Receipt {
+            cumulative_gas_used,
+            success,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_replay_mismatches() {
+        let receipts = [
+            receipt(21_000, true),
+            receipt(71_000, false),
+            receipt(92_000, true),
+        ];
+
+        // 每笔 21000、50000、21000
+        let ok = [
+            replayed(21_000, true),
+            replayed(50_000, false),
+            replayed(21_000, true),
+        ];
+        assert!(replay_mismatches(&ok, &receipts).unwrap().is_empty());
+
+        // 第二笔多算了 gas，第三笔重放失败了
+        let bad = [
+            replayed(21_000, true),
+            replayed(50_100, false),
+            replayed(21_000, false),
+        ];
+        let mismatches = replay_mismatches(&bad, &receipts).unwrap();
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(mismatches[0].replayed_gas, 50_100);
+        assert_eq!(mismatches[0].receipt_gas, 50_000);
+        assert!(!mismatches[1].replayed_success && mismatches[1].receipt_success);
+
+        assert!(replay_mismatches(&ok[..2], &receipts).is_err());
+    }
+}
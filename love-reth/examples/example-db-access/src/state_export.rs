@@ -0,0 +1,128 @@
+// --- 把账户状态流式导出成 NDJSON（每行一个 JSON），比如用来生成新链的 genesis ---
+//
+// StateProvider 只提供点查（basic_account / storage / account_code），没有 “列出所有账户” 的接口：
+// 账户在 trie 里的 key 是 keccak256(address)，从 trie 节点只能倒推出 hash，倒推不出地址。
+// 所以这里由调用方给出要导出的地址列表（比如 genesis alloc 里的地址），逐个查询、逐行写出。
+//
+// 整个过程只持有一行的缓冲区，不会把全部状态先攒在内存里再一次性序列化
+
+use alloy_primitives::{Address, B256, U256};
+use reth_ethereum::primitives::Account;
+use reth_ethereum::storage::AccountReader;
+use serde::Serialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// NDJSON 里的一行
+#[derive(Debug, Serialize)]
+struct AccountLine {
+    address: Address,
+    nonce: u64,
+    balance: U256,
+    /// 没有代码的账户为 null
+    bytecode_hash: Option<B256>,
+}
+
+/// 查询 `addresses` 里每个地址当前的账户状态，存在的账户各写一行 JSON，返回写出的行数
+pub async fn stream_all_accounts<P, W>(
+    provider: &P,
+    addresses: impl IntoIterator<Item = Address>,
+    output: &mut W,
+) -> eyre::Result<u64>
+where
+    P: AccountReader,
+    W: AsyncWrite + Unpin,
+{
+    let accounts = addresses
+        .into_iter()
+        .map(|address| Ok((address, provider.basic_account(&address)?)));
+    write_accounts(accounts, output).await
+}
+
+async fn write_accounts<W>(
+    accounts: impl IntoIterator<Item = eyre::Result<(Address, Option<Account>)>>,
+    output: &mut W,
+) -> eyre::Result<u64>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut line = Vec::new();
+    let mut count = 0;
+
+    for account in accounts {
+        // 不存在的账户（从没收过钱、也没被创建过）不写
+        let (address, Some(account)) = account? else {
+            continue;
+        };
+
+        line.clear();
+        serde_json::to_writer(
+            &mut line,
+            &AccountLine {
+                address,
+                nonce: account.nonce,
+                balance: account.balance,
+                bytecode_hash: account.bytecode_hash,
+            },
+        )?;
+        line.push(b'\n');
+        output.write_all(&line).await?;
+        count += 1;
+    }
+
+    output.flush().await?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_ndjson() {
+        let rich = Address::with_last_byte(1);
+        let contract = Address::with_last_byte(2);
+        let missing = Address::with_last_byte(3);
+        let accounts = vec![
+            Ok((
+                rich,
+                Some(Account {
+                    nonce: 3,
+                    balance: U256::from(1_000u64),
+                    bytecode_hash: None,
+                }),
+            )),
+            Ok((
+                contract,
+                Some(Account {
+                    nonce: 1,
+                    balance: U256::ZERO,
+                    bytecode_hash: Some(B256::repeat_byte(0xab)),
+                }),
+            )),
+            Ok((missing, None)),
+        ];
+
+        let mut out: Vec<u8> = Vec::new();
+        let count = write_accounts(accounts, &mut out).await.unwrap();
+        assert_eq!(count, 2);
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["address"], rich.to_string().to_lowercase());
+        assert_eq!(lines[0]["nonce"], 3);
+        assert_eq!(lines[0]["balance"], "0x3e8");
+        assert!(lines[0]["bytecode_hash"].is_null());
+        assert_eq!(
+            lines[1]["bytecode_hash"],
+            B256::repeat_byte(0xab).to_string()
+        );
+
+        // 查询出错时直接返回错误
+        let failing = vec![Err(eyre::eyre!("db closed"))];
+        assert!(write_accounts(failing, &mut Vec::new()).await.is_err());
+    }
+}
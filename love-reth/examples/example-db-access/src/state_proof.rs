@@ -0,0 +1,112 @@
+// --- 一次给一批账户生成 Merkle 证明，比如轻客户端一个请求要几十上百个账户 ---
+//
+// state_provider_example 里是一个地址调用一次 provider.proof()：每次都要从数据库读出账户在 trie 里
+// 的整条路径，再把沿途的节点重新算一遍，同步阻塞、纯串行。地址之间互不依赖，可以并行。
+//
+// 和 erc20_scan 一样用 JoinSet::spawn_blocking，每个地址一个阻塞任务。
+// StateProvider 绑在一个只读事务上，不能 clone 到别的线程，所以传进来的是 ProviderFactory 这种
+// 工厂，每个任务用 history_by_block_number(block_num) 自己开一个事务：
+// 钉在同一个块号上，所有证明都对着同一个 state root，不会因为中途出了新块而对不上。
+//
+// 任务完成的顺序是乱的，每个任务带上自己的下标，收齐之后按下标放回去，返回的顺序和输入一致。
+// 一个地址出错（比如那个块的历史状态已经被 prune 了）只影响它自己那一项，不影响其他地址；
+// 只有任务 panic 了才整体返回错误
+
+use alloy_primitives::Address;
+use reth_ethereum::storage::StateProviderFactory;
+use reth_ethereum::trie::AccountProof;
+use tokio::task::JoinSet;
+
+/// 并行生成 `addresses` 在 `block_num` 这个块的状态上的账户证明，结果的顺序和 `addresses` 一致
+pub async fn parallel_state_proof<P>(
+    factory: P,
+    block_num: u64,
+    addresses: Vec<Address>,
+) -> eyre::Result<Vec<eyre::Result<AccountProof>>>
+where
+    P: StateProviderFactory + Clone + Send + 'static,
+{
+    spawn_ordered(addresses, move |address| {
+        let state = factory.history_by_block_number(block_num)?;
+        // 只要账户本身的证明，不带 storage slot
+        Ok(state.proof(Default::default(), address, &[])?)
+    })
+    .await
+}
+
+/// 每个输入一个阻塞任务，按输入的顺序返回每个任务的结果
+async fn spawn_ordered<I, T, F>(inputs: Vec<I>, f: F) -> eyre::Result<Vec<eyre::Result<T>>>
+where
+    I: Send + 'static,
+    T: Send + 'static,
+    F: Fn(I) -> eyre::Result<T> + Clone + Send + 'static,
+{
+    let mut results: Vec<Option<eyre::Result<T>>> = inputs.iter().map(|_| None).collect();
+
+    let mut tasks = JoinSet::new();
+    for (i, input) in inputs.into_iter().enumerate() {
+        let f = f.clone();
+        tasks.spawn_blocking(move || (i, f(input)));
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        let (i, result) = joined?;
+        results[i] = Some(result);
+    }
+
+    // join_next 返回 None 时所有任务都结束了，每个下标都填上了
+    Ok(results.into_iter().map(Option::unwrap).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_results_in_input_order() {
+        // 越靠前的输入睡得越久，完成的顺序和输入正好相反
+        let inputs: Vec<u64> = (0..8).collect();
+        let results = spawn_ordered(inputs, |n| {
+            std::thread::sleep(Duration::from_millis(10 * (8 - n)));
+            Ok(n * n)
+        })
+        .await
+        .unwrap();
+
+        let squares: Vec<u64> = results.into_iter().map(|r| r.unwrap()).collect();
+        assert_eq!(squares, (0..8).map(|n| n * n).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_error_per_input() {
+        let results = spawn_ordered(vec![1, 2, 3, 4], |n| {
+            eyre::ensure!(n % 2 == 1, "{n} is even");
+            Ok(n)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(*results[0].as_ref().unwrap(), 1);
+        assert_eq!(results[1].as_ref().unwrap_err().to_string(), "2 is even");
+        assert_eq!(*results[2].as_ref().unwrap(), 3);
+        assert!(results[3].is_err());
+
+        // 任务 panic 了，整体返回错误
+        let panicked = spawn_ordered(vec![1, 2], |n: u32| {
+            assert_ne!(n, 2, "boom");
+            Ok(n)
+        })
+        .await;
+        assert!(panicked.is_err());
+
+        assert!(
+            spawn_ordered(Vec::<u32>::new(), Ok)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,192 @@
+// --- 每个 WebSocket 连接的状态，连接自己的任务和监控（GET /ws/stats）都能看到 ---
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use atomic_refcell::AtomicRefCell;
+use axum::{Json, extract::State};
+use serde::Serialize;
+use smol_str::SmolStr;
+
+use crate::AppState;
+
+// 放在 Arc 里，连接任务和监控任务各持有一份，都不用加锁：
+// - 计数器是原子变量，谁都可以随时读
+// - topic 集合用 AtomicRefCell：和 RefCell 一样在运行时检查借用，只是借用标记是原子的，所以是 Sync。
+//   它不是锁，借用冲突时不会等，直接 panic；而且冲突的两边都可能 panic：
+//   监控那边 try_borrow 成功拿着读借用时，连接这边的 borrow_mut 就 panic 了。
+//   所以 topic 集合只有连接自己的任务碰（不跨 .await 持有借用），监控只读计数器
+pub struct ConnectionState {
+    topics: AtomicRefCell<HashSet<SmolStr>>,
+    // 最近一次收到 Ping 的时间（Unix 毫秒），0 表示还没收到过
+    last_ping: AtomicU64,
+    // 收到的客户端消息数
+    msg_count: AtomicU64,
+}
+
+// GET /ws/stats 里每个连接的一项
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ConnectionStats {
+    pub id: u64,
+    pub last_ping: u64,
+    pub msg_count: u64,
+}
+
+impl ConnectionState {
+    pub fn new() -> Self {
+        ConnectionState {
+            topics: AtomicRefCell::new(HashSet::new()),
+            last_ping: AtomicU64::new(0),
+            msg_count: AtomicU64::new(0),
+        }
+    }
+
+    // 下面三个只能由连接自己的任务调用
+    pub fn subscribe(&self, topic: SmolStr) -> bool {
+        self.topics.borrow_mut().insert(topic)
+    }
+
+    pub fn unsubscribe(&self, topic: &str) -> bool {
+        self.topics.borrow_mut().remove(topic)
+    }
+
+    pub fn is_subscribed(&self, topic: &str) -> bool {
+        self.topics.borrow().contains(topic)
+    }
+
+    pub fn record_msg(&self) {
+        // 只是统计，不和其他内存操作同步，Relaxed 就够了
+        self.msg_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_ping(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.last_ping.store(now, Ordering::Relaxed);
+    }
+
+    // 任何任务都可以调用，不碰 topic 集合
+    pub fn stats(&self, id: u64) -> ConnectionStats {
+        ConnectionStats {
+            id,
+            last_ping: self.last_ping.load(Ordering::Relaxed),
+            msg_count: self.msg_count.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// 所有在线连接。这里的 Mutex 只在连接建立 / 断开和监控拿快照时锁一下，
+// 连接处理消息时只碰自己的 ConnectionState，不经过它
+pub struct ConnectionRegistry {
+    next_id: AtomicU64,
+    conns: Mutex<HashMap<u64, Arc<ConnectionState>>>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        ConnectionRegistry {
+            next_id: AtomicU64::new(1),
+            conns: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn register(&self) -> (u64, Arc<ConnectionState>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let conn = Arc::new(ConnectionState::new());
+        self.conns.lock().unwrap().insert(id, conn.clone());
+        (id, conn)
+    }
+
+    pub fn unregister(&self, id: u64) {
+        self.conns.lock().unwrap().remove(&id);
+    }
+
+    // 先把 Arc 拷出来再读，读的时候不持有锁
+    pub fn stats(&self) -> Vec<ConnectionStats> {
+        let conns: Vec<(u64, Arc<ConnectionState>)> = self
+            .conns
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, conn)| (*id, conn.clone()))
+            .collect();
+        let mut stats: Vec<ConnectionStats> =
+            conns.iter().map(|(id, conn)| conn.stats(*id)).collect();
+        stats.sort_by_key(|s| s.id);
+        stats
+    }
+}
+
+// GET /ws/stats
+pub async fn ws_stats(State(state): State<Arc<AppState>>) -> Json<Vec<ConnectionStats>> {
+    Json(state.connections.stats())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 模拟 WebSocket 的访问方式：连接任务不停地订阅 / 取消订阅 / 计数，
+    // 同时几个监控任务在别的线程上不停地读统计，整个过程不能 panic
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_access() {
+        const ROUNDS: u64 = 10_000;
+        let conn = Arc::new(ConnectionState::new());
+
+        let handler = tokio::spawn({
+            let conn = conn.clone();
+            async move {
+                for i in 0..ROUNDS {
+                    conn.record_msg();
+                    // 偶数轮订阅，下一轮取消同一个
+                    let topic = SmolStr::new(format!("topic_{}", i / 2 % 8));
+                    if i % 2 == 0 {
+                        conn.subscribe(topic);
+                    } else {
+                        conn.record_ping();
+                        conn.unsubscribe(&topic);
+                    }
+                    assert!(!conn.is_subscribed("never"));
+                    if i % 100 == 0 {
+                        tokio::task::yield_now().await;
+                    }
+                }
+            }
+        });
+
+        let monitors: Vec<_> = (0..3)
+            .map(|_| {
+                let conn = conn.clone();
+                tokio::spawn(async move {
+                    let mut last = 0;
+                    while last < ROUNDS {
+                        let stats = conn.stats(1);
+                        // 计数只增不减
+                        assert!(stats.msg_count >= last);
+                        last = stats.msg_count;
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+
+        handler.await.unwrap();
+        for m in monitors {
+            m.await.unwrap();
+        }
+
+        let stats = conn.stats(1);
+        assert_eq!(stats.msg_count, ROUNDS);
+        assert!(stats.last_ping > 0);
+        // 每次订阅之后紧跟着取消，最后什么都没订阅
+        assert!(conn.topics.borrow().is_empty());
+    }
+}
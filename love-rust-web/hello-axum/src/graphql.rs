@@ -0,0 +1,114 @@
+// --- GraphQL: 查询 / 创建用户，订阅新用户事件 ---
+// 和 REST 接口共用同一个 AppState：同一个 db，同一个用户事件广播
+
+use std::sync::Arc;
+
+use async_graphql::{Context, Object, Schema, SimpleObject, Subscription, futures_util::Stream};
+use tokio_stream::{StreamExt, wrappers::BroadcastStream};
+
+use crate::{AppState, User};
+
+// GraphQL 里的 User 类型，字段和 REST 的 User 一一对应
+// SimpleObject 会把每个字段自动变成 GraphQL 字段（蛇形命名自动转驼峰）
+#[derive(SimpleObject, Clone, Debug)]
+pub struct GqlUser {
+    id: u64,
+    username: String,
+    age: u8,
+}
+
+impl From<User> for GqlUser {
+    fn from(user: User) -> Self {
+        GqlUser {
+            id: user.id,
+            username: user.username,
+            age: user.age,
+        }
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// query { user(id: 1) { id username age } }
+    async fn user(&self, ctx: &Context<'_>, id: u64) -> Option<GqlUser> {
+        let state = ctx.data_unchecked::<Arc<AppState>>();
+        let db = state.db.lock().unwrap();
+        db.get(&id).cloned().map(GqlUser::from)
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// mutation { createUser(username: "alice", age: 18) { id } }
+    async fn create_user(&self, ctx: &Context<'_>, username: String, age: u8) -> GqlUser {
+        let state = ctx.data_unchecked::<Arc<AppState>>();
+        state.create_user(username, age).into()
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// subscription { userCreated { id username } }
+    ///
+    /// 每个订阅者一个 broadcast::Receiver，REST 和 GraphQL 创建的用户都会推过来
+    async fn user_created(&self, ctx: &Context<'_>) -> impl Stream<Item = GqlUser> {
+        let state = ctx.data_unchecked::<Arc<AppState>>();
+        // 订阅者太慢、落后太多时 BroadcastStream 会产生 Lagged 错误，直接跳过丢掉的那部分
+        BroadcastStream::new(state.user_events.subscribe())
+            .filter_map(|event| event.ok().map(GqlUser::from))
+    }
+}
+
+pub type AppSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+pub fn schema(state: Arc<AppState>) -> AppSchema {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .data(state)
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_graphql::value;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscription_receives_created_user() {
+        let schema = schema(Arc::new(AppState::new()));
+
+        let mut stream = schema.execute_stream("subscription { userCreated { id username age } }");
+        // 订阅的 resolver 在第一次 poll 时才执行（才真正 subscribe），
+        // 先 poll 一次，确认订阅已经建立并且还没有事件
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), stream.next())
+                .await
+                .is_err()
+        );
+
+        let res = schema
+            .execute(r#"mutation { createUser(username: "alice", age: 18) { id } }"#)
+            .await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        assert_eq!(res.data, value!({ "createUser": { "id": 1 } }));
+
+        let event = stream.next().await.unwrap();
+        assert_eq!(
+            event.data,
+            value!({ "userCreated": { "id": 1, "username": "alice", "age": 18 } })
+        );
+
+        let res = schema.execute("{ user(id: 1) { username } }").await;
+        assert_eq!(res.data, value!({ "user": { "username": "alice" } }));
+        let res = schema.execute("{ user(id: 2) { username } }").await;
+        assert_eq!(res.data, value!({ "user": null }));
+    }
+}
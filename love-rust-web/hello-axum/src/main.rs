@@ -1,50 +1,164 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
+    future::{Ready, ready},
     sync::{Arc, Mutex},
 };
 
+use async_graphql_axum::{GraphQL, GraphQLSubscription};
 use axum::{
-    Json, Router,
+    Json, Router, async_trait,
     extract::{
-        Path, Query, State, WebSocketUpgrade,
+        FromRequestParts, Path, Query, Request, State, WebSocketUpgrade,
         ws::{Message, WebSocket},
     },
-    http::StatusCode,
-    response::{Html, IntoResponse},
-    routing::{get, post},
+    http::{HeaderName, HeaderValue, Method, StatusCode, header, request::Parts},
+    middleware::{self, MapResponseLayer, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{get, get_service, post, post_service},
 };
+use axum_extra::TypedHeader;
+use semver::Version;
 use serde::{Deserialize, Serialize};
-use tokio::net::TcpListener;
+use smol_str::SmolStr;
+use tokio::{net::TcpListener, sync::broadcast};
+use tower_http::limit::RequestBodyLimitLayer;
+
+use crate::connection::{ConnectionRegistry, ConnectionState};
+use crate::mempool::{MEMPOOL_TOPIC, PendingTx, TxBroadcaster};
+
+mod connection;
+mod graphql;
+mod mempool;
+mod tls;
+
+// 请求体最大 4KB，超过直接 413，防止有人发一个超大 body 把内存撑爆
+const MAX_BODY_BYTES: usize = 4096;
+
+// 低于这个版本的客户端不允许写数据，返回 426 让它升级
+const MIN_CLIENT_VERSION: Version = Version::new(1, 0, 0);
 
 #[tokio::main] // 启动 tokio 异步运行时
 async fn main() {
     // 初始化共享状态
-    let shared_state = Arc::new(AppState {
-        db: Mutex::new(HashMap::new()),
-    });
+    let shared_state = Arc::new(AppState::new());
+
+    let app = app(shared_state);
+
+    // 定义监听地址
+    let listiner = TcpListener::bind("127.0.0.1:3000").await.unwrap();
+
+    // 启动服务：配置了证书就走 HTTPS，否则普通 HTTP
+    match tls::config_from_env().unwrap() {
+        Some(config) => {
+            println!("🚀 Server running on https://127.0.0.1:3000");
+            tls::serve_tls(listiner, app, config).await.unwrap();
+        }
+        None => {
+            println!("🚀 Server running on http://127.0.0.1:3000");
+            axum::serve(listiner, app).await.unwrap();
+        }
+    }
+}
+
+// 构建应用路由，单独拆出来是为了测试里可以直接拿到 Router，不用真的监听端口
+fn app(shared_state: Arc<AppState>) -> Router {
+    let schema = graphql::schema(shared_state.clone());
 
-    // 构建应用路由
     // 当用户访问根路径 / 时，调用 root 函数
     // GET / 返回纯文本
     // POST /json 接收json返回json
-    let app: Router = Router::new()
+    Router::new()
         .route("/", get(root))
         .route("/json", post(echo_json))
         .route("/users", post(create_user).get(search_users)) // 同一个路径，不同方法
         .route("/users/:id", get(get_user_by_id)) // :id 是路径参数占位符
-        .route("/ws", get(ws_handler)) // 添加 WebSocket 路由
+        // GraphQL 查询 / 修改，createUser 这种写操作和 POST /users 一样要检查客户端版本
+        .route(
+            "/graphql",
+            post_service(GraphQL::new(schema.clone())).layer(middleware::from_fn_with_state(
+                shared_state.clone(),
+                require_client_version,
+            )),
+        )
+        .route("/mempool/broadcast", post(mempool::broadcast_tx)) // 广播交易给订阅了 mempool 的 WebSocket 连接
+        // layer 只作用于它之前添加的路由，后面的 /ws 不受限制
+        // 多个 layer 时，后加的在外层，请求先经过 body 大小限制，再检查 Content-Type
+        .layer(middleware::from_fn(require_json))
+        .layer(RequestBodyLimitLayer::new(MAX_BODY_BYTES))
+        .route("/ws", get(ws_handler)) // 添加 WebSocket 路由，握手请求没有 body，不需要上面两层
+        .route("/ws/stats", get(connection::ws_stats)) // 在线 WebSocket 连接的订阅和计数
+        // GraphQL 订阅走 WebSocket，和上面的 POST /graphql 同一个路径，不同方法
+        .route("/graphql", get_service(GraphQLSubscription::new(schema)))
         .with_state(shared_state) // 注入状态！
-        .fallback(handler_404); // 处理所有未匹配路由;
+        .fallback(handler_404) // 处理所有未匹配路由;
+        // 最外层，所有响应（包括 404、413、415 这些中间件直接返回的）都要经过
+        .layer(strip_sensitive_headers_layer())
+}
 
-    // 定义监听地址
-    let listiner = TcpListener::bind("127.0.0.1:3000").await.unwrap();
-    println!("🚀 Server running on http://127.0.0.1:3000");
+// 自定义中间件：POST / PUT 必须带 Content-Type: application/json，否则 415
+// 允许带参数，比如 application/json; charset=utf-8
+async fn require_json(req: Request, next: Next) -> Response {
+    if matches!(*req.method(), Method::POST | Method::PUT) {
+        let is_json = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+
+        if !is_json {
+            return (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Content-Type 必须是 application/json",
+            )
+                .into_response();
+        }
+    }
+
+    next.run(req).await
+}
 
-    // 启动服务
-    axum::serve(listiner, app).await.unwrap();
+// 会暴露服务端实现细节（用的什么框架、内部链路追踪 id）的响应头，一律去掉
+const SENSITIVE_HEADERS: [HeaderName; 3] = [
+    header::SERVER,
+    HeaderName::from_static("x-powered-by"),
+    HeaderName::from_static("x-internal-trace-id"),
+];
+
+// 每个响应都带上的安全头：
+// nosniff 禁止浏览器猜 Content-Type，DENY 禁止被 iframe 嵌套（防点击劫持），HSTS 一年内只走 HTTPS
+const SECURITY_HEADERS: [(HeaderName, HeaderValue); 3] = [
+    (
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    ),
+    (header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY")),
+    (
+        header::STRICT_TRANSPORT_SECURITY,
+        HeaderValue::from_static("max-age=31536000"),
+    ),
+];
+
+// map_response 只改响应，不需要看请求，比 from_fn 简单
+// 返回值类型要写得出来，所以用函数指针 + Ready，而不是闭包 / async fn
+fn strip_sensitive_headers_layer() -> MapResponseLayer<fn(Response) -> Ready<Response>, (), ()> {
+    middleware::map_response(|res| ready(strip_sensitive_headers(res)))
+}
+
+fn strip_sensitive_headers(mut res: Response) -> Response {
+    let headers = res.headers_mut();
+    for name in SENSITIVE_HEADERS {
+        headers.remove(name);
+    }
+    // insert 会覆盖 handler 自己设置的同名头
+    for (name, value) in SECURITY_HEADERS {
+        headers.insert(name, value);
+    }
+    res
 }
 
 /// 5. 处理函数 root
+///
 /// axum 非常智能，只要你的返回值实现了 IntoResponse tarit 它就能变成 http 响应
 /// &'static str axum 会自动把它变成 text/plain 响应
 async fn root() -> Html<&'static str> {
@@ -64,8 +178,11 @@ struct User {
 // 自动读取 Body，自动用 serde_json 反序列化成 User 结构体。
 // 如果格式不对，Axum 会自动返回 400 Bad Request，你都不用写错误处理代码。
 // 参数解构语法
-async fn echo_json(Json(payload): Json<User>) -> Json<User> {
-    println!("收到用户: {}, 年龄: {}", payload.username, payload.age);
+async fn echo_json(ClientVersion(version): ClientVersion, Json(payload): Json<User>) -> Json<User> {
+    println!(
+        "收到用户: {}, 年龄: {}, 客户端版本: {}",
+        payload.username, payload.age, version
+    );
 
     // 直接返回 json 包裹的结构体，axum 会自动序列化回 json 字符串
     Json(payload)
@@ -90,6 +207,114 @@ struct AppState {
     // Key是ID, Value是User。
     // 使用 Mutex 是因为 Axum 是多线程并发的，修改数据必须加锁。
     db: Mutex<HashMap<u64, User>>,
+    // 允许的最低客户端版本
+    min_client_version: Version,
+    // 新用户事件广播，GraphQL 订阅从这里拿数据
+    user_events: broadcast::Sender<User>,
+    // 待广播交易，WebSocket 连接订阅了 mempool topic 才会收到
+    mempool: TxBroadcaster,
+    // 在线的 WebSocket 连接，GET /ws/stats 从这里读
+    connections: ConnectionRegistry,
+}
+
+impl AppState {
+    fn new() -> Self {
+        AppState {
+            db: Mutex::new(HashMap::new()),
+            min_client_version: MIN_CLIENT_VERSION,
+            user_events: broadcast::channel(64).0,
+            mempool: TxBroadcaster::new(1024),
+            connections: ConnectionRegistry::new(),
+        }
+    }
+
+    // REST 和 GraphQL 共用的创建用户逻辑：写库 + 广播事件
+    fn create_user(&self, username: String, age: u8) -> User {
+        let mut db = self.db.lock().unwrap(); //以此获取写锁
+
+        let new_id = (db.len() as u64) + 1;
+        let new_user = User {
+            id: new_id,
+            username,
+            age,
+        };
+
+        db.insert(new_id, new_user.clone());
+
+        // 没有订阅者时 send 会返回 Err，忽略即可
+        let _ = self.user_events.send(new_user.clone());
+        new_user
+    }
+}
+
+// --- 自定义 Header: X-Client-Version: 1.2.3 ---
+// 实现 headers::Header 之后就可以用 TypedHeader<UserAgentVersion> 直接提取，
+// 解析失败 / 缺失时 TypedHeader 自己会返回 400
+static X_CLIENT_VERSION: HeaderName = HeaderName::from_static("x-client-version");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UserAgentVersion(Version);
+
+impl headers::Header for UserAgentVersion {
+    fn name() -> &'static HeaderName {
+        &X_CLIENT_VERSION
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let value = values.next().ok_or_else(headers::Error::invalid)?;
+        let value = value.to_str().map_err(|_| headers::Error::invalid())?;
+        Version::parse(value.trim())
+            .map(UserAgentVersion)
+            .map_err(|_| headers::Error::invalid())
+    }
+
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        // semver 的 Display 只会输出 ASCII，不会失败
+        let value = HeaderValue::from_str(&self.0.to_string()).unwrap();
+        values.extend(std::iter::once(value));
+    }
+}
+
+// TypedHeader 拿不到 State，比较最低版本要再包一层提取器
+// 放在 handler 参数里就自动生效：版本太旧直接 426，不会进入 handler
+struct ClientVersion(Version);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for ClientVersion {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let TypedHeader(UserAgentVersion(version)) =
+            TypedHeader::<UserAgentVersion>::from_request_parts(parts, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+
+        if version < state.min_client_version {
+            return Err((
+                StatusCode::UPGRADE_REQUIRED,
+                [(header::UPGRADE, "x-my-protocol/2.0")],
+                format!(
+                    "客户端版本 {} 太旧，最低要求 {}",
+                    version, state.min_client_version
+                ),
+            )
+                .into_response());
+        }
+
+        Ok(ClientVersion(version))
+    }
+}
+
+// POST /graphql 交给 GraphQL service 处理，没有 handler 参数可以放 ClientVersion，
+// 只能在中间件里跑一遍这个提取器：版本太旧直接 426，请求到不了 GraphQL
+async fn require_client_version(_version: ClientVersion, req: Request, next: Next) -> Response {
+    next.run(req).await
 }
 
 // --- 3. Handlers (业务逻辑) ---
@@ -98,19 +323,12 @@ struct AppState {
 async fn create_user(
     // 1. 获取状态 (必须是 Clone 的，所以我们用 Arc)
     State(state): State<Arc<AppState>>,
+    // 校验客户端版本，只要出现在参数里就行
+    _version: ClientVersion,
     // 2. 解析 JSON Body
     Json(payload): Json<CreateUserPayload>,
 ) -> impl IntoResponse {
-    let mut db = state.db.lock().unwrap(); //以此获取写锁
-
-    let new_id = (db.len() as u64) + 1;
-    let new_user = User {
-        id: new_id,
-        username: payload.username,
-        age: payload.age,
-    };
-
-    db.insert(new_id, new_user.clone());
+    let new_user = state.create_user(payload.username, payload.age);
 
     // 返回 201 Created 和 创建的用户数据
     (StatusCode::CREATED, Json(new_user))
@@ -154,13 +372,17 @@ async fn handler_404() -> impl IntoResponse {
 
 // --- 1. 定义通信协议 (JSON 格式) ---
 
+// topic 用 SmolStr 而不是 String：
+// "mempool"、"price_feed_BTCUSDT" 这种 topic 都很短（<= 23 字节），SmolStr 直接存在结构体里，不分配堆内存；
+// 同一个 topic 在订阅集合、回复消息里各有一份，clone 也只是拷贝 24 个字节
+
 // 客户端发送给服务器的消息
 #[derive(Deserialize, Debug)]
 #[serde(tag = "type", rename_all = "lowercase")] // 这样 JSON 会长这样: {"type": "ping"}
 enum ClientMsg {
     Ping,
-    Subscribe { topic: String },
-    Unsubscribe { topic: String },
+    Subscribe { topic: SmolStr },
+    Unsubscribe { topic: SmolStr },
 }
 
 // 服务器回复给客户端的消息
@@ -168,79 +390,524 @@ enum ClientMsg {
 #[serde(tag = "type", rename_all = "lowercase")]
 enum ServerMsg {
     Pong,
-    Subscribed { topic: String },
-    Unsubscribed { topic: String },
+    Subscribed { topic: SmolStr },
+    Unsubscribed { topic: SmolStr },
     Error { msg: String },
+    // 推送给订阅了 mempool 的连接：{"type":"tx","hash":"0x..","nonce":1}
+    Tx { hash: String, nonce: u64 },
+}
+
+impl From<PendingTx> for ServerMsg {
+    fn from(tx: PendingTx) -> Self {
+        ServerMsg::Tx {
+            hash: tx.hash,
+            nonce: tx.nonce,
+        }
+    }
 }
 
 // --- 2. WebSocket 握手处理 ---
 
 // 这个 Handler 负责处理 HTTP 升级到 WebSocket 的握手请求
-async fn ws_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
+async fn ws_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> impl IntoResponse {
     // on_upgrade 接受一个闭包，这个闭包里写具体的 socket 处理逻辑
-    ws.on_upgrade(handle_socket)
+    ws.on_upgrade(move |socket| handle_socket(socket, state))
 }
 
 // --- 3. 具体的连接逻辑 ---
-async fn handle_socket(mut socket: WebSocket) {
+async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     println!("新连接已建立");
 
-    // 【关键点】：这是属于“当前连接”的私有状态
-    // 用 HashSet 存储该连接订阅的所有 topic，避免重复订阅
-    let mut subscribed_topics: HashSet<String> = HashSet::new();
-    // 循环接收消息
-    while let Some(msg) = socket.recv().await {
-        let msg = if let Ok(msg) = msg {
-            msg
-        } else {
-            // 客户端断开连接
-            println!("客户端断开连接");
-            return;
-        };
-
-        if let Message::Text(text) = msg {
-            // 1. 解析客户端发来的 JSON
-            let client_msg: Result<ClientMsg, _> = serde_json::from_str(&text);
+    // 【关键点】：这是属于“当前连接”的状态，订阅的 topic 和消息计数都在里面
+    // 放进 registry 是为了让 GET /ws/stats 也能读到，写入只有这个连接自己，见 connection.rs
+    let (id, conn) = state.connections.register();
+    run_socket(socket, &state, &conn).await;
+    state.connections.unregister(id);
+}
 
-            match client_msg {
-                Ok(cmd) => {
+async fn run_socket(mut socket: WebSocket, state: &AppState, conn: &ConnectionState) {
+    // 每个连接一个交易 Receiver，没订阅 mempool 时收到的交易直接丢掉
+    let mut mempool_rx = state.mempool.subscribe();
+
+    loop {
+        // 同时等两件事：客户端发来的消息，广播器推来的交易
+        let response = tokio::select! {
+            msg = socket.recv() => {
+                let msg = if let Some(Ok(msg)) = msg {
+                    msg
+                } else {
+                    // 客户端断开连接
+                    println!("客户端断开连接");
+                    return;
+                };
+
+                let Message::Text(text) = msg else {
+                    continue;
+                };
+                conn.record_msg();
+
+                // 1. 解析客户端发来的 JSON
+                let client_msg = serde_json::from_str::<ClientMsg>(&text);
+
+                match client_msg {
                     // 2. 根据指令处理逻辑
-                    let response = match cmd {
+                    Ok(cmd) => match cmd {
                         ClientMsg::Ping => {
                             println!("收到 Ping");
+                            conn.record_ping();
                             ServerMsg::Pong
                         }
                         ClientMsg::Subscribe { topic } => {
                             println!("收到订阅: {}", topic);
-                            // 保存 topic 到 HashSet
-                            subscribed_topics.insert(topic.clone());
+                            // 保存 topic 到 HashSet，重复订阅不会存两份
+                            conn.subscribe(topic.clone());
                             ServerMsg::Subscribed { topic }
                         }
                         ClientMsg::Unsubscribe { topic } => {
                             println!("收到取消订阅: {}", topic);
                             // 从 HashSet 删除 topic
-                            subscribed_topics.remove(&topic);
+                            conn.unsubscribe(&topic);
                             ServerMsg::Unsubscribed { topic }
                         }
-                    };
-
-                    // 3. 发送响应回客户端
-                    let response_text = serde_json::to_string(&response).unwrap();
-                    if socket.send(Message::Text(response_text)).await.is_err() {
-                        println!("发送消息失败，可能连接已断开");
-                        break;
-                    }
-                }
-                Err(_) => {
+                    },
                     // JSON 格式不对
-                    let err_msg = ServerMsg::Error {
+                    Err(_) => ServerMsg::Error {
                         msg: "无效的 JSON 格式".into(),
-                    };
-                    let _ = socket
-                        .send(Message::Text(serde_json::to_string(&err_msg).unwrap()))
-                        .await;
+                    },
                 }
             }
+            pending = mempool_rx.recv() => match pending {
+                Ok(tx) if conn.is_subscribed(MEMPOOL_TOPIC) => tx.into(),
+                // 没订阅，或者这个连接太慢落后了（Lagged），跳过
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        };
+
+        // 3. 发送响应回客户端
+        let response_text = serde_json::to_string(&response).unwrap();
+        if socket.send(Message::Text(response_text)).await.is_err() {
+            println!("发送消息失败，可能连接已断开");
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use axum::body::Body;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt; // oneshot
+
+    use super::*;
+
+    fn test_app() -> Router {
+        app(Arc::new(AppState::new()))
+    }
+
+    fn post_json(uri: &str, content_type: &str, body: String) -> Request {
+        post_json_with_version(uri, content_type, body, "1.2.3")
+    }
+
+    fn post_json_with_version(
+        uri: &str,
+        content_type: &str,
+        body: String,
+        version: &str,
+    ) -> Request {
+        Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(&X_CLIENT_VERSION, version)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_valid_json_ok() {
+        let body = r#"{"id":1,"username":"alice","age":18}"#.to_string();
+        let res = test_app()
+            .oneshot(post_json("/json", "application/json", body.clone()))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let bytes = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(bytes, body.as_bytes());
+
+        let res = test_app()
+            .oneshot(post_json(
+                "/users",
+                "application/json; charset=utf-8",
+                r#"{"username":"bob","age":20}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_body_too_large() {
+        // 5KB 的合法 JSON
+        let name = "a".repeat(5 * 1024);
+        let body = format!(r#"{{"id":1,"username":"{name}","age":18}}"#);
+        let res = test_app()
+            .oneshot(post_json("/json", "application/json", body))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_not_json_content_type() {
+        let res = test_app()
+            .oneshot(post_json("/json", "text/plain", "hello".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        // GET 不检查 Content-Type
+        let req = Request::builder()
+            .uri("/users")
+            .body(Body::empty())
+            .unwrap();
+        let res = test_app().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_ws_route_exempt() {
+        // 不是真正的 WebSocket 握手，WebSocketUpgrade 提取器会拒绝，
+        // 但拒绝原因不能是 415 / 413，说明请求绕过了那两层
+        let req = Request::builder()
+            .uri("/ws")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(Body::from("x".repeat(5 * 1024)))
+            .unwrap();
+        let res = test_app().oneshot(req).await.unwrap();
+        assert_ne!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_ne!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_client_version() {
+        let body = r#"{"username":"bob","age":20}"#;
+
+        // 太旧：426 + Upgrade 头
+        let res = test_app()
+            .oneshot(post_json_with_version(
+                "/users",
+                "application/json",
+                body.to_string(),
+                "0.9.5",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(res.headers()[header::UPGRADE], "x-my-protocol/2.0");
+
+        // 刚好等于最低版本 / 更新的版本：通过
+        for version in ["1.0.0", "2.3.4"] {
+            let res = test_app()
+                .oneshot(post_json_with_version(
+                    "/users",
+                    "application/json",
+                    body.to_string(),
+                    version,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::CREATED);
+        }
+
+        // 不是合法的 semver：400
+        let res = test_app()
+            .oneshot(post_json_with_version(
+                "/json",
+                "application/json",
+                r#"{"id":1,"username":"alice","age":18}"#.to_string(),
+                "v1",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_graphql_http() {
+        let app = test_app();
+
+        let res = app
+            .clone()
+            .oneshot(post_json(
+                "/graphql",
+                "application/json",
+                r#"{"query":"mutation { createUser(username: \"carol\", age: 30) { id } }"}"#
+                    .to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = app
+            .oneshot(post_json(
+                "/graphql",
+                "application/json",
+                r#"{"query":"{ user(id: 1) { username age } }"}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        let bytes = res.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "data": { "user": { "username": "carol", "age": 30 } } })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_graphql_client_version() {
+        let state = Arc::new(AppState::new());
+
+        // 太旧：426，mutation 不会执行
+        let res = app(state.clone())
+            .oneshot(post_json_with_version(
+                "/graphql",
+                "application/json",
+                r#"{"query":"mutation { createUser(username: \"carol\", age: 30) { id } }"}"#
+                    .to_string(),
+                "0.9.5",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(res.headers()[header::UPGRADE], "x-my-protocol/2.0");
+        assert!(state.db.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mempool_broadcast() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::{connect_async, tungstenite::Message as WsMessage};
+
+        let state = Arc::new(AppState::new());
+
+        // 真的监听一个端口，WebSocket 客户端要走完整的握手
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, app(state.clone())).into_future());
+
+        // 读下一条文本消息，解析成 JSON
+        async fn next_json<S>(ws: &mut S) -> serde_json::Value
+        where
+            S: StreamExt<Item = Result<WsMessage, tokio_tungstenite::tungstenite::Error>> + Unpin,
+        {
+            let msg = tokio::time::timeout(std::time::Duration::from_secs(2), ws.next())
+                .await
+                .expect("等待 WebSocket 消息超时")
+                .unwrap()
+                .unwrap();
+            serde_json::from_str(msg.to_text().unwrap()).unwrap()
+        }
+
+        // 两个客户端都订阅 mempool，第三个只订阅别的 topic
+        let mut clients = Vec::new();
+        for topic in ["mempool", "mempool", "blocks"] {
+            let (mut ws, _) = connect_async(format!("ws://{addr}/ws")).await.unwrap();
+            let sub = format!(r#"{{"type":"subscribe","topic":"{topic}"}}"#);
+            ws.send(WsMessage::Text(sub)).await.unwrap();
+            assert_eq!(
+                next_json(&mut ws).await,
+                serde_json::json!({ "type": "subscribed", "topic": topic })
+            );
+            clients.push(ws);
+        }
+
+        // hash 为空：400，谁也收不到
+        let res = app(state.clone())
+            .oneshot(post_json(
+                "/mempool/broadcast",
+                "application/json",
+                r#"{"hash":"","nonce":1}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        // nonce 是负数：反序列化失败
+        let res = app(state.clone())
+            .oneshot(post_json(
+                "/mempool/broadcast",
+                "application/json",
+                r#"{"hash":"0xabc","nonce":-1}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // 客户端版本太旧：426，交易不会广播（否则下面收到的第一条就是它）
+        let res = app(state.clone())
+            .oneshot(post_json_with_version(
+                "/mempool/broadcast",
+                "application/json",
+                r#"{"hash":"0xold","nonce":1}"#.to_string(),
+                "0.9.5",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UPGRADE_REQUIRED);
+
+        // 和监听端口的服务共用同一个 AppState，这里直接 oneshot 就能触发广播
+        let res = app(state.clone())
+            .oneshot(post_json(
+                "/mempool/broadcast",
+                "application/json",
+                r#"{"hash":"0xabc","nonce":7}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+
+        let expected = serde_json::json!({ "type": "tx", "hash": "0xabc", "nonce": 7 });
+        assert_eq!(next_json(&mut clients[0]).await, expected);
+        assert_eq!(next_json(&mut clients[1]).await, expected);
+
+        // 没订阅 mempool 的客户端收不到交易，下一条是它自己的 pong
+        clients[2]
+            .send(WsMessage::Text(r#"{"type":"ping"}"#.into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            next_json(&mut clients[2]).await,
+            serde_json::json!({ "type": "pong" })
+        );
+
+        // 监控接口能看到三个连接各自的消息数，只有第三个发过 ping
+        let req = Request::builder()
+            .uri("/ws/stats")
+            .body(Body::empty())
+            .unwrap();
+        let res = app(state.clone()).oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let stats = stats.as_array().unwrap();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0]["msg_count"], 1);
+        assert_eq!(stats[0]["last_ping"], 0);
+        assert_eq!(stats[2]["msg_count"], 2);
+        assert!(stats[2]["last_ping"].as_u64().unwrap() > 0);
+
+        // 断开之后从监控里消失
+        clients.pop().unwrap().close(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(state.connections.stats().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_strip_sensitive_headers() {
+        // handler 故意带上要去掉的头，X-Frame-Options 也设了一个更宽松的值
+        async fn leaky() -> impl IntoResponse {
+            (
+                [
+                    (header::SERVER, "axum/0.7"),
+                    (HeaderName::from_static("x-powered-by"), "Rust"),
+                    (HeaderName::from_static("x-internal-trace-id"), "abc123"),
+                    (header::X_FRAME_OPTIONS, "SAMEORIGIN"),
+                ],
+                "ok",
+            )
         }
+        let app = Router::new()
+            .route("/leaky", get(leaky))
+            .layer(strip_sensitive_headers_layer());
+
+        let req = Request::builder()
+            .uri("/leaky")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let headers = res.headers();
+        for name in ["server", "x-powered-by", "x-internal-trace-id"] {
+            assert!(headers.get(name).is_none(), "{name} 应该被去掉");
+        }
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+        assert_eq!(
+            headers[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=31536000"
+        );
+
+        // 真正的 app 里，连 fallback 返回的 404 也带上了安全头
+        let req = Request::builder()
+            .uri("/no-such-route")
+            .body(Body::empty())
+            .unwrap();
+        let res = test_app().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.headers()[header::X_FRAME_OPTIONS], "DENY");
+    }
+
+    // 1000 个连接，每个订阅 5 个 topic，比较两种集合额外占用的堆内存
+    // 两种元素本身都是 24 字节，哈希表的桶一样大；区别只在字符串内容：
+    // String 每个 topic 单独分配一块堆内存，SmolStr 23 字节以内直接存在元素里
+    #[test]
+    fn test_topic_set_memory() {
+        const CONNECTIONS: usize = 1000;
+        const TOPICS: [&str; 5] = [
+            "user_updates",
+            "price_feed_BTCUSDT",
+            "price_feed_ETHUSDT",
+            "mempool",
+            "blocks",
+        ];
+
+        // 和 handle_socket 里一样，topic 是从客户端发来的 JSON 里解析出来的
+        let parse = |topic: &str| {
+            let json = format!(r#"{{"type":"subscribe","topic":"{topic}"}}"#);
+            match serde_json::from_str::<ClientMsg>(&json).unwrap() {
+                ClientMsg::Subscribe { topic } => topic,
+                other => panic!("{other:?}"),
+            }
+        };
+
+        let string_sets: Vec<HashSet<String>> = (0..CONNECTIONS)
+            .map(|_| TOPICS.iter().map(|t| parse(t).to_string()).collect())
+            .collect();
+        let smol_sets: Vec<HashSet<SmolStr>> = (0..CONNECTIONS)
+            .map(|_| TOPICS.iter().map(|t| parse(t)).collect())
+            .collect();
+
+        let buckets = |capacity: usize| capacity * std::mem::size_of::<String>();
+        assert_eq!(
+            std::mem::size_of::<String>(),
+            std::mem::size_of::<SmolStr>()
+        );
+
+        let string_heap: usize = string_sets
+            .iter()
+            .map(|set| buckets(set.capacity()) + set.iter().map(String::capacity).sum::<usize>())
+            .sum();
+        let string_allocs: usize = string_sets.iter().map(HashSet::len).sum();
+
+        let smol_heap: usize = smol_sets.iter().map(|set| buckets(set.capacity())).sum();
+        let smol_allocs = smol_sets
+            .iter()
+            .flatten()
+            .filter(|t| t.is_heap_allocated())
+            .count();
+
+        println!("HashSet<String>:  {string_heap} 字节，字符串单独分配 {string_allocs} 次");
+        println!("HashSet<SmolStr>: {smol_heap} 字节，字符串单独分配 {smol_allocs} 次");
+
+        // 字符串内容一共 12 + 18 + 18 + 7 + 6 = 61 字节，String 每个连接多出这么多，还要多分配 5 次
+        assert_eq!(smol_allocs, 0);
+        assert_eq!(string_allocs, CONNECTIONS * TOPICS.len());
+        assert_eq!(string_heap - smol_heap, CONNECTIONS * 61);
     }
 }
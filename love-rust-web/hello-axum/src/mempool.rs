@@ -0,0 +1,65 @@
+// --- 交易广播：POST /mempool/broadcast 收交易，推给订阅了 mempool topic 的 WebSocket 客户端 ---
+
+use std::sync::Arc;
+
+use axum::{Json, extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::{AppState, ClientVersion};
+
+// WebSocket 客户端订阅这个 topic 才会收到交易
+pub const MEMPOOL_TOPIC: &str = "mempool";
+
+// 待广播的交易，只保留客户端关心的两个字段
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PendingTx {
+    pub hash: String,
+    // u64 天然 >= 0，负数在 Json 反序列化阶段就会被拒绝（422）
+    pub nonce: u64,
+}
+
+// 交易广播器：一个 broadcast channel，每个 WebSocket 连接各持有一个 Receiver
+// 和 AppState 里的 user_events 是同一个套路
+pub struct TxBroadcaster {
+    tx: broadcast::Sender<PendingTx>,
+}
+
+impl TxBroadcaster {
+    pub fn new(capacity: usize) -> Self {
+        TxBroadcaster {
+            tx: broadcast::channel(capacity).0,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PendingTx> {
+        self.tx.subscribe()
+    }
+
+    // 校验通过后广播；是否推给某个连接由连接自己按订阅的 topic 过滤
+    pub fn broadcast(&self, pending: PendingTx) -> Result<(), String> {
+        if pending.hash.trim().is_empty() {
+            return Err("hash 不能为空".into());
+        }
+
+        // 没有任何连接时 send 返回 Err，不算失败，只是没人收到
+        let _ = self.tx.send(pending);
+        Ok(())
+    }
+}
+
+// POST /mempool/broadcast  {"hash": "0x...", "nonce": 1}
+pub async fn broadcast_tx(
+    State(state): State<Arc<AppState>>,
+    // 和 POST /users 一样，旧客户端直接 426，不会广播出去
+    _version: ClientVersion,
+    Json(pending): Json<PendingTx>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .mempool
+        .broadcast(pending)
+        .map_err(|msg| (StatusCode::BAD_REQUEST, msg))?;
+
+    // 202：已经交给广播器，不保证有客户端收到
+    Ok(StatusCode::ACCEPTED)
+}
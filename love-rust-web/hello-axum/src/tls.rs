@@ -0,0 +1,148 @@
+// --- HTTPS：设置了 TLS_CERT_PATH 和 TLS_KEY_PATH 就用 rustls 加密，否则还是普通 HTTP ---
+//
+// axum 0.7 的 axum::serve 只接受 TcpListener，没法插一层 TLS，所以这里自己写 accept 循环：
+//   TcpStream --TlsAcceptor 握手--> TlsStream --hyper--> Router
+// Router 本身就是一个 tower Service，TowerToHyperService 把它转成 hyper 要的 Service，
+// auto::Builder 根据 ALPN 协商的结果自动选 HTTP/1.1 还是 HTTP/2
+
+use std::{error::Error, fs, path::Path, sync::Arc};
+
+use axum::Router;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+    service::TowerToHyperService,
+};
+use rustls::{
+    ServerConfig,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
+pub const TLS_CERT_PATH: &str = "TLS_CERT_PATH";
+pub const TLS_KEY_PATH: &str = "TLS_KEY_PATH";
+
+// 两个环境变量都设置了返回 Some，都没设置返回 None（走 HTTP）
+// 只设置了一个多半是配置写漏了，直接报错，不要悄悄降级成明文
+pub fn config_from_env() -> Result<Option<Arc<ServerConfig>>, BoxError> {
+    match (
+        std::env::var_os(TLS_CERT_PATH),
+        std::env::var_os(TLS_KEY_PATH),
+    ) {
+        (Some(cert), Some(key)) => load_server_config(cert.as_ref(), key.as_ref()).map(Some),
+        (None, None) => Ok(None),
+        _ => Err(format!("{TLS_CERT_PATH} 和 {TLS_KEY_PATH} 必须同时设置").into()),
+    }
+}
+
+// 证书文件是 PEM 格式的证书链（服务端证书在前，中间证书在后），私钥文件是 PEM 格式的一个私钥
+pub fn load_server_config(
+    cert_path: &Path,
+    key_path: &Path,
+) -> Result<Arc<ServerConfig>, BoxError> {
+    let certs =
+        CertificateDer::pem_slice_iter(&fs::read(cert_path)?).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(format!("{} 里没有证书", cert_path.display()).into());
+    }
+    let key = PrivateKeyDer::from_pem_slice(&fs::read(key_path)?)?;
+
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth() // 不要求客户端证书
+        .with_single_cert(certs, key)?;
+    // 告诉客户端两种协议都支持，优先 HTTP/2
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+// 一直 accept，每个连接一个任务：TLS 握手、再交给 hyper 处理 HTTP
+pub async fn serve_tls(
+    listener: TcpListener,
+    app: Router,
+    config: Arc<ServerConfig>,
+) -> std::io::Result<()> {
+    let acceptor = TlsAcceptor::from(config);
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+
+        tokio::spawn(async move {
+            // 握手失败（比如客户端用明文 HTTP 连过来）只影响这一个连接
+            let tls_stream = match acceptor.accept(stream).await {
+                Ok(s) => s,
+                Err(e) => {
+                    println!("TLS 握手失败 {peer}: {e}");
+                    return;
+                }
+            };
+
+            // with_upgrades：/ws 和 GraphQL 订阅的 WebSocket 升级要用
+            let service = TowerToHyperService::new(app);
+            if let Err(e) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(tls_stream), service)
+                .await
+            {
+                println!("连接出错 {peer}: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AppState, app};
+
+    #[tokio::test]
+    async fn test_https_with_self_signed_cert() {
+        // 自签名证书写到临时目录，和真实部署一样从文件加载
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = std::env::temp_dir().join(format!("hello-axum-tls-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+        fs::write(&cert_path, cert.cert.pem()).unwrap();
+        fs::write(&key_path, cert.signing_key.serialize_pem()).unwrap();
+
+        let config = load_server_config(&cert_path, &key_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // 端口 0：让系统分配一个空闲端口，测试之间不会冲突
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(serve_tls(listener, app(Arc::new(AppState::new())), config));
+
+        // 自签名证书不在信任列表里，测试里跳过校验
+        let client = reqwest::ClientBuilder::new()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+        let res = client
+            .get(format!("https://localhost:{port}/"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            res.text().await.unwrap(),
+            "<h1> Hello, World! From Axum. </h1>"
+        );
+
+        // 明文 HTTP 连 HTTPS 端口：握手失败，服务还活着
+        assert!(
+            reqwest::get(format!("http://localhost:{port}/"))
+                .await
+                .is_err()
+        );
+        let res = client
+            .get(format!("https://localhost:{port}/users"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+    }
+}